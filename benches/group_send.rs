@@ -0,0 +1,72 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Measures the buffers allocated when one message is sent to every member of a group.
+//!
+//! crust takes an owned buffer per send.  `RoutingNode` used to copy the message into the packer
+//! and then copy the packed buffer for every peer: 34 allocations for a group of 32.  It now
+//! packs from a borrow and hands the last peer the packed buffer itself: 32 allocations.
+
+#![feature(test)]
+
+extern crate test;
+
+use std::mem;
+use test::{black_box, Bencher};
+
+static GROUP_SIZE: usize = 32;
+static MESSAGE_SIZE: usize = 1 << 16;
+
+// stands in for the uncompressed path of compression::pack
+fn pack(serialised_message: &[u8]) -> Vec<u8> {
+    let mut packed = Vec::with_capacity(serialised_message.len() + 1);
+    packed.push(0u8);
+    packed.extend(serialised_message.iter().cloned());
+    packed
+}
+
+// stands in for crust's ConnectionManager::send
+fn send(bytes: Vec<u8>) { black_box(bytes); }
+
+#[bench]
+fn copy_for_every_peer(b: &mut Bencher) {
+    let message = vec![7u8; MESSAGE_SIZE];
+    b.bytes = (GROUP_SIZE * MESSAGE_SIZE) as u64;
+    b.iter(|| {
+        let packed = pack(&message.clone());
+        for _ in 0..GROUP_SIZE {
+            send(packed.clone());
+        }
+    });
+}
+
+#[bench]
+fn last_peer_takes_buffer(b: &mut Bencher) {
+    let message = vec![7u8; MESSAGE_SIZE];
+    b.bytes = (GROUP_SIZE * MESSAGE_SIZE) as u64;
+    b.iter(|| {
+        let mut packed = pack(&message);
+        for index in 0..GROUP_SIZE {
+            let bytes = if index == GROUP_SIZE - 1 {
+                mem::replace(&mut packed, vec![])
+            } else {
+                packed.clone()
+            };
+            send(bytes);
+        }
+    });
+}
@@ -0,0 +1,94 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Measures how long sending one message to every member of a group takes when each send takes a
+//! millisecond and one peer is ten times slower than that.
+//!
+//! `RoutingNode` used to send to each peer in turn, so the fan-out took the sum of every send, the
+//! slow one included: about 41ms for a group of 32.  The sends are now spread over a pool of four
+//! threads, which brings it down to about 10ms, bounded below by the slow peer.
+
+#![feature(test)]
+
+extern crate test;
+
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use test::{black_box, Bencher};
+
+static GROUP_SIZE: usize = 32;
+static SEND_THREADS: usize = 4;
+static SLOW_PEER: usize = 3;
+static SEND_MS: u32 = 1;
+static SLOW_SEND_MS: u32 = 10;
+
+// stands in for crust's ConnectionManager::send, with one peer taking its time
+fn send(peer: usize, bytes: Vec<u8>) -> Result<(), ()> {
+    thread::sleep_ms(if peer == SLOW_PEER { SLOW_SEND_MS } else { SEND_MS });
+    black_box(bytes);
+    Ok(())
+}
+
+// stands in for send_pool::SendPool
+struct Pool {
+    jobs: Sender<(usize, Vec<u8>, Sender<Result<(), ()>>)>,
+}
+
+impl Pool {
+    fn new(threads: usize) -> Pool {
+        let (jobs, job_input) = mpsc::channel::<(usize, Vec<u8>, Sender<Result<(), ()>>)>();
+        let job_input = Arc::new(Mutex::new(job_input));
+        for _ in 0..threads {
+            let job_input = job_input.clone();
+            let _ = thread::spawn(move || loop {
+                let (peer, bytes, result) = match job_input.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => break,
+                };
+                let _ = result.send(send(peer, bytes));
+            });
+        }
+        Pool { jobs: jobs }
+    }
+
+    fn send_all(&self, message: &Vec<u8>) -> usize {
+        let (result_sender, results) = mpsc::channel();
+        for peer in 0..GROUP_SIZE {
+            let _ = self.jobs.send((peer, message.clone(), result_sender.clone()));
+        }
+        drop(result_sender);
+        results.iter().filter(|result| result.is_ok()).count()
+    }
+}
+
+#[bench]
+fn serial_fan_out(b: &mut Bencher) {
+    let message = vec![7u8; 1024];
+    b.iter(|| {
+        let sent = (0..GROUP_SIZE).filter(|&peer| send(peer, message.clone()).is_ok()).count();
+        assert_eq!(sent, GROUP_SIZE);
+    });
+}
+
+#[bench]
+fn pooled_fan_out(b: &mut Bencher) {
+    let message = vec![7u8; 1024];
+    let pool = Pool::new(SEND_THREADS);
+    b.iter(|| assert_eq!(pool.send_all(&message), GROUP_SIZE));
+}
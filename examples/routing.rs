@@ -233,11 +233,16 @@ impl Interface for TestNode {
         // return with abort to terminate the flow
         Err(InterfaceError::Abort)
     }
+    fn handle_delete(&mut self, our_authority: types::Authority, from_authority: types::Authority,
+                     from_address: NameType, dest_address: types::DestinationAddress,
+                     name: NameType) -> Result<Action, InterfaceError> {
+        Err(InterfaceError::Abort)
+    }
     fn handle_post(&mut self, our_authority: types::Authority, from_authority: types::Authority,
                    from_address: NameType, name : NameType, data: Vec<u8>) -> Result<Action, InterfaceError> {
         Err(InterfaceError::Abort)
     }
-    fn handle_get_response(&mut self, from_address: NameType,
+    fn handle_get_response(&mut self, from_authority: types::Authority, from_address: NameType,
                            response: Result<Vec<u8>, ResponseError>) -> routing::node_interface::RoutingNodeAction {
         if response.is_ok() {
             let mut d = cbor::Decoder::from_bytes(response.unwrap());
@@ -262,7 +267,7 @@ impl Interface for TestNode {
     }
     fn handle_churn(&mut self, close_group: Vec<NameType>)
         -> Vec<routing::node_interface::RoutingNodeAction> {
-        unimplemented!();
+        vec![]
     }
     fn handle_cache_get(&mut self, type_id: u64, name : NameType, from_authority: types::Authority,
                         from_address: NameType) -> Result<Action, InterfaceError> {
@@ -0,0 +1,209 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Every message on the wire is prefixed with an envelope: a magic constant identifying routing
+//! traffic, the wire format version, the serialisation the message is in and a flag byte saying
+//! whether the rest of the message has been deflated.  Only messages above a size threshold are
+//! compressed.
+
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use std::io;
+use std::io::{Read, Write};
+use error::RoutingError;
+use serialisation::Serialisation;
+
+type Bytes = Vec<u8>;
+
+/// Marks the start of every routing message
+static MAGIC: &'static [u8] = b"MR";
+/// Version of the wire format, bumped whenever the envelope or message encoding changes
+static WIRE_VERSION: u8 = 3;
+/// Length of the envelope: magic, version, serialisation and flag
+pub static ENVELOPE_LEN: usize = 5;
+/// Flag byte of a message sent as it is
+static PLAIN: u8 = 0;
+/// Flag byte of a message which has been deflated
+static DEFLATED: u8 = 1;
+
+/// Prepares a CBOR serialised message for the wire, deflating it if `threshold` is given and the
+/// message is larger than that.  A message which doesn't shrink is sent as it is.
+pub fn pack(serialised_message: &[u8], threshold: Option<usize>) -> Bytes {
+    pack_as(serialised_message, Serialisation::Cbor, threshold)
+}
+
+/// As `pack`, for a message in `serialisation`.
+pub fn pack_as(serialised_message: &[u8], serialisation: Serialisation, threshold: Option<usize>)
+               -> Bytes {
+    let compress = threshold.map(|threshold| serialised_message.len() > threshold)
+                            .unwrap_or(false);
+    if compress {
+        let mut encoder = ZlibEncoder::new(envelope(serialisation, DEFLATED), Compression::Default);
+        let deflated = encoder.write_all(serialised_message).and_then(|_| encoder.finish());
+        if let Ok(deflated) = deflated {
+            if deflated.len() < serialised_message.len() {
+                return deflated;
+            }
+        }
+    }
+    let mut packed = Vec::with_capacity(serialised_message.len() + ENVELOPE_LEN);
+    packed.extend(envelope(serialisation, PLAIN).into_iter());
+    packed.extend(serialised_message.iter().cloned());
+    packed
+}
+
+/// Recovers the CBOR serialised message from what `pack` put on the wire.  Anything not starting
+/// with our magic is refused before any attempt to decode it, as is a message in another wire
+/// format version.  A message larger than `max_size` once inflated is refused too; inflating stops
+/// as soon as the limit is passed.
+pub fn unpack(packed: &Bytes, max_size: usize) -> Result<Bytes, RoutingError> {
+    unpack_as(packed, Serialisation::Cbor, max_size)
+}
+
+/// As `unpack`, for a message expected in `serialisation`.  One in any other is refused with
+/// `SerialisationMismatch`.
+pub fn unpack_as(packed: &Bytes, serialisation: Serialisation, max_size: usize)
+                 -> Result<Bytes, RoutingError> {
+    if packed.len() < ENVELOPE_LEN || &packed[..MAGIC.len()] != MAGIC {
+        return Err(RoutingError::NotARoutingMessage);
+    }
+    let version = packed[MAGIC.len()];
+    if version != WIRE_VERSION {
+        return Err(RoutingError::IncompatibleProtocolVersion(version as u32));
+    }
+    let format = packed[MAGIC.len() + 1];
+    if format != serialisation.format_byte() {
+        return Err(RoutingError::SerialisationMismatch(format));
+    }
+    let flag = packed[ENVELOPE_LEN - 1];
+    let body = &packed[ENVELOPE_LEN..];
+    if flag == PLAIN {
+        if body.len() > max_size {
+            return Err(RoutingError::MessageTooLarge(body.len()));
+        }
+        Ok(body.to_vec())
+    } else if flag == DEFLATED {
+        let mut serialised_message = Vec::new();
+        try!(ZlibDecoder::new(body).take(max_size as u64 + 1)
+                                   .read_to_end(&mut serialised_message));
+        if serialised_message.len() > max_size {
+            return Err(RoutingError::MessageTooLarge(serialised_message.len()));
+        }
+        Ok(serialised_message)
+    } else {
+        Err(From::from(io::Error::new(io::ErrorKind::InvalidInput, "unknown message flag")))
+    }
+}
+
+fn envelope(serialisation: Serialisation, flag: u8) -> Bytes {
+    let mut envelope = MAGIC.to_vec();
+    envelope.push(WIRE_VERSION);
+    envelope.push(serialisation.format_byte());
+    envelope.push(flag);
+    envelope
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use error::RoutingError;
+    use serialisation::Serialisation;
+    use std::io;
+    use types::{generate_random_vec_u8, MAX_MESSAGE_SIZE};
+
+    #[test]
+    fn small_messages_sent_plain() {
+        let message = vec![7u8; 100];
+        let packed = pack(&message, Some(1000));
+        assert_eq!(packed.len(), message.len() + ENVELOPE_LEN);
+        assert_eq!(unpack(&packed, MAX_MESSAGE_SIZE).unwrap(), message);
+
+        // nor is anything compressed when compression is off
+        let message = vec![7u8; 10000];
+        assert_eq!(pack(&message, None).len(), message.len() + ENVELOPE_LEN);
+    }
+
+    #[test]
+    fn large_messages_deflated() {
+        let message = vec![7u8; 10000];
+        let packed = pack(&message, Some(1000));
+        assert!(packed.len() < message.len());
+        assert_eq!(unpack(&packed, MAX_MESSAGE_SIZE).unwrap(), message);
+
+        // incompressible data is sent as it is
+        let message = generate_random_vec_u8(10000);
+        let packed = pack(&message, Some(1000));
+        assert_eq!(packed.len(), message.len() + ENVELOPE_LEN);
+        assert_eq!(unpack(&packed, MAX_MESSAGE_SIZE).unwrap(), message);
+    }
+
+    #[test]
+    fn oversized_messages_refused() {
+        let message = vec![7u8; 10000];
+        for threshold in vec![None, Some(1000)].into_iter() {
+            let packed = pack(&message, threshold);
+            match unpack(&packed, 9999) {
+                Err(RoutingError::MessageTooLarge(size)) => assert!(size > 9999),
+                _ => panic!(),
+            }
+            assert_eq!(unpack(&packed, 10000).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn unknown_flag() {
+        let mut packed = pack(&[1u8, 2, 3], None);
+        packed[ENVELOPE_LEN - 1] = 9;
+        match unpack(&packed, MAX_MESSAGE_SIZE) {
+            Err(RoutingError::Io(kind)) => assert_eq!(kind, io::ErrorKind::InvalidInput),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn foreign_traffic_refused() {
+        for garbage in vec![vec![], vec![0x4du8], generate_random_vec_u8(100)].iter() {
+            match unpack(garbage, MAX_MESSAGE_SIZE) {
+                Err(RoutingError::NotARoutingMessage) => (),
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn other_serialisation_refused() {
+        let packed = pack_as(&[1u8, 2, 3], Serialisation::Bincode, None);
+        assert_eq!(unpack_as(&packed, Serialisation::Bincode, MAX_MESSAGE_SIZE).unwrap(), vec![1u8, 2, 3]);
+        match unpack(&packed, MAX_MESSAGE_SIZE) {
+            Err(RoutingError::SerialisationMismatch(format)) =>
+                assert_eq!(format, Serialisation::Bincode.format_byte()),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn wrong_version_refused() {
+        let mut packed = pack(&[1u8, 2, 3], None);
+        let newer = packed[2] + 1;
+        packed[2] = newer;
+        match unpack(&packed, MAX_MESSAGE_SIZE) {
+            Err(RoutingError::IncompatibleProtocolVersion(version)) => assert_eq!(version, newer as u32),
+            _ => panic!(),
+        }
+    }
+}
@@ -2,12 +2,17 @@
 use std::io;
 use std::convert::From;
 use cbor::CborError;
+use NameType;
+use messages::MessageTypeTag;
 
 //------------------------------------------------------------------------------
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub enum ResponseError {
     NoData,
     InvalidRequest,
+    TooLarge,
+    /// no response arrived in time
+    Timeout,
 }
 
 //------------------------------------------------------------------------------
@@ -26,15 +31,45 @@ impl From<ResponseError> for InterfaceError {
 //------------------------------------------------------------------------------
 #[derive(Debug)]
 pub enum RoutingError {
-    Other, // TODO: Discuss: we probably don't need this error
     BadAuthority,
     AlreadyConnected,
     UnknownMessageType,
     FilterCheckFailed,
     FailedToBootstrap,
+    NotBootstrapped,
+    NotSent,
+    SignatureCheckFailed,
+    /// the public sign key of the given sender wasn't learnt in time to check its message
+    UnknownSender(NameType),
+    RelayTargetNotConnected(NameType),
+    /// a response to relay arrived after the node it was relayed for had disconnected
+    NoReplyEndpoint,
+    /// a close group must have at least one member
+    InvalidGroupSize,
+    /// every message id tried is still awaiting a reply or held in the message filter
+    NoFreeMessageId,
+    NotConnected(NameType),
+    RateLimitExceeded(NameType),
+    TooManyNodesOnIp,
+    IncompatibleProtocolVersion(u32),
+    /// what arrived isn't a routing message at all
+    NotARoutingMessage,
+    /// the message has been sent on as many times as its header allows
+    TtlExpired,
+    /// the message claims to come from us, without being relayed for one of our clients
+    MessageFromSelf,
+    /// the serialised message, of the given size, is over the size limit
+    MessageTooLarge(usize),
+    /// the message is in the serialisation with the given format byte, which isn't ours
+    SerialisationMismatch(u8),
+    /// bincode failed to encode or decode a message
+    BincodeError(String),
     Interface(InterfaceError),
-    Io(io::Error),
+    /// an io error, by kind, so callers can tell e.g. a refused connection from a broken pipe
+    Io(io::ErrorKind),
     CborError(CborError),
+    /// the body of a message of the given type failed to decode
+    MalformedMessage { type_tag: MessageTypeTag, source: CborError },
     Response(ResponseError),
 }
 
@@ -47,7 +82,7 @@ impl From<CborError> for RoutingError {
 }
 
 impl From<io::Error> for RoutingError {
-    fn from(e: io::Error) -> RoutingError { RoutingError::Io(e) }
+    fn from(e: io::Error) -> RoutingError { RoutingError::Io(e.kind()) }
 }
 
 impl From<InterfaceError> for RoutingError {
@@ -0,0 +1,43 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use NameType;
+use error::RoutingError;
+
+/// Notable occurrences in a `RoutingNode`, for applications building monitoring or a UI on top of
+/// the node.  Subscribe with `RoutingConfig::events` when constructing the node.
+#[derive(Debug)]
+pub enum RoutingEvent {
+    /// A routing table peer connected to us
+    Connected(NameType),
+    /// A connected peer was lost or dropped
+    Disconnected(NameType),
+    /// A message was discarded, either received and failed to be handled or failed to be sent
+    MessageDropped(RoutingError),
+    /// We have bootstrapped off the given node
+    Bootstrapped(NameType),
+    /// Nobody but our bootstrap node is close to us once bootstrapped: we are starting the network
+    FirstNode,
+    /// Our close group changed and the interface is being told of the churn
+    ChurnStarted,
+    /// The connection manager has gone away; the node has stopped and should be dropped
+    TransportClosed,
+    /// A lost routing table peer couldn't be reconnected to and has been given up on
+    ReconnectFailed(NameType),
+    /// The node couldn't start listening when constructed; it can still connect out to peers
+    ListeningFailed(RoutingError),
+}
@@ -55,15 +55,20 @@ extern crate crust;
 extern crate accumulator;
 extern crate lru_time_cache;
 extern crate message_filter;
+extern crate flate2;
+extern crate bincode;
 
 mod common_bits;
+mod compression;
 mod macros;
 mod message_header;
 mod messages;
 mod frequency;
 mod name_type;
 mod routing_table;
+mod send_pool;
 mod sentinel;
+mod serialisation;
 
 pub mod client_interface;
 pub mod node_interface;
@@ -73,11 +78,15 @@ pub mod sendable;
 pub mod test_utils;
 pub mod types;
 pub mod error;
+pub mod event;
 
 use sodiumoxide::crypto;
 
 /// NameType is a 512bit name to address elements on the DHT network.
 pub use name_type::{NameType, closer_to_target};
+/// Tags identifying each routing message type, e.g. for `Interface::allow_send`.
+pub use messages::{MessageTypeTag, MessageStats};
+pub use serialisation::Serialisation;
 
 //#[derive(RustcEncodable, RustcDecodable)]
 struct SignedKey {
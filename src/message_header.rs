@@ -21,29 +21,35 @@ use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
 use types;
 use NameType;
 
+/// Number of times a new message may be sent on before it is dropped.  Generous compared to the
+/// few groups a message passes through on its way to the managed node.
+pub static DEFAULT_TTL: u8 = 32;
+
 /// Header of various message types used on routing level
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
 pub struct MessageHeader {
     pub message_id: types::MessageId,
     pub destination: types::DestinationAddress,
     pub source: types::SourceAddress,
-    pub authority: types::Authority
+    pub authority: types::Authority,
+    /// remaining number of times the message may be sent on; see `create_send_on`
+    pub ttl: u8
 }
 
 impl Encodable for MessageHeader {
     fn encode<E: Encoder>(&self, e: &mut E)->Result<(), E::Error> {
         CborTagEncode::new(5483_004,
                            &(&self.message_id, &self.destination, &self.source,
-                             &self.authority)).encode(e)
+                             &self.authority, &self.ttl)).encode(e)
     }
 }
 
 impl Decodable for MessageHeader {
     fn decode<D: Decoder>(d: &mut D)->Result<MessageHeader, D::Error> {
         try!(d.read_u64());
-        let (message_id, destination, source, authority) = try!(Decodable::decode(d));
+        let (message_id, destination, source, authority, ttl) = try!(Decodable::decode(d));
         Ok(MessageHeader{ message_id : message_id, destination : destination,
-            source : source, authority : authority })
+            source : source, authority : authority, ttl : ttl })
     }
 }
 
@@ -54,7 +60,7 @@ impl MessageHeader {
                authority : types::Authority) -> MessageHeader {
         MessageHeader {
             message_id : message_id, destination : destination,
-            source : source, authority : authority
+            source : source, authority : authority, ttl : DEFAULT_TTL
         }
     }
 
@@ -123,6 +129,7 @@ impl MessageHeader {
     /// and then mutates the destination and source accordingly.
     /// Authority is changed at this point as this method is called after
     /// the interface has processed the message.
+    /// Each send on uses up one unit of the message's ttl.
     /// Note: this is not for XOR-forwarding; then the header is preserved!
     pub fn create_send_on(&self, our_name : &NameType, our_authority : &types::Authority,
                           destination : &NameType) -> MessageHeader {
@@ -138,6 +145,7 @@ impl MessageHeader {
             reply_to : self.destination.reply_to.clone()
         };
         send_on_header.authority = our_authority.clone();
+        send_on_header.ttl = self.ttl.saturating_sub(1);
         send_on_header
     }
 
@@ -190,6 +198,23 @@ mod test {
                                                      reply_to: None },
             source : types::SourceAddress { from_node : Random::generate_random(),
                                             from_group : None, reply_to: None },
-            authority : types::Authority::ManagedNode });
+            authority : types::Authority::ManagedNode,
+            ttl : DEFAULT_TTL });
+    }
+
+    #[test]
+    fn send_on_uses_up_ttl() {
+        let header = MessageHeader::new(random::<u32>(),
+            types::DestinationAddress { dest: Random::generate_random(), reply_to: None },
+            types::SourceAddress { from_node: Random::generate_random(), from_group: None,
+                                   reply_to: None },
+            types::Authority::ClientManager);
+        assert_eq!(header.ttl, DEFAULT_TTL);
+        let send_on_header = header.create_send_on(&Random::generate_random(),
+            &types::Authority::NaeManager, &Random::generate_random());
+        assert_eq!(send_on_header.ttl, DEFAULT_TTL - 1);
+        // a reply doesn't use any up
+        assert_eq!(send_on_header.create_reply(&Random::generate_random(),
+                                               &types::Authority::NaeManager).ttl, DEFAULT_TTL - 1);
     }
 }
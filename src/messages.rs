@@ -27,6 +27,10 @@ pub mod connect_request;
 pub mod connect_response;
 #[path="messages/connect_success.rs"]
 pub mod connect_success;
+#[path="messages/delete_data.rs"]
+pub mod delete_data;
+#[path="messages/delete_data_response.rs"]
+pub mod delete_data_response;
 #[path="messages/find_group.rs"]
 pub mod find_group;
 #[path="messages/find_group_response.rs"]
@@ -43,12 +47,18 @@ pub mod get_data_response;
 pub mod get_group_key;
 #[path="messages/get_group_key_response.rs"]
 pub mod get_group_key_response;
+#[path="messages/ping.rs"]
+pub mod ping;
 #[path="messages/post.rs"]
 pub mod post;
+#[path="messages/post_response.rs"]
+pub mod post_response;
 #[path="messages/put_data.rs"]
 pub mod put_data;
 #[path="messages/put_data_response.rs"]
 pub mod put_data_response;
+#[path="messages/put_key.rs"]
+pub mod put_key;
 #[path="messages/put_public_pmid.rs"]
 pub mod put_public_pmid;
 
@@ -68,6 +78,8 @@ pub enum MessageTypeTag {
     BootstrapIdResponse,
     ConnectRequest,
     ConnectResponse,
+    DeleteData,
+    DeleteDataResponse,
     FindGroup,
     FindGroupResponse,
     GetData,
@@ -76,6 +88,8 @@ pub enum MessageTypeTag {
     GetKeyResponse,
     GetGroupKey,
     GetGroupKeyResponse,
+    Ping,
+    PingResponse,
     Post,
     PostResponse,
     PutData,
@@ -84,6 +98,8 @@ pub enum MessageTypeTag {
     PutKey,
     AccountTransfer,
     PutPublicPmid,
+    Refresh,
+    // keep last: `MESSAGE_TYPE_COUNT` is counted from it
     Unknown,
 }
 
@@ -95,6 +111,8 @@ impl Encodable for MessageTypeTag {
             MessageTypeTag::BootstrapIdResponse => type_tag = "BootstrapIdResponse",
             MessageTypeTag::ConnectRequest => type_tag = "ConnectRequest",
             MessageTypeTag::ConnectResponse => type_tag = "ConnectResponse",
+            MessageTypeTag::DeleteData => type_tag = "DeleteData",
+            MessageTypeTag::DeleteDataResponse => type_tag = "DeleteDataResponse",
             MessageTypeTag::FindGroup => type_tag = "FindGroup",
             MessageTypeTag::FindGroupResponse => type_tag = "FindGroupResponse",
             MessageTypeTag::GetData => type_tag = "GetData",
@@ -103,6 +121,8 @@ impl Encodable for MessageTypeTag {
             MessageTypeTag::GetKeyResponse => type_tag = "GetKeyResponse",
             MessageTypeTag::GetGroupKey => type_tag = "GetGroupKey",
             MessageTypeTag::GetGroupKeyResponse => type_tag = "GetGroupKeyResponse",
+            MessageTypeTag::Ping => type_tag = "Ping",
+            MessageTypeTag::PingResponse => type_tag = "PingResponse",
             MessageTypeTag::Post => type_tag = "Post",
             MessageTypeTag::PostResponse => type_tag = "PostResponse",
             MessageTypeTag::PutData => type_tag = "PutData",
@@ -111,6 +131,7 @@ impl Encodable for MessageTypeTag {
             MessageTypeTag::PutKey => type_tag = "PutKey",
             MessageTypeTag::AccountTransfer => type_tag = "AccountTransfer",
             MessageTypeTag::PutPublicPmid => type_tag = "PutPublicPmid",
+            MessageTypeTag::Refresh => type_tag = "Refresh",
             MessageTypeTag::Unknown => type_tag = "Unknown",
         };
         CborTagEncode::new(5483_100, &(&type_tag)).encode(e)
@@ -127,6 +148,8 @@ impl Decodable for MessageTypeTag {
             "BootstrapIdResponse" => Ok(MessageTypeTag::BootstrapIdResponse),
             "ConnectRequest" => Ok(MessageTypeTag::ConnectRequest),
             "ConnectResponse" => Ok(MessageTypeTag::ConnectResponse),
+            "DeleteData" => Ok(MessageTypeTag::DeleteData),
+            "DeleteDataResponse" => Ok(MessageTypeTag::DeleteDataResponse),
             "FindGroup" => Ok(MessageTypeTag::FindGroup),
             "FindGroupResponse" => Ok(MessageTypeTag::FindGroupResponse),
             "GetData" => Ok(MessageTypeTag::GetData),
@@ -135,6 +158,8 @@ impl Decodable for MessageTypeTag {
             "GetKeyResponse" => Ok(MessageTypeTag::GetKeyResponse),
             "GetGroupKey" => Ok(MessageTypeTag::GetGroupKey),
             "GetGroupKeyResponse" => Ok(MessageTypeTag::GetGroupKeyResponse),
+            "Ping" => Ok(MessageTypeTag::Ping),
+            "PingResponse" => Ok(MessageTypeTag::PingResponse),
             "Post" => Ok(MessageTypeTag::Post),
             "PostResponse" => Ok(MessageTypeTag::PostResponse),
             "PutData" => Ok(MessageTypeTag::PutData),
@@ -143,11 +168,45 @@ impl Decodable for MessageTypeTag {
             "PutKey" => Ok(MessageTypeTag::PutKey),
             "PutPublicPmid" => Ok(MessageTypeTag::PutPublicPmid),
             "AccountTransfer" => Ok(MessageTypeTag::AccountTransfer),
+            "Refresh" => Ok(MessageTypeTag::Refresh),
             _ => Ok(MessageTypeTag::Unknown)
         }
     }
 }
 
+/// The number of variants of `MessageTypeTag`
+const MESSAGE_TYPE_COUNT: usize = MessageTypeTag::Unknown as usize + 1;
+
+/// How many messages of each type a node has sent and received.  A message sent to several peers
+/// counts once.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct MessageStats {
+    sent: [u64; MESSAGE_TYPE_COUNT],
+    received: [u64; MESSAGE_TYPE_COUNT],
+}
+
+impl MessageStats {
+    pub fn new() -> MessageStats {
+        MessageStats { sent: [0; MESSAGE_TYPE_COUNT], received: [0; MESSAGE_TYPE_COUNT] }
+    }
+
+    pub fn sent(&self, message_type: &MessageTypeTag) -> u64 {
+        self.sent[message_type.clone() as usize]
+    }
+
+    pub fn received(&self, message_type: &MessageTypeTag) -> u64 {
+        self.received[message_type.clone() as usize]
+    }
+
+    pub fn count_sent(&mut self, message_type: &MessageTypeTag) {
+        self.sent[message_type.clone() as usize] += 1;
+    }
+
+    pub fn count_received(&mut self, message_type: &MessageTypeTag) {
+        self.received[message_type.clone() as usize] += 1;
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct RoutingMessage {
     pub message_type: MessageTypeTag,
@@ -191,6 +250,17 @@ impl RoutingMessage {
             signature: signature }
     }
 
+    /// Checks the signature over the serialised body against the given public sign key.
+    pub fn verify(&self, public_sign_key: &types::PublicSignKey) -> bool {
+        if self.signature.signature.len() != crypto::sign::SIGNATUREBYTES ||
+           public_sign_key.public_sign_key.len() != crypto::sign::PUBLICKEYBYTES {
+            return false;
+        }
+        crypto::sign::verify_detached(&self.signature.get_crypto_signature(),
+                                      &self.serialised_body[..],
+                                      &public_sign_key.get_crypto_public_sign_key())
+    }
+
     pub fn get_message_body<T>(&self) -> T where T: for<'a> Encodable + Decodable {
         let mut d = cbor::Decoder::from_bytes(&self.serialised_body[..]);
         let obj: T = d.decode().next().unwrap().unwrap();
@@ -203,3 +273,45 @@ impl RoutingMessage {
         self.serialised_body = e.as_bytes().to_vec()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sodiumoxide::crypto;
+    use message_header::{MessageHeader, DEFAULT_TTL};
+    use test_utils::Random;
+    use types;
+
+    #[test]
+    fn message_stats() {
+        let mut stats = MessageStats::new();
+        stats.count_sent(&MessageTypeTag::FindGroup);
+        stats.count_sent(&MessageTypeTag::FindGroup);
+        stats.count_received(&MessageTypeTag::Unknown);
+        assert_eq!(stats.sent(&MessageTypeTag::FindGroup), 2);
+        assert_eq!(stats.received(&MessageTypeTag::FindGroup), 0);
+        assert_eq!(stats.sent(&MessageTypeTag::BootstrapIdRequest), 0);
+        assert_eq!(stats.received(&MessageTypeTag::Unknown), 1);
+    }
+
+    #[test]
+    fn verify() {
+        let (public_key, secret_key) = crypto::sign::gen_keypair();
+        let (other_public_key, _) = crypto::sign::gen_keypair();
+        let header = MessageHeader {
+            message_id:  1u32,
+            destination: types::DestinationAddress { dest: Random::generate_random(), reply_to: None },
+            source:      Random::generate_random(),
+            authority:   types::Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let mut message = RoutingMessage::new(MessageTypeTag::GetData, header,
+                                              vec![1u8, 2, 3], &secret_key);
+
+        assert!(message.verify(&types::PublicSignKey::new(public_key)));
+        assert!(!message.verify(&types::PublicSignKey::new(other_public_key)));
+
+        message.set_message_body(vec![3u8, 2, 1]);
+        assert!(!message.verify(&types::PublicSignKey::new(public_key)));
+    }
+}
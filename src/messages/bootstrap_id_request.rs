@@ -24,18 +24,19 @@ use NameType;
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct BootstrapIdRequest {
   pub sender_id  : NameType,
+  pub protocol_version : u32,
 }
 
 impl Encodable for BootstrapIdRequest {
   fn encode<E: Encoder>(&self, e: &mut E)->Result<(), E::Error> {
-    CborTagEncode::new(5483_001, &(&self.sender_id)).encode(e)
+    CborTagEncode::new(5483_001, &(&self.sender_id, &self.protocol_version)).encode(e)
   }
 }
 
 impl Decodable for BootstrapIdRequest {
   fn decode<D: Decoder>(d: &mut D)->Result<BootstrapIdRequest, D::Error> {
     try!(d.read_u64());
-    let sender_id = try!(Decodable::decode(d));
-    Ok(BootstrapIdRequest { sender_id: sender_id })
+    let (sender_id, protocol_version) = try!(Decodable::decode(d));
+    Ok(BootstrapIdRequest { sender_id: sender_id, protocol_version: protocol_version })
   }
 }
@@ -23,10 +23,14 @@ use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
 use types;
 use error::ResponseError;
 
+/// Data too large for one message is sent as `total` responses, each carrying `part` of it.
+/// A response sent whole has `part` 0 and `total` 1.
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct GetDataResponse {
   pub name_and_type_id : types::NameAndTypeId,
   pub data : Result<Vec<u8>, ResponseError>,
+  pub part : u32,
+  pub total : u32,
 }
 
 impl Encodable for GetDataResponse {
@@ -43,19 +47,21 @@ impl Encodable for GetDataResponse {
             Err(_) => &dummy,
         };
 
-        CborTagEncode::new(5483_001, &(&self.name_and_type_id, &data, &error)).encode(e)
+        CborTagEncode::new(5483_001, &(&self.name_and_type_id, &data, &error, &self.part,
+                                       &self.total)).encode(e)
     }
 }
 
 impl Decodable for GetDataResponse {
   fn decode<D: Decoder>(d: &mut D)->Result<GetDataResponse, D::Error> {
     try!(d.read_u64());
-    let (name_and_type_id, data, error) = try!(Decodable::decode(d));
+    let (name_and_type_id, data, error, part, total) = try!(Decodable::decode(d));
 
-    match error {
-        None        => Ok(GetDataResponse { name_and_type_id: name_and_type_id, data: Ok(data) }),
-        Some(error) => Ok(GetDataResponse { name_and_type_id: name_and_type_id, data: Err(error)})
-    }
+    let data = match error {
+        None        => Ok(data),
+        Some(error) => Err(error),
+    };
+    Ok(GetDataResponse { name_and_type_id: name_and_type_id, data: data, part: part, total: total })
   }
 }
 
@@ -21,24 +21,37 @@ use cbor::CborTagEncode;
 use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
 
 use types;
+use error::ResponseError;
 use NameType;
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+/// Carries the key asked for, or why it couldn't be given, e.g. `ResponseError::NoData` if no key
+/// is held for `address`.
+#[derive(PartialEq, Eq, Clone, Debug)]
 pub struct GetKeyResponse {
   pub address : NameType,
-  pub public_sign_key : types::PublicSignKey
+  pub public_sign_key : Result<types::PublicSignKey, ResponseError>
 }
 
 impl Encodable for GetKeyResponse {
   fn encode<E: Encoder>(&self, e: &mut E)->Result<(), E::Error> {
-    CborTagEncode::new(5483_001, &(&self.address, &self.public_sign_key)).encode(e)
+    let (public_sign_key, error) = match self.public_sign_key {
+        Ok(ref public_sign_key) => (Some(public_sign_key), None),
+        Err(ref error) => (None, Some(error)),
+    };
+    CborTagEncode::new(5483_001, &(&self.address, &public_sign_key, &error)).encode(e)
   }
 }
 
 impl Decodable for GetKeyResponse {
   fn decode<D: Decoder>(d: &mut D)->Result<GetKeyResponse, D::Error> {
     try!(d.read_u64());
-    let (address, public_sign_key) = try!(Decodable::decode(d));
+    let (address, public_sign_key, error): (NameType, Option<types::PublicSignKey>,
+                                            Option<ResponseError>) = try!(Decodable::decode(d));
+    let public_sign_key = match (public_sign_key, error) {
+        (_, Some(error)) => Err(error),
+        (Some(public_sign_key), None) => Ok(public_sign_key),
+        (None, None) => return Err(d.error("GetKeyResponse carries neither a key nor an error")),
+    };
     Ok(GetKeyResponse { address: address , public_sign_key: public_sign_key})
   }
 }
@@ -47,6 +60,7 @@ impl Decodable for GetKeyResponse {
 mod test {
     use super::*;
     use cbor;
+    use error::ResponseError;
     use test_utils::Random;
 
     #[test]
@@ -61,4 +75,18 @@ mod test {
 
         assert_eq!(obj_before, obj_after);
     }
+
+    #[test]
+    fn key_not_found_serialisation() {
+        let obj_before = GetKeyResponse { address: Random::generate_random(),
+                                          public_sign_key: Err(ResponseError::NoData) };
+
+        let mut e = cbor::Encoder::from_memory();
+        e.encode(&[&obj_before]).unwrap();
+
+        let mut d = cbor::Decoder::from_bytes(e.as_bytes());
+        let obj_after: GetKeyResponse = d.decode().next().unwrap().unwrap();
+
+        assert_eq!(obj_before, obj_after);
+    }
 }
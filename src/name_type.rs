@@ -126,6 +126,27 @@ pub fn closer_to_target_or_equal(lhs: &NameType, rhs: &NameType, target: &NameTy
     true
 }
 
+/// Returns the XOR distance between `lhs` and `rhs`.  Distances order as names do, so the nearer
+/// of two names to a target is the one with the lesser distance.
+pub fn xor_distance(lhs: &NameType, rhs: &NameType) -> NameType {
+    let mut distance = [0u8; NAME_TYPE_LEN];
+    for i in 0..NAME_TYPE_LEN {
+        distance[i] = lhs.0[i] ^ rhs.0[i];
+    }
+    NameType(distance)
+}
+
+/// Returns the number of leading bits `lhs` and `rhs` have in common.
+pub fn common_leading_bits(lhs: &NameType, rhs: &NameType) -> usize {
+    for i in 0..lhs.0.len() {
+        let diff = lhs.0[i] ^ rhs.0[i];
+        if diff != 0 {
+            return i * 8 + diff.leading_zeros() as usize;
+        }
+    }
+    lhs.0.len() * 8
+}
+
 /// The `NameType` can be ordered from zero as a normal Euclidean number
 impl Ord for NameType {
     #[inline]
@@ -211,6 +232,28 @@ mod test {
         assert_eq!(obj_before, obj_after);
     }
 
+    #[test]
+    fn leading_bits_in_common() {
+        let name: NameType = Random::generate_random();
+        assert_eq!(common_leading_bits(&name, &name), NAME_TYPE_LEN * 8);
+        let mut id = name.get_id();
+        id[2] ^= 0x20;
+        assert_eq!(common_leading_bits(&name, &NameType::new(id)), 18);
+        id[0] ^= 0x80;
+        assert_eq!(common_leading_bits(&name, &NameType::new(id)), 0);
+    }
+
+    #[test]
+    fn distance() {
+        let name: NameType = Random::generate_random();
+        assert_eq!(xor_distance(&name, &name), NameType::new([0u8; NAME_TYPE_LEN]));
+        let mut id = [0u8; NAME_TYPE_LEN];
+        id[0] = 0x0f;
+        let other = NameType::new(id);
+        assert_eq!(xor_distance(&other, &NameType::new([0xffu8; NAME_TYPE_LEN])).get_id()[0], 0xf0);
+        assert_eq!(xor_distance(&name, &other), xor_distance(&other, &name));
+    }
+
     #[test]
     fn name_type_equal_assertion() {
         let type1: NameType = Random::generate_random();
@@ -20,23 +20,28 @@ use name_type::NameType;
 use types::{Authority, DestinationAddress};
 use super::Action;
 use error::{InterfaceError, ResponseError};
+use messages::MessageTypeTag;
 
 pub enum RoutingNodeAction {
     None,
     Put { destination: NameType, content: Box<Sendable>, is_client: bool, },
     Get { type_id: u64, name: NameType, },
-    Post,
+    Post { destination: NameType, content: Vec<u8>, },
     Refresh { content: Box<Sendable>, },
 }
 
 pub trait Interface : Sync + Send {
-    /// the public key or address of the node store it is returned on success.
+    /// the public key or address of the node store it is returned on success.  A key which
+    /// isn't held is signalled with `ResponseError::NoData`, which is what is returned by default;
+    /// the requester is sent that error in place of the key.
     fn handle_get_key(&mut self,
                       type_id: u64,
                       name: NameType,
                       our_authority: Authority,
                       from_authority: Authority,
-                      from_address: NameType) -> Result<Action, InterfaceError>;
+                      from_address: NameType) -> Result<Action, InterfaceError> {
+        Err(InterfaceError::Response(ResponseError::NoData))
+    }
 
     /// if reply is data then we send back the response message (ie get_response )
     fn handle_get(&mut self,
@@ -54,6 +59,14 @@ pub trait Interface : Sync + Send {
                   dest_address: DestinationAddress,
                   data: Vec<u8>) -> Result<Action, InterfaceError>;
 
+    /// name is the name of the data to be removed
+    fn handle_delete(&mut self,
+                     our_authority: Authority,
+                     from_authority: Authority,
+                     from_address: NameType,
+                     dest_address: DestinationAddress,
+                     name: NameType) -> Result<Action, InterfaceError>;
+
     fn handle_post(&mut self,
                    our_authority: Authority,
                    from_authority: Authority,
@@ -62,6 +75,7 @@ pub trait Interface : Sync + Send {
                    data: Vec<u8>) -> Result<Action, InterfaceError>;
 
     fn handle_get_response(&mut self,
+                           from_authority: Authority,
                            from_address: NameType,
                            response: Result<Vec<u8>, ResponseError>) -> RoutingNodeAction;
 
@@ -70,6 +84,12 @@ pub trait Interface : Sync + Send {
                            from_address: NameType,
                            response: Result<Vec<u8>, ResponseError>);
 
+    /// the default ignores the response.
+    fn handle_delete_response(&mut self,
+                              from_authority: Authority,
+                              from_address: NameType,
+                              response: Result<(), ResponseError>) {}
+
     fn handle_post_response(&mut self,
                             from_authority: Authority,
                             from_address: NameType,
@@ -87,4 +107,18 @@ pub trait Interface : Sync + Send {
                         from_authority: Authority,
                         from_address: NameType,
                         data: Vec<u8>) -> Result<Action, InterfaceError>;
+
+    /// parses data refreshed by a group member into its Sendable type, so that routing can merge
+    /// the copies received from the group.  Only data sent with `refresh` is parsed, never a
+    /// plain put.  The default returns None and refreshed data is passed to handle_put unmerged.
+    fn parse_refresh(&mut self, name: &NameType, data: &Vec<u8>) -> Option<Box<Sendable>> { None }
+
+    /// consulted before any message leaves the node, other than the bootstrap id exchange with a
+    /// peer whose name isn't known yet; returning false drops the message.  `target` is the name
+    /// the message is addressed to.  The default allows everything.
+    fn allow_send(&mut self, target: &NameType, message_type: MessageTypeTag) -> bool { true }
+
+    /// consulted before an unauthorised put (one carrying no authority) is passed to handle_put;
+    /// returning false drops it.  The default accepts everything.
+    fn authorise_unauthorised_put(&mut self, name: &NameType, data: &Vec<u8>) -> bool { true }
 }
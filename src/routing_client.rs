@@ -26,6 +26,7 @@ use std::sync::{Mutex, Arc, mpsc};
 use std::sync::mpsc::Receiver;
 
 use client_interface::Interface;
+use compression;
 use crust;
 use crust::Endpoint::Tcp;
 use messages;
@@ -44,6 +45,9 @@ use message_header::MessageHeader;
 use messages::{RoutingMessage, MessageTypeTag};
 use types::{MessageId, Authority};
 
+/// Size, in bytes, above which the data we put is deflated before being sent
+static COMPRESSION_THRESHOLD: usize = 1 << 12;
+
 pub use crust::Endpoint;
 
 type Bytes = Vec<u8>;
@@ -228,7 +232,7 @@ impl<F> RoutingClient<F> where F: Interface {
 
         // Give Serialised RoutingMessage to connection manager
         match self.connection_manager.send(self.bootstrap_address.1.clone().unwrap(),
-                                           encoder_routingmsg.into_bytes()) {
+                compression::pack(&encoder_routingmsg.into_bytes(), None)) {
             Ok(_) => Ok(message_id),
             Err(error) => Err(error),
         }
@@ -273,7 +277,7 @@ impl<F> RoutingClient<F> where F: Interface {
 
         // Give Serialised RoutingMessage to connection manager
         match self.connection_manager.send(self.bootstrap_address.1.clone().unwrap(),
-                                           encoder_routingmsg.into_bytes()) {
+                compression::pack(&encoder_routingmsg.into_bytes(), Some(COMPRESSION_THRESHOLD))) {
             Ok(_) => Ok(message_id),
             Err(error) => Err(error),
         }
@@ -296,7 +300,8 @@ impl<F> RoutingClient<F> where F: Interface {
         let mut e = Encoder::from_memory();
 
         e.encode(&[message]).unwrap();
-        let _ = self.connection_manager.send(self.bootstrap_address.1.clone().unwrap(), e.into_bytes());
+        let _ = self.connection_manager.send(self.bootstrap_address.1.clone().unwrap(),
+                compression::pack(&e.into_bytes(), Some(COMPRESSION_THRESHOLD)));
     }
 
     pub fn run(&mut self) {
@@ -309,6 +314,10 @@ impl<F> RoutingClient<F> where F: Interface {
                 // The received id is Endpoint(i.e. ip + socket) which is no use to upper layer
                 // println!("received a new message from {}",
                 //          match endpoint.clone() { Tcp(socket_addr) => socket_addr });
+                let bytes = match compression::unpack(&bytes, types::MAX_MESSAGE_SIZE) {
+                    Ok(bytes) => bytes,
+                    Err(_) => return,
+                };
                 let mut decode_routing_msg = cbor::Decoder::from_bytes(&bytes[..]);
                 let routing_msg: messages::RoutingMessage = decode_routing_msg.decode().next().unwrap().unwrap();
                 // println!("received a {:?} from {}", routing_msg.message_type,
@@ -360,7 +369,8 @@ impl<F> RoutingClient<F> where F: Interface {
         let destination = types::DestinationAddress{ dest: NameType::new([0u8; NAME_TYPE_LEN]), reply_to: None };
         let source = types::SourceAddress{ from_node: self.id_packet.get_name().clone(), from_group: None, reply_to: None };
         let authority = types::Authority::Client;
-        let request = BootstrapIdRequest { sender_id: self.id_packet.get_name().clone() };
+        let request = BootstrapIdRequest { sender_id: self.id_packet.get_name().clone(),
+                                           protocol_version: types::PROTOCOL_VERSION };
         let header = MessageHeader::new(message_id, destination, source, authority);
         let message = RoutingMessage::new(MessageTypeTag::BootstrapIdRequest, header,
             request, &self.id_packet.get_crypto_secret_sign_key());
@@ -375,7 +385,8 @@ impl<F> RoutingClient<F> where F: Interface {
         let destination = types::DestinationAddress{ dest: NameType::new([0u8; NAME_TYPE_LEN]), reply_to: None };
         let source = types::SourceAddress{ from_node: self.id_packet.get_name().clone(), from_group: None, reply_to: None };
         let authority = types::Authority::Client;
-        let request = BootstrapIdResponse { sender_id: self.id_packet.get_name().clone() };
+        let request = BootstrapIdResponse { sender_id: self.id_packet.get_name().clone(),
+                                            protocol_version: types::PROTOCOL_VERSION };
         let header = MessageHeader::new(message_id, destination, source, authority);
         let message = RoutingMessage::new(MessageTypeTag::BootstrapIdResponse, header,
             request, &self.id_packet.get_crypto_secret_sign_key());
@@ -386,7 +397,8 @@ impl<F> RoutingClient<F> where F: Interface {
     }
 
     fn send_to_bootstrap_node(&mut self, serialised_message: &Vec<u8>) {
-        let _ = self.connection_manager.send(self.bootstrap_address.1.clone().unwrap(), serialised_message.clone());
+        let _ = self.connection_manager.send(self.bootstrap_address.1.clone().unwrap(),
+                compression::pack(serialised_message, None));
     }
 
     fn get_next_message_id(&mut self) -> MessageId {
@@ -16,29 +16,32 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use cbor::{Decoder, Encoder, CborError};
+use cbor::{Decoder, Encoder, CborError, CborTagEncode};
 use rand;
+use rustc_serialize;
 use rustc_serialize::{Decodable, Encodable};
 use sodiumoxide;
-use std::collections::{BTreeMap, HashMap};
-use std::sync::mpsc;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{mpsc, Arc};
 use std::boxed::Box;
 use std::ops::DerefMut;
-use std::sync::mpsc::Receiver;
-use time::Duration;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::thread;
+use time::{Duration, SteadyTime};
 
+use compression;
 use crust;
-use crust::Endpoint::Tcp;
 use lru_time_cache::LruCache;
 use message_filter::MessageFilter;
 use NameType;
-use name_type::{closer_to_target, NAME_TYPE_LEN};
+use name_type::{closer_to_target, common_leading_bits, xor_distance, NAME_TYPE_LEN};
 use node_interface;
 use node_interface::Interface;
 use routing_table::{RoutingTable, NodeInfo};
+use send_pool::SendPool;
 use sendable::Sendable;
 use types;
-use types::{MessageId, Authority, NameAndTypeId};
+use types::{MessageId, Authority, NameAndTypeId, DestinationKind};
 use message_header::MessageHeader;
 use messages::bootstrap_id_request::BootstrapIdRequest;
 use messages::bootstrap_id_response::BootstrapIdResponse;
@@ -49,20 +52,29 @@ use messages::put_data_response::PutDataResponse;
 use messages::connect_request::ConnectRequest;
 use messages::connect_response::ConnectResponse;
 use messages::connect_success::ConnectSuccess;
+use messages::delete_data::DeleteData;
+use messages::delete_data_response::DeleteDataResponse;
 use messages::find_group::FindGroup;
 use messages::find_group_response::FindGroupResponse;
 use messages::get_group_key::GetGroupKey;
 use messages::get_group_key_response::GetGroupKeyResponse;
+use messages::ping::Ping;
 use messages::post::Post;
+use messages::post_response::PostResponse;
 use messages::get_client_key::GetKey;
 use messages::get_client_key_response::GetKeyResponse;
+use messages::put_key::PutKey;
 use messages::put_public_pmid::PutPublicPmid;
-use messages::{RoutingMessage, MessageTypeTag};
+use messages::{RoutingMessage, MessageTypeTag, MessageStats};
+use serialisation::Serialisation;
 use super::{Action};
-use error::{RoutingError, InterfaceError};
+use error::{RoutingError, InterfaceError, ResponseError};
+use event::RoutingEvent;
 
 use std::io;
+use std::mem;
 use std::convert::From;
+use std::net::SocketAddr;
 
 type ConnectionManager = crust::ConnectionManager;
 type Event = crust::Event;
@@ -72,177 +84,986 @@ type Bytes = Vec<u8>;
 
 type RecvResult = Result<(), RoutingError>;
 
+/// Default port on which to listen for broadcasts from bootstrapping nodes
+static BEACON_PORT: u16 = 5483;
+/// Default upper bound on the size of data an interface may return for a get
+static MAX_REPLY_SIZE: usize = 1 << 20;
+/// Default number of bytes a single peer may send us within one accounting window
+static MAX_INBOUND_BYTES: usize = 1 << 23;
+/// Number of consecutive windows a peer may exceed its byte budget before being disconnected
+static MAX_WINDOWS_EXCEEDED: u32 = 3;
+/// Default number of consecutive undecodable messages after which a peer is dropped
+static MAX_DECODE_FAILURES: u32 = 5;
+/// Default window, in seconds, within which consecutive decode failures are counted
+static DECODE_FAILURE_WINDOW_SECS: i64 = 60;
+/// Default window, in milliseconds, over which refreshes of the same content are coalesced
+static REFRESH_WINDOW_MS: i64 = 500;
+/// Time, in seconds, copies of refreshed data wait for the rest of a quorum before being dropped
+static REFRESH_ACCUMULATION_SECS: i64 = 60;
+/// Longest time, in milliseconds, `run_blocking` waits for an event before seeing to its timers
+static HOUSEKEEPING_TICK_MS: i64 = 100;
+/// Time `stop` allows for messages held for retry to be resent
+static STOP_FLUSH_TIMEOUT_MS: i64 = 1000;
+/// Time, in seconds, messages held for retry wait for their peer to be redialled before being
+/// dropped
+static RETRY_REDIAL_TIMEOUT_SECS: i64 = 10;
+/// Default number of messages remembered by the filter which drops repeats
+static MESSAGE_FILTER_CAPACITY: usize = 10_000;
+/// Default time, in minutes, a message is remembered by the filter
+static MESSAGE_FILTER_EXPIRY_MINS: i64 = 20;
+/// Default number of public pmids held for checking signatures
+static PUBLIC_PMID_CACHE_CAPACITY: usize = 1_000;
+/// Default time, in minutes, a public pmid is held for
+static PUBLIC_PMID_CACHE_EXPIRY_MINS: i64 = 10;
+/// Default number of public keys held for serving GetKey without calling the interface
+static PUBLIC_KEY_CACHE_CAPACITY: usize = 1_000;
+/// Default time, in minutes, a public key is held for
+static PUBLIC_KEY_CACHE_EXPIRY_MINS: i64 = 10;
+/// Default number of GetData responses cached for answering repeat reads locally
+static DATA_CACHE_CAPACITY: usize = 100;
+/// Default time, in seconds, a cached GetData response is served for
+static DATA_CACHE_TTL_SECS: i64 = 600;
+/// Time, in seconds, during which a repeat connect request to the same peer is suppressed
+static PENDING_CONNECT_SECS: i64 = 5;
+/// Number of senders whose messages may be held while we wait to learn their public sign key
+static MAX_UNVERIFIED_SENDERS: usize = 64;
+/// Number of messages held per sender while we wait to learn its public sign key
+static MAX_UNVERIFIED_MESSAGES: usize = 8;
+/// Time, in seconds, a message is held while we wait to learn its sender's public sign key
+static UNVERIFIED_MESSAGE_TIMEOUT_SECS: i64 = 30;
+/// Size, in bytes, above which data carrying messages are deflated before being sent
+static COMPRESSION_THRESHOLD: usize = 1 << 12;
+/// Default time, in seconds, between attempts to reconnect to a lost peer
+static RECONNECT_INTERVAL_SECS: i64 = 5;
+/// Default time, in seconds, a get waits for its response
+static GET_TIMEOUT_SECS: i64 = 60;
+/// Time, in seconds, a `find_group` waits for answers before its receiver is closed
+static FIND_GROUP_TIMEOUT_SECS: i64 = 60;
+/// Number of threads over which the sends of one message to several peers are spread
+static SEND_THREADS: usize = 4;
+/// Default number of messages per second we accept from a single peer
+static MESSAGE_RATE: u32 = 100;
+/// Default number of messages a peer may send in a burst above `MESSAGE_RATE`
+static MESSAGE_BURST: u32 = 200;
+/// Consecutive messages dropped for being over rate before the peer is reported
+static MAX_RATE_LIMITED_MESSAGES: u32 = 10;
+/// Default time, in seconds, a connected peer may stay silent before we ping it
+static PING_INTERVAL_SECS: i64 = 30;
+/// Default time, in seconds, we wait for any message from a pinged peer before dropping it
+static PING_DEADLINE_SECS: i64 = 10;
+/// Default time, in seconds, a node added to the routing table has to connect before it is dropped
+static CONNECT_TIMEOUT_SECS: i64 = 30;
+/// Number of endpoints kept in the bootstrap cache
+static BOOTSTRAP_CACHE_SIZE: usize = 100;
+/// Default size, in bytes, of each part a large GetData response is split into
+static GET_RESPONSE_PART_SIZE: usize = 1 << 18;
+/// Time, in seconds, we wait for the next part of a split GetData response before discarding it
+static GET_RESPONSE_PART_TIMEOUT_SECS: i64 = 60;
+/// Number of split GetData responses which may be being reassembled at once
+static MAX_PARTIAL_GET_RESPONSES: usize = 16;
+/// Number of message ids tried before giving up on finding one which isn't in use
+static MAX_MESSAGE_ID_ATTEMPTS: u32 = 1 << 16;
+
+/// Inbound traffic received from a single connected peer.
+#[derive(Clone, Debug)]
+pub struct PeerBandwidth {
+    /// Bytes received since the peer connected
+    pub total_bytes: u64,
+    /// Bytes received in the current window
+    pub window_bytes: usize,
+    /// Messages dropped because the peer was over its budget
+    pub dropped_messages: u64,
+    window_start: SteadyTime,
+    windows_exceeded: u32,
+}
+
+impl PeerBandwidth {
+    fn new() -> PeerBandwidth {
+        PeerBandwidth { total_bytes: 0, window_bytes: 0, dropped_messages: 0,
+                        window_start: SteadyTime::now(), windows_exceeded: 0 }
+    }
+}
+
+/// Token bucket limiting the rate at which a single peer's messages are processed.
+struct MessageRate {
+    tokens: f64,
+    last_refill: SteadyTime,
+    consecutive_drops: u32,
+}
+
+impl MessageRate {
+    fn new(burst: u32) -> MessageRate {
+        MessageRate { tokens: burst as f64, last_refill: SteadyTime::now(), consecutive_drops: 0 }
+    }
+}
+
+/// How a node is set up, for `RoutingNode::with_config`.  `RoutingConfig::default()` is the set
+/// up `new` uses; change only what is needed with e.g.
+/// `RoutingConfig { group_size: 4, ..RoutingConfig::default() }`.
+pub struct RoutingConfig {
+    /// The ports to listen on; an empty list lets the connection manager pick its defaults
+    pub ports_and_protocols: Vec<PortAndProtocol>,
+    /// The port to listen for broadcasts from bootstrapping nodes on, if any
+    pub beacon_port: Option<u16>,
+    /// The number of nodes in a close group, `types::GROUP_SIZE` by default; group quorums are
+    /// scaled to match.  Meant for small test networks: every node in a network must use the
+    /// same group size, otherwise nodes disagree on who is responsible for what and the network
+    /// misbehaves.  Must be at least 1.
+    pub group_size: usize,
+    /// The serialisation the messages we put on the wire are encoded in, CBOR by default.  Every
+    /// node in a network must use the same serialisation; messages in any other are refused with
+    /// `SerialisationMismatch`, and clients only speak CBOR.
+    pub serialisation: Serialisation,
+    /// How many messages the filter which drops repeats remembers; the oldest entry is evicted
+    /// once this is reached
+    pub message_filter_capacity: usize,
+    /// How long the filter remembers a message for
+    pub message_filter_expiry: Duration,
+    /// How many public pmids are held for checking signatures; the least recently used entry is
+    /// evicted once this is reached.  Every pmid accepted by `handle_put_public_pmid` is added, so
+    /// a stricter acceptance policy (see `RoutingNode::set_min_pmid_leading_bits`) lets a smaller
+    /// cache do.  Messages from a sender whose pmid has been evicted are held back until it is put
+    /// again.
+    pub public_pmid_cache_capacity: usize,
+    /// How long a public pmid is held for
+    pub public_pmid_cache_expiry: Duration,
+    /// Where to report what happens in the node, from its construction on
+    pub events: Option<Sender<RoutingEvent>>,
+}
+
+impl Default for RoutingConfig {
+    fn default() -> RoutingConfig {
+        RoutingConfig {
+            ports_and_protocols: Vec::new(),
+            beacon_port: Some(BEACON_PORT),
+            group_size: types::GROUP_SIZE,
+            serialisation: Serialisation::Cbor,
+            message_filter_capacity: MESSAGE_FILTER_CAPACITY,
+            message_filter_expiry: Duration::minutes(MESSAGE_FILTER_EXPIRY_MINS),
+            public_pmid_cache_capacity: PUBLIC_PMID_CACHE_CAPACITY,
+            public_pmid_cache_expiry: Duration::minutes(PUBLIC_PMID_CACHE_EXPIRY_MINS),
+            events: None,
+        }
+    }
+}
+
+/// A point-in-time copy of a node's state, used to checkpoint or fork a simulated network and to
+/// ship a reproducible node state with a bug report.
+///
+/// The secret keys of the node are deliberately never captured; only the `PublicPmid` is kept so
+/// a snapshot can be shared freely.  A node brought back with `restore` signs with a freshly
+/// generated key pair, so its messages fail the signature checks of peers which know the
+/// original; `restore_with_pmid` takes the original `Pmid` back from the caller instead.
+/// Live sockets, the message filter, the time-expiring caches (public pmids, public keys and
+/// data) and pending requests (gets, find_groups, retries and refreshes) are not captured either.
+/// The caches are rebuilt by normal traffic; pending requests are lost and must be made again.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct NodeSnapshot {
+    pub own_id: NameType,
+    pub public_pmid: types::PublicPmid,
+    pub routing_table: Vec<(types::PublicPmid, Vec<Endpoint>, Option<Endpoint>)>,
+    pub connections: Vec<(NameType, Endpoint)>,
+    pub accepting_on: Vec<Endpoint>,
+    pub bootstrap_endpoint: Option<Endpoint>,
+    pub bootstrap_node_id: Option<NameType>,
+    pub next_message_id: MessageId,
+}
+
+impl Encodable for NodeSnapshot {
+    fn encode<E: rustc_serialize::Encoder>(&self, e: &mut E)->Result<(), E::Error> {
+        CborTagEncode::new(5483_001, &(&self.own_id, &self.public_pmid, &self.routing_table,
+                                       &self.connections, &self.accepting_on,
+                                       &self.bootstrap_endpoint, &self.bootstrap_node_id,
+                                       &self.next_message_id)).encode(e)
+    }
+}
+
+impl Decodable for NodeSnapshot {
+    fn decode<D: rustc_serialize::Decoder>(d: &mut D)->Result<NodeSnapshot, D::Error> {
+        try!(d.read_u64());
+        let (own_id, public_pmid, routing_table, connections, accepting_on, bootstrap_endpoint,
+             bootstrap_node_id, next_message_id) = try!(Decodable::decode(d));
+        Ok(NodeSnapshot { own_id: own_id, public_pmid: public_pmid, routing_table: routing_table,
+                          connections: connections, accepting_on: accepting_on,
+                          bootstrap_endpoint: bootstrap_endpoint,
+                          bootstrap_node_id: bootstrap_node_id,
+                          next_message_id: next_message_id })
+    }
+}
+
 /// DHT node
 pub struct RoutingNode<F: Interface> {
     interface: Box<F>,
     pmid: types::Pmid,
     own_id: NameType,
     event_input: Receiver<Event>,
-    connection_manager: ConnectionManager,
+    connection_manager: Arc<ConnectionManager>,
+    send_pool: SendPool,
     all_connections: (HashMap<Endpoint, NameType>, BTreeMap<NameType, Endpoint>),
+    non_routing_connections: HashMap<NameType, Endpoint>,
     routing_table: RoutingTable,
     accepting_on: Vec<Endpoint>,
+    external_endpoints: Vec<Endpoint>,
     listening_for_broadcasts_on_port: Option<u16>,
     next_message_id: MessageId,
     bootstrap_endpoint: Option<Endpoint>,
     bootstrap_node_id: Option<NameType>,
-    filter: MessageFilter<types::FilterType>,
-    public_pmid_cache: LruCache<NameType, types::PublicPmid>
+    first_node: bool,
+    bootstrap_candidates: Vec<Endpoint>,
+    bootstrap_cache: Vec<Endpoint>,
+    filter: MessageFilter<(types::FilterType, u32)>,
+    public_pmid_cache: LruCache<NameType, types::PublicPmid>,
+    public_key_cache: LruCache<NameType, types::PublicSignKey>,
+    data_cache: LruCache<(NameType, u64), Vec<u8>>,
+    data_cache_types: HashMap<NameType, Vec<u64>>,
+    cacheable_type_ids: Option<HashSet<u64>>,
+    get_response_part_size: usize,
+    partial_get_responses: LruCache<(MessageId, NameType), Vec<Option<Vec<u8>>>>,
+    compression_threshold: Option<usize>,
+    pending_connects: LruCache<NameType, ()>,
+    vetoed_sends: usize,
+    max_reply_size: usize,
+    max_message_size: usize,
+    inbound_bandwidth: HashMap<NameType, PeerBandwidth>,
+    max_inbound_bytes: usize,
+    inbound_window: Duration,
+    failed_sends: usize,
+    message_stats: MessageStats,
+    serialisation: Serialisation,
+    message_rates: HashMap<NameType, MessageRate>,
+    message_rate: u32,
+    message_burst: u32,
+    rate_limited_messages: usize,
+    max_nodes_per_ip: Option<usize>,
+    min_pmid_leading_bits: usize,
+    pending_retries: HashMap<NameType, (SteadyTime, Vec<Bytes>)>,
+    pending_refreshes: HashMap<NameType, (SteadyTime, MessageId, Box<Sendable>)>,
+    refresh_window: Duration,
+    pending_gets: HashMap<MessageId, (Sender<Result<Vec<u8>, ResponseError>>, SteadyTime)>,
+    get_timeout: Duration,
+    pending_find_groups: HashMap<MessageId, (Sender<Vec<NameType>>, Vec<NameType>, SteadyTime)>,
+    stopped: bool,
+    accumulating_refresh: HashMap<NameType, (SteadyTime, Vec<(NameType, Box<Sendable>)>)>,
+    decode_failures: HashMap<NameType, (u32, SteadyTime)>,
+    max_decode_failures: u32,
+    decode_failure_window: Duration,
+    unverified_messages: HashMap<NameType, Vec<(Bytes, SteadyTime)>>,
+    last_seen: HashMap<NameType, SteadyTime>,
+    pings_sent: HashMap<NameType, SteadyTime>,
+    ping_interval: Duration,
+    ping_deadline: Duration,
+    unconnected_nodes: HashMap<NameType, SteadyTime>,
+    connect_timeout: Duration,
+    reconnecting: HashMap<NameType, (NodeInfo, u32, SteadyTime)>,
+    reconnect_attempts: u32,
+    reconnect_interval: Duration,
+    previous_close_group: Vec<NameType>,
+    events: Option<Sender<RoutingEvent>>
+}
+
+/// A node dropped without being stopped closes its connections, but doesn't wait for held
+/// messages to be resent.  Dropping a stopped node has nothing more to do.
+impl<F> Drop for RoutingNode<F> where F: Interface {
+    fn drop(&mut self) {
+        if !self.stopped {
+            self.close();
+        }
+    }
 }
 
 impl<F> RoutingNode<F> where F: Interface {
     pub fn new(my_interface: F) -> RoutingNode<F> {
         sodiumoxide::init();  // enable shared global (i.e. safe to multithread now)
+        RoutingNode::listen(my_interface, types::Pmid::new(), RoutingConfig::default())
+    }
+
+    /// Creates a node set up as `config` says.  Fails with `InvalidGroupSize` if the group size
+    /// is 0.
+    pub fn with_config(my_interface: F, config: RoutingConfig)
+            -> Result<RoutingNode<F>, RoutingError> {
+        if config.group_size == 0 {
+            return Err(RoutingError::InvalidGroupSize);
+        }
+        sodiumoxide::init();  // enable shared global (i.e. safe to multithread now)
+        Ok(RoutingNode::listen(my_interface, types::Pmid::new(), config))
+    }
+
+    /// As `new`, but with a close group of `group_size` nodes; see `RoutingConfig::group_size`.
+    pub fn with_group_size(my_interface: F, group_size: usize)
+            -> Result<RoutingNode<F>, RoutingError> {
+        RoutingNode::with_config(my_interface,
+                                 RoutingConfig { group_size: group_size, ..RoutingConfig::default() })
+    }
+
+    /// As `new`, but takes on the identity `pmid` rather than generating one, e.g. one encoded by
+    /// an earlier run.  The `Pmid` holds the node's secret keys: anyone who gets hold of a
+    /// persisted one can sign as this node, so keep it as private as any other secret key.
+    pub fn with_pmid(my_interface: F, pmid: types::Pmid) -> RoutingNode<F> {
+        sodiumoxide::init();  // enable shared global (i.e. safe to multithread now)
+        RoutingNode::listen(my_interface, pmid, RoutingConfig::default())
+    }
+
+    fn listen(my_interface: F, pmid: types::Pmid, mut config: RoutingConfig) -> RoutingNode<F> {
         let (event_output, event_input) = mpsc::channel();
-        let pmid = types::Pmid::new();
         let own_id = pmid.get_name();
         let mut cm = crust::ConnectionManager::new(event_output);
-        // TODO: Default Protocol and Port need to be passed down
-        let ports_and_protocols : Vec<PortAndProtocol> = Vec::new();
-        // TODO: Beacon port should be passed down
-        let beacon_port = Some(5483u16);
-        let listeners = match cm.start_listening(ports_and_protocols, beacon_port) {
-            Err(reason) => {
-                println!("Failed to start listening: {:?}", reason);
-                (vec![], None)
-            }
-            Ok(listeners_and_beacon) => listeners_and_beacon
+        let ports_and_protocols = mem::replace(&mut config.ports_and_protocols, vec![]);
+        let (listeners, listen_error) = match cm.start_listening(ports_and_protocols,
+                                                                 config.beacon_port) {
+            Err(reason) => ((vec![], None), Some(RoutingError::from(reason))),
+            Ok(listeners_and_beacon) => (listeners_and_beacon, None)
         };
 
+        let node = RoutingNode::construct(my_interface, pmid, own_id, event_input, cm, listeners,
+                                          config);
+        if let Some(error) = listen_error {
+            node.emit(RoutingEvent::ListeningFailed(error));
+        }
+        node
+    }
+
+    /// Reconstructs a node from a snapshot taken by `snapshot`.  The restored node has no live
+    /// connections and is not listening, and it signs with new keys which don't match its name;
+    /// see `NodeSnapshot` for what else is left out.
+    pub fn restore(snapshot: NodeSnapshot, my_interface: F) -> RoutingNode<F> {
+        RoutingNode::restore_as(snapshot, types::Pmid::new(), my_interface)
+    }
+
+    /// As `restore`, but the node signs with `pmid`, the identity the snapshot was taken of, so
+    /// that peers can verify it.  Panics if `pmid` isn't that identity.
+    pub fn restore_with_pmid(snapshot: NodeSnapshot, pmid: types::Pmid, my_interface: F)
+            -> RoutingNode<F> {
+        assert!(pmid.get_name() == snapshot.own_id &&
+                types::PublicPmid::new(&pmid) == snapshot.public_pmid);
+        RoutingNode::restore_as(snapshot, pmid, my_interface)
+    }
+
+    fn restore_as(snapshot: NodeSnapshot, pmid: types::Pmid, my_interface: F) -> RoutingNode<F> {
+        sodiumoxide::init();  // enable shared global (i.e. safe to multithread now)
+        let (event_output, event_input) = mpsc::channel();
+        let cm = crust::ConnectionManager::new(event_output);
+        let mut node = RoutingNode::construct(my_interface, pmid,
+                                              snapshot.own_id, event_input, cm,
+                                              (snapshot.accepting_on, None),
+                                              RoutingConfig::default());
+        for (fob, endpoints, connected_endpoint) in snapshot.routing_table {
+            node.routing_table.add_node(NodeInfo::new(fob, endpoints, connected_endpoint));
+        }
+        for (peer_id, endpoint) in snapshot.connections {
+            node.all_connections.0.insert(endpoint.clone(), peer_id.clone());
+            node.all_connections.1.insert(peer_id, endpoint);
+        }
+        node.bootstrap_endpoint = snapshot.bootstrap_endpoint;
+        node.bootstrap_node_id = snapshot.bootstrap_node_id;
+        node.next_message_id = snapshot.next_message_id;
+        node
+    }
+
+    /// Captures the current state of this node.  Secret keys are never included.
+    pub fn snapshot(&self) -> NodeSnapshot {
+        NodeSnapshot {
+            own_id: self.own_id.clone(),
+            public_pmid: types::PublicPmid::new(&self.pmid),
+            routing_table: self.routing_table.all_nodes().into_iter()
+                               .map(|node| (node.fob, node.endpoints, node.connected_endpoint))
+                               .collect(),
+            connections: self.all_connections.1.iter()
+                             .map(|(peer_id, endpoint)| (peer_id.clone(), endpoint.clone()))
+                             .collect(),
+            accepting_on: self.accepting_on.clone(),
+            bootstrap_endpoint: self.bootstrap_endpoint.clone(),
+            bootstrap_node_id: self.bootstrap_node_id.clone(),
+            next_message_id: self.next_message_id,
+        }
+    }
+
+    fn construct(my_interface: F, pmid: types::Pmid, own_id: NameType, event_input: Receiver<Event>,
+                 cm: ConnectionManager, listeners: (Vec<Endpoint>, Option<u16>),
+                 config: RoutingConfig) -> RoutingNode<F> {
+        let cm = Arc::new(cm);
         RoutingNode { interface: Box::new(my_interface),
                       pmid : pmid,
                       own_id : own_id.clone(),
                       event_input: event_input,
+                      send_pool: SendPool::new(SEND_THREADS, cm.clone()),
                       connection_manager: cm,
                       all_connections: (HashMap::new(), BTreeMap::new()),
-                      routing_table : RoutingTable::new(own_id),
+                      non_routing_connections: HashMap::new(),
+                      routing_table : RoutingTable::with_group_size(own_id, config.group_size),
                       accepting_on: listeners.0,
+                      external_endpoints: vec![],
                       listening_for_broadcasts_on_port: listeners.1,
                       next_message_id: rand::random::<MessageId>(),
                       bootstrap_endpoint: None,
                       bootstrap_node_id: None,
-                      filter: MessageFilter::with_expiry_duration(Duration::minutes(20)),
-                      public_pmid_cache: LruCache::with_expiry_duration(Duration::minutes(10))
+                      first_node: false,
+                      bootstrap_candidates: Vec::new(),
+                      bootstrap_cache: Vec::new(),
+                      filter: MessageFilter::with_expiry_duration_and_capacity(
+                          config.message_filter_expiry, config.message_filter_capacity),
+                      public_pmid_cache: LruCache::with_expiry_duration_and_capacity(
+                          config.public_pmid_cache_expiry, config.public_pmid_cache_capacity),
+                      public_key_cache: LruCache::with_expiry_duration_and_capacity(
+                          Duration::minutes(PUBLIC_KEY_CACHE_EXPIRY_MINS), PUBLIC_KEY_CACHE_CAPACITY),
+                      data_cache: LruCache::with_expiry_duration_and_capacity(
+                          Duration::seconds(DATA_CACHE_TTL_SECS), DATA_CACHE_CAPACITY),
+                      data_cache_types: HashMap::new(),
+                      cacheable_type_ids: None,
+                      get_response_part_size: GET_RESPONSE_PART_SIZE,
+                      partial_get_responses: LruCache::with_expiry_duration_and_capacity(
+                          Duration::seconds(GET_RESPONSE_PART_TIMEOUT_SECS),
+                          MAX_PARTIAL_GET_RESPONSES),
+                      compression_threshold: Some(COMPRESSION_THRESHOLD),
+                      pending_connects: LruCache::with_expiry_duration(
+                          Duration::seconds(PENDING_CONNECT_SECS)),
+                      vetoed_sends: 0,
+                      max_reply_size: MAX_REPLY_SIZE,
+                      max_message_size: types::MAX_MESSAGE_SIZE,
+                      inbound_bandwidth: HashMap::new(),
+                      max_inbound_bytes: MAX_INBOUND_BYTES,
+                      inbound_window: Duration::seconds(1),
+                      failed_sends: 0,
+                      message_stats: MessageStats::new(),
+                      serialisation: config.serialisation,
+                      message_rates: HashMap::new(),
+                      message_rate: MESSAGE_RATE,
+                      message_burst: MESSAGE_BURST,
+                      rate_limited_messages: 0,
+                      max_nodes_per_ip: None,
+                      min_pmid_leading_bits: 0,
+                      pending_retries: HashMap::new(),
+                      pending_refreshes: HashMap::new(),
+                      refresh_window: Duration::milliseconds(REFRESH_WINDOW_MS),
+                      pending_gets: HashMap::new(),
+                      get_timeout: Duration::seconds(GET_TIMEOUT_SECS),
+                      pending_find_groups: HashMap::new(),
+                      stopped: false,
+                      accumulating_refresh: HashMap::new(),
+                      decode_failures: HashMap::new(),
+                      max_decode_failures: MAX_DECODE_FAILURES,
+                      decode_failure_window: Duration::seconds(DECODE_FAILURE_WINDOW_SECS),
+                      unverified_messages: HashMap::new(),
+                      last_seen: HashMap::new(),
+                      pings_sent: HashMap::new(),
+                      ping_interval: Duration::seconds(PING_INTERVAL_SECS),
+                      ping_deadline: Duration::seconds(PING_DEADLINE_SECS),
+                      unconnected_nodes: HashMap::new(),
+                      connect_timeout: Duration::seconds(CONNECT_TIMEOUT_SECS),
+                      reconnecting: HashMap::new(),
+                      reconnect_attempts: 0,
+                      reconnect_interval: Duration::seconds(RECONNECT_INTERVAL_SECS),
+                      previous_close_group: Vec::new(),
+                      events: config.events
                     }
     }
 
     /// Retrieve something from the network (non mutating) - Direct call
-    pub fn get(&mut self, type_id: u64, name: NameType) {
-        let destination = types::DestinationAddress{ dest: NameType::new(name.get_id()),
-                                                     reply_to: None };
-        let header = MessageHeader::new(self.get_next_message_id(),
-                                        destination, self.our_source_address(),
-                                        types::Authority::Client);
+    /// Returns the id of the message sent, with a receiver the result is delivered on as well as
+    /// to `Interface::handle_get_response`.  Fails if the request couldn't be sent to any peer.  If
+    /// no response arrives within the get timeout, `ResponseError::Timeout` is delivered instead.
+    pub fn get(&mut self, type_id: u64, name: NameType)
+               -> Result<(MessageId, Receiver<Result<Vec<u8>, ResponseError>>), RoutingError> {
+        let destination = DestinationKind::Group(name.clone());
+        let message_id = try!(self.get_next_message_id());
+        let (result_sender, result_receiver) = mpsc::channel();
+        let deadline = SteadyTime::now() + self.get_timeout;
+        self.pending_gets.insert(message_id, (result_sender, deadline));
+        let header = MessageHeader::new(message_id,
+                                        self.destination_address(&destination),
+                                        self.our_source_address(), types::Authority::Client);
         let request = GetData{ requester: self.our_source_address(),
                                name_and_type_id: NameAndTypeId{name: NameType::new(name.get_id()),
                                                                type_id: type_id} };
         let message = RoutingMessage::new(MessageTypeTag::GetData, header,
                                           request, &self.pmid.get_crypto_secret_sign_key());
 
-        // FIXME: We might want to return the result.
-        let _ = encode(&message).map(|msg| self.send_swarm_or_parallel(&name, &msg));
+        if let Err(error) = self.send_to_destination(&destination, &message) {
+            self.pending_gets.remove(&message_id);
+            return Err(error);
+        }
+        Ok((message_id, result_receiver))
+    }
+
+    /// Add something to the network, sent to the group of `destination`
+    /// Returns the id of the message sent, or fails if it couldn't be sent to any peer.
+    pub fn put(&mut self, destination: NameType, content: Box<Sendable>, client_authority: bool)
+               -> Result<MessageId, RoutingError> {
+        let message_id = try!(self.get_next_message_id());
+        self.send_put(message_id, destination, content, client_authority)
     }
 
-    /// Add something to the network, will always go via ClientManager group
-    pub fn put(&mut self, destination: NameType, content: Box<Sendable>, client_authority: bool) {
-        let destination = types::DestinationAddress{ dest: destination, reply_to: None };
+    fn send_put(&mut self, message_id: MessageId, destination: NameType, content: Box<Sendable>,
+                client_authority: bool) -> Result<MessageId, RoutingError> {
+        let destination = DestinationKind::Group(destination);
         let authority = if client_authority {
             types::Authority::Client
         } else {
             types::Authority::ManagedNode
         };
         let request = PutData{ name: content.name(), data: content.serialised_contents() };
-        let header = MessageHeader::new(self.get_next_message_id(),
-                                        destination, self.our_source_address(), authority);
+        let header = MessageHeader::new(message_id, self.destination_address(&destination),
+                                        self.our_source_address(), authority);
         let message = RoutingMessage::new(MessageTypeTag::PutData, header,
                 request, &self.pmid.get_crypto_secret_sign_key());
 
-        // FIXME: We might want to return the result.
-        let _ = encode(&message).map(|msg| self.send_swarm_or_parallel(&self.id(), &msg));
+        self.send_to_destination(&destination, &message)
+    }
+
+    fn send_refresh(&mut self, message_id: MessageId, content: Box<Sendable>)
+            -> Result<MessageId, RoutingError> {
+        let destination = DestinationKind::Group(content.name());
+        let message = self.construct_refresh_msg(message_id, content);
+        self.send_to_destination(&destination, &message)
+    }
+
+    fn construct_refresh_msg(&self, message_id: MessageId, content: Box<Sendable>)
+            -> RoutingMessage {
+        let destination = DestinationKind::Group(content.name());
+        let request = PutData{ name: content.name(), data: content.serialised_contents() };
+        let header = MessageHeader::new(message_id, self.destination_address(&destination),
+                                        self.our_source_address(), types::Authority::NodeManager);
+        RoutingMessage::new(MessageTypeTag::Refresh, header, request,
+                            &self.pmid.get_crypto_secret_sign_key())
+    }
+
+    /// Remove something from the network
+    /// Returns the id of the message sent, or fails if it couldn't be sent to any peer.
+    pub fn delete(&mut self, destination: NameType) -> Result<MessageId, RoutingError> {
+        let request = DeleteData{ name: destination.clone() };
+        let destination = DestinationKind::Group(destination);
+        let header = MessageHeader::new(try!(self.get_next_message_id()),
+                                        self.destination_address(&destination),
+                                        self.our_source_address(), types::Authority::Client);
+        let message = RoutingMessage::new(MessageTypeTag::DeleteData, header,
+                request, &self.pmid.get_crypto_secret_sign_key());
+
+        self.send_to_destination(&destination, &message)
     }
 
     /// Add something to the network
-    pub fn unauthorised_put(&mut self, destination: NameType, content: Box<Sendable>) {
-        let destination = types::DestinationAddress{ dest: destination, reply_to: None };
+    /// Returns the id of the message sent, or fails if it couldn't be sent to any peer.
+    pub fn unauthorised_put(&mut self, destination: NameType, content: Box<Sendable>)
+                            -> Result<MessageId, RoutingError> {
+        let destination = DestinationKind::Group(destination);
         let request = PutData{ name: content.name(), data: content.serialised_contents() };
-        let header = MessageHeader::new(self.get_next_message_id(), destination,
+        let header = MessageHeader::new(try!(self.get_next_message_id()),
+                                        self.destination_address(&destination),
                                         self.our_source_address(), types::Authority::Unknown);
         let message = RoutingMessage::new(MessageTypeTag::UnauthorisedPut, header,
                 request, &self.pmid.get_crypto_secret_sign_key());
 
-        // FIXME: We might want to return the result.
-        let _ = encode(&message).map(|msg| self.send_swarm_or_parallel(&self.id(), &msg));
+        self.send_to_destination(&destination, &message)
     }
 
     /// Refresh the content in the close group nodes of group address content::name.
     /// This method needs to be called when churn is triggered.
     /// all the group members need to call this, otherwise it will not be resolved as a valid
-    /// content.
-    pub fn refresh(&mut self, content: Box<Sendable>) {
-        self.put(content.name(), content, false);
+    /// content.  The group holds each copy until a quorum has arrived and merges them.
+    /// Refreshes of the same content within the refresh window are coalesced; only the latest
+    /// content is sent once the window closes.
+    /// Returns the id the refresh is, or will be, sent under.  A refresh held for the window is
+    /// reported with `MessageDropped` if it then can't be sent to any peer.
+    pub fn refresh(&mut self, content: Box<Sendable>) -> Result<MessageId, RoutingError> {
+        if self.refresh_window == Duration::zero() {
+            let message_id = try!(self.get_next_message_id());
+            return self.send_refresh(message_id, content);
+        }
+        let name = content.name();
+        let (window_start, message_id) = match self.pending_refreshes.remove(&name) {
+            Some((window_start, message_id, _)) => (window_start, message_id),
+            None => (SteadyTime::now(), try!(self.get_next_message_id())),
+        };
+        self.pending_refreshes.insert(name, (window_start, message_id, content));
+        Ok(message_id)
+    }
+
+    /// Asks the group responsible for `target` who its members are.  Each member of the group
+    /// answers; the names from the first answer are delivered on the returned receiver, and
+    /// each later answer delivers only the names not seen before.  The receiver is closed once
+    /// answers have stopped being awaited.
+    pub fn find_group(&mut self, target: NameType) -> Receiver<Vec<NameType>> {
+        let (group_sender, group_receiver) = mpsc::channel();
+        let message_id = match self.get_next_message_id() {
+            Ok(message_id) => message_id,
+            Err(error) => {
+                // the receiver is closed at once, as nothing will be sent to it
+                self.emit(RoutingEvent::MessageDropped(error));
+                return group_receiver;
+            },
+        };
+        let deadline = SteadyTime::now() + Duration::seconds(FIND_GROUP_TIMEOUT_SECS);
+        self.pending_find_groups.insert(message_id, (group_sender, vec![], deadline));
+        let header = MessageHeader::new(message_id,
+                                        types::DestinationAddress{ dest: target.clone(),
+                                                                   reply_to: None },
+                                        self.our_source_address(),
+                                        types::Authority::ManagedNode);
+        let message = RoutingMessage::new(MessageTypeTag::FindGroup, header,
+            FindGroup{ requester_id: self.own_id.clone(), target_id: target.clone() },
+            &self.pmid.get_crypto_secret_sign_key());
+
+        let _ = self.serialise(&message).map(|msg| {
+            self.send_swarm_or_parallel(&target, &MessageTypeTag::FindGroup, &msg)
+        });
+        // we may still be bootstrapping
+        match self.send_to_bootstrap_node(&message) {
+            Ok(()) | Err(RoutingError::NotBootstrapped) => (),
+            Err(error) => self.emit(RoutingEvent::MessageDropped(error)),
+        }
+        group_receiver
     }
 
     /// Mutate something on the network (you must prove ownership) - Direct call
-    pub fn post(&self, destination: NameType, content: Vec<u8>) { unimplemented!() }
+    /// Returns the id of the message sent, or fails if it couldn't be sent to any peer.
+    pub fn post(&mut self, destination: NameType, content: Vec<u8>)
+                -> Result<MessageId, RoutingError> {
+        let request = Post{ name: destination.clone(), data: content };
+        let destination = DestinationKind::Group(destination);
+        let header = MessageHeader::new(try!(self.get_next_message_id()),
+                                        self.destination_address(&destination),
+                                        self.our_source_address(),
+                                        types::Authority::ManagedNode);
+        let message = RoutingMessage::new(MessageTypeTag::Post, header,
+                request, &self.pmid.get_crypto_secret_sign_key());
+
+        self.send_to_destination(&destination, &message)
+    }
 
     pub fn bootstrap(&mut self, bootstrap_list: Option<Vec<Endpoint>>,
                      beacon_port: Option<u16>) -> Result<(), RoutingError> {
-        let bootstrapped_to = try!(self.connection_manager.bootstrap(bootstrap_list, beacon_port)
-                                   .map_err(|_|RoutingError::FailedToBootstrap));
-        self.bootstrap_endpoint = Some(bootstrapped_to);
-        // starts swapping ID with the bootstrap peer
-        self.send_bootstrap_id_request();
+        self.bootstrap_with_retry(bootstrap_list, beacon_port, 1, Duration::zero()).map(|_| ())
+    }
+
+    /// As `bootstrap`, but also handles events until the node has at least one peer in its
+    /// routing table.  Fails with `FailedToBootstrap` if that hasn't happened within `timeout`.
+    pub fn bootstrap_and_wait(&mut self, bootstrap_list: Option<Vec<Endpoint>>,
+                              beacon_port: Option<u16>, timeout: Duration)
+                              -> Result<(), RoutingError> {
+        let deadline = SteadyTime::now() + timeout;
+        try!(self.bootstrap(bootstrap_list, beacon_port));
+        while self.routing_table.size() == 0 {
+            if SteadyTime::now() >= deadline {
+                return Err(RoutingError::FailedToBootstrap);
+            }
+            if !self.try_run() {
+                thread::sleep_ms(10);
+            }
+        }
         Ok(())
     }
 
+    /// As `bootstrap`, but tries the whole list up to `attempts` times, waiting `back_off` between
+    /// attempts, so peers which are still starting up don't fail us.  At least one attempt is
+    /// made.  Returns the number of attempts it took.
+    ///
+    /// Without a `bootstrap_list` the endpoints in our bootstrap cache are tried, if there are any.
+    ///
+    /// Besides the peer the connection manager bootstraps us to, the other peers on the list are
+    /// connected to as well.  Whichever of them answers our id exchange first becomes our
+    /// bootstrap node, and the connections to the others are dropped.
+    pub fn bootstrap_with_retry(&mut self, bootstrap_list: Option<Vec<Endpoint>>,
+                                beacon_port: Option<u16>, attempts: u32,
+                                back_off: Duration) -> Result<u32, RoutingError> {
+        let bootstrap_list = match bootstrap_list {
+            None if !self.bootstrap_cache.is_empty() => Some(self.bootstrap_cache.clone()),
+            bootstrap_list => bootstrap_list,
+        };
+        let mut attempt = 1u32;
+        loop {
+            match self.connection_manager.bootstrap(bootstrap_list.clone(), beacon_port) {
+                Ok(bootstrapped_to) => {
+                    let others: Vec<Endpoint> = bootstrap_list.clone().unwrap_or(vec![])
+                        .into_iter()
+                        .filter(|endpoint| *endpoint != bootstrapped_to)
+                        .collect();
+                    self.bootstrap_candidates = vec![bootstrapped_to.clone()];
+                    self.bootstrap_candidates.extend(others.iter().cloned());
+                    if !others.is_empty() {
+                        self.connection_manager.connect(others);
+                    }
+                    // starts swapping ID with the bootstrap peer
+                    try!(self.send_bootstrap_id_request(&bootstrapped_to));
+                    break;
+                },
+                Err(_) => {
+                    if attempt >= attempts {
+                        return Err(RoutingError::FailedToBootstrap);
+                    }
+                    attempt += 1;
+                    thread::sleep_ms(back_off.num_milliseconds() as u32);
+                }
+            }
+        }
+        Ok(attempt)
+    }
+
+    /// Processes events until every message held for retry after a failed send has been resent or
+    /// dropped, or `timeout` elapses.  Returns whether everything was flushed.  Messages already handed to
+    /// the connection manager are not tracked here.
+    pub fn flush(&mut self, timeout: Duration) -> bool {
+        let deadline = SteadyTime::now() + timeout;
+        while !self.pending_retries.is_empty() {
+            if SteadyTime::now() >= deadline {
+                return false;
+            }
+            if !self.try_run() {
+                thread::sleep_ms(10);
+            }
+        }
+        true
+    }
+
+    /// Shuts the node down: gives held messages a chance to be resent, then drops every
+    /// connection and abandons outstanding gets.  After this `run` does nothing.  The listener
+    /// threads belong to the connection manager and end when it is dropped with the node.
+    pub fn stop(&mut self) {
+        if self.stopped {
+            return;
+        }
+        let _ = self.flush(Duration::milliseconds(STOP_FLUSH_TIMEOUT_MS));
+        self.close();
+    }
+
+    /// Drops every connection and forgets everything outstanding, without flushing.
+    fn close(&mut self) {
+        self.stopped = true;
+        for endpoint in self.all_connections.0.keys().chain(self.non_routing_connections.values()) {
+            self.connection_manager.drop_node(endpoint.clone());
+        }
+        self.all_connections.0.clear();
+        self.all_connections.1.clear();
+        self.non_routing_connections.clear();
+        self.pending_retries.clear();
+        self.pending_refreshes.clear();
+        self.pending_gets.clear();
+        self.pending_find_groups.clear();
+        self.unverified_messages.clear();
+        self.accumulating_refresh.clear();
+    }
+
+    /// Handles every event currently available from the connection manager, then returns.  If
+    /// the connection manager has gone away the node stops and emits `TransportClosed`.
     pub fn run(&mut self) {
-        let event = self.event_input.try_recv();
+        let _ = self.try_run();
+    }
+
+    /// As `run`, but returns whether any event was handled, so that callers can sleep while
+    /// the node is idle.
+    pub fn try_run(&mut self) -> bool {
+        if self.stopped {
+            return false;
+        }
+        self.housekeeping();
+        let mut events = Vec::new();
+        let closed = self.drain_events(&mut events);
+        let handled = !events.is_empty();
+        self.handle_events(events);
+        if closed {
+            self.handle_transport_closed();
+        }
+        handled
+    }
+
+    /// As `run`, but blocks until at least one event has been handled or the node has stopped.
+    /// Timers are still seen to while it waits, so an idle node pings its peers and times out
+    /// its requests as one driven by `run` would.
+    pub fn run_blocking(&mut self) {
+        // nothing wakes a blocked receive when a timer falls due, so wait in short ticks instead
+        let tick = ::std::cmp::max(1, ::std::cmp::min(HOUSEKEEPING_TICK_MS,
+                                                      self.refresh_window.num_milliseconds()));
+        while !self.try_run() && !self.stopped {
+            thread::sleep_ms(tick as u32);
+        }
+    }
+
+    /// Does whatever is due on a timer: refreshes, pings, reconnects and expiring what has been
+    /// waited on for too long.
+    fn housekeeping(&mut self) {
+        self.send_due_refreshes();
+        self.ping_silent_peers();
+        self.drop_unconnected_nodes();
+        self.retry_reconnects();
+        self.expire_pending_gets();
+        self.expire_pending_retries();
+        self.expire_pending_find_groups();
+        self.expire_unverified_messages();
+        self.expire_accumulating_refreshes();
+    }
+
+    /// Whether the node has been stopped, by `stop` or because its transport closed.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Moves every queued event into `events`.  Returns true if the connection manager has
+    /// dropped its end of the channel.
+    fn drain_events(&mut self, events: &mut Vec<crust::Event>) -> bool {
+        loop {
+            match self.event_input.try_recv() {
+                Ok(event) => events.push(event),
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => return true,
+            }
+        }
+    }
+
+    /// Nothing can be sent or received once the connection manager has gone, so there is nothing
+    /// to flush; the node is stopped and the application told.
+    fn handle_transport_closed(&mut self) {
+        self.pending_retries.clear();
+        self.stop();
+        self.emit(RoutingEvent::TransportClosed);
+    }
 
-        if event.is_err() { return; }
+    /// Lost connections are handled first, so that messages still queued from a peer which has
+    /// gone are not acted upon.
+    fn handle_events(&mut self, events: Vec<crust::Event>) {
+        let mut lost = Vec::new();
+        for event in events.iter() {
+            if let crust::Event::LostConnection(ref endpoint) = *event {
+                lost.push(endpoint.clone());
+            }
+        }
+        for endpoint in lost.iter() {
+            self.handle_lost_connection(endpoint.clone());
+        }
 
-        match event.unwrap() {
-            crust::Event::NewMessage(endpoint, bytes) => {
-                if self.all_connections.0.contains_key(&endpoint) {
-                    let peer_id = self.all_connections.0.get(&endpoint).unwrap().clone();
-                    if self.message_received(&peer_id, bytes).is_err() {
-                        // println!("failed to Parse message !!! check  from - {:?} ", peer_id);
-                        // let _ = self.connection_manager.drop_node(id);  // discuss : no need to drop
+        for event in events {
+            match event {
+                crust::Event::NewMessage(endpoint, bytes) => {
+                    if !lost.contains(&endpoint) {
+                        self.handle_new_message(endpoint, bytes);
                     }
-                } else {
-                    // reply with own_id if the incoming msg is BootstrapIdRequest
-                    // record the peer_id if the incoming msg is BootstrapIdResponse
-                    let _ = self.bootstrap_message_received(endpoint, bytes);
+                },
+                crust::Event::NewConnection(endpoint) => {
+                    self.handle_connect(endpoint);
+                },
+                crust::Event::LostConnection(_) => (),
+            }
+        }
+    }
+
+    fn handle_new_message(&mut self, endpoint: Endpoint, bytes: Bytes) {
+        let known_peer = self.all_connections.0.get(&endpoint).cloned();
+        // bandwidth is accounted against what actually crossed the wire
+        if let Some(ref peer_id) = known_peer {
+            // any traffic at all shows the peer is alive
+            self.last_seen.insert(peer_id.clone(), SteadyTime::now());
+            self.pings_sent.remove(peer_id);
+            if !self.check_inbound_bandwidth(&endpoint, peer_id, bytes.len()) {
+                return;
+            }
+        }
+        // foreign traffic is refused here, before any attempt to decode it
+        let bytes = match compression::unpack_as(&bytes, self.serialisation, self.max_message_size) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                self.emit(RoutingEvent::MessageDropped(error));
+                if let Some(peer_id) = known_peer {
+                    self.record_decode_failure(&endpoint, &peer_id);
                 }
+                return;
             },
-            crust::Event::NewConnection(endpoint) => {
-                self.handle_connect(endpoint);
-            },
-            crust::Event::LostConnection(endpoint) => {
-                self.handle_lost_connection(endpoint);
+        };
+        if let Some(peer_id) = known_peer {
+            // the count is reset by `message_received` once a message has been handled
+            match self.message_received(&peer_id, bytes) {
+                Err(error @ RoutingError::CborError(_)) |
+                Err(error @ RoutingError::BincodeError(_)) |
+                Err(error @ RoutingError::MalformedMessage { .. }) => {
+                    self.emit(RoutingEvent::MessageDropped(error));
+                    self.record_decode_failure(&endpoint, &peer_id);
+                },
+                // counted, and reported once the peer stays over rate
+                Err(RoutingError::RateLimitExceeded(_)) => (),
+                Err(RoutingError::FilterCheckFailed) | Ok(()) => (),
+                Err(error) => self.emit(RoutingEvent::MessageDropped(error)),
+            }
+        } else if let Some(client_id) = self.non_routing_peer(&endpoint) {
+            match self.message_received(&client_id, bytes) {
+                Err(RoutingError::RateLimitExceeded(_)) | Ok(()) => (),
+                Err(error) => self.emit(RoutingEvent::MessageDropped(error)),
+            }
+        } else {
+            // reply with own_id if the incoming msg is BootstrapIdRequest
+            // record the peer_id if the incoming msg is BootstrapIdResponse
+            if let Err(error) = self.bootstrap_message_received(endpoint, bytes) {
+                self.emit(RoutingEvent::MessageDropped(error));
+            }
+        }
+    }
+
+    /// Counts a message from `peer_id` which failed to unpack or decode.  A peer sending
+    /// `max_decode_failures` such messages in a row, within the decode failure window, is dropped.
+    /// Only a message which decodes, verifies and hasn't been seen before breaks the run.
+    fn record_decode_failure(&mut self, peer_endpoint: &Endpoint, peer_id: &NameType) {
+        let now = SteadyTime::now();
+        let window = self.decode_failure_window;
+        let failures = {
+            let entry = self.decode_failures.entry(peer_id.clone()).or_insert((0, now));
+            if now - entry.1 > window {
+                *entry = (0, now);
             }
+            entry.0 += 1;
+            entry.0
+        };
+        if failures >= self.max_decode_failures {
+            self.drop_peer(peer_endpoint.clone());
+        }
+    }
+
+    /// The client, connected to us but not in our routing table, on `endpoint`.
+    fn non_routing_peer(&self, endpoint: &Endpoint) -> Option<NameType> {
+        self.non_routing_connections.iter()
+            .find(|&(_, client_endpoint)| client_endpoint == endpoint)
+            .map(|(client_id, _)| client_id.clone())
+    }
+
+    /// Disconnects from a misbehaving peer and forgets it.
+    fn drop_peer(&mut self, peer_endpoint: Endpoint) {
+        self.connection_manager.drop_node(peer_endpoint.clone());
+        self.handle_lost_connection(peer_endpoint);
+    }
+
+    fn emit(&self, event: RoutingEvent) {
+        if let Some(ref events) = self.events {
+            // nobody listening any more is fine
+            let _ = events.send(event);
         }
     }
 
-    fn send_bootstrap_id_request(&mut self) {
+    fn send_bootstrap_id_request(&mut self, endpoint: &Endpoint) -> Result<(), RoutingError> {
         let message = RoutingMessage::new(MessageTypeTag::BootstrapIdRequest,
-            MessageHeader::new(self.get_next_message_id(),
+            MessageHeader::new(try!(self.get_next_message_id()),
                 types::DestinationAddress{ dest: NameType::new([0u8; NAME_TYPE_LEN]), reply_to: None },
                 types::SourceAddress{ from_node: self.id(), from_group: None, reply_to: None },
                 types::Authority::ManagedNode),
-            BootstrapIdRequest { sender_id: self.id() }, &self.pmid.get_crypto_secret_sign_key());
-        self.send_to_bootstrap_node(&message);
+            BootstrapIdRequest { sender_id: self.id(), protocol_version: types::PROTOCOL_VERSION },
+            &self.pmid.get_crypto_secret_sign_key());
+        let message = try!(self.serialise(&message));
+        try!(self.send_to(endpoint, message));
+        self.message_stats.count_sent(&MessageTypeTag::BootstrapIdRequest);
+        Ok(())
     }
 
     fn send_bootstrap_id_response(&mut self, peer_endpoint: Endpoint) {
+        let message_id = match self.get_next_message_id() {
+            Ok(message_id) => message_id,
+            Err(error) => return self.emit(RoutingEvent::MessageDropped(error)),
+        };
         let message = RoutingMessage::new(MessageTypeTag::BootstrapIdResponse,
-            MessageHeader::new(self.get_next_message_id(),
+            MessageHeader::new(message_id,
                 types::DestinationAddress{ dest: NameType::new([0u8; NAME_TYPE_LEN]), reply_to: None },
                 types::SourceAddress{ from_node: self.id(), from_group: None, reply_to: None },
                 types::Authority::ManagedNode),
-            BootstrapIdResponse { sender_id: self.id() }, &self.pmid.get_crypto_secret_sign_key());
+            BootstrapIdResponse { sender_id: self.id(), protocol_version: types::PROTOCOL_VERSION },
+            &self.pmid.get_crypto_secret_sign_key());
 
         // need to send to bootstrap node as we are not yet connected to anyone else
-        let _ = encode(&message).map(|msg| self.connection_manager.send(peer_endpoint, msg));
+        let sent = self.serialise(&message).ok().map_or(false, |msg| self.send_to(&peer_endpoint, msg).is_ok());
+        if sent {
+            self.message_stats.count_sent(&MessageTypeTag::BootstrapIdResponse);
+        }
     }
 
     fn handle_bootstrap_id_response(&mut self, peer_endpoint: Endpoint, bytes: Bytes, is_client: bool) {
@@ -257,9 +1078,32 @@ impl<F> RoutingNode<F> where F: Interface {
             return;
         }
         let bootstrap_id_response_msg = bootstrap_id_response_msg.unwrap();
-        assert!(self.bootstrap_node_id.is_none());
-        assert_eq!(self.bootstrap_endpoint, Some(peer_endpoint.clone()));
+        if bootstrap_id_response_msg.protocol_version != types::PROTOCOL_VERSION {
+            self.connection_manager.drop_node(peer_endpoint);
+            self.emit(RoutingEvent::MessageDropped(RoutingError::IncompatibleProtocolVersion(
+                bootstrap_id_response_msg.protocol_version)));
+            return;
+        }
+        if self.bootstrap_endpoint.as_ref() == Some(&peer_endpoint) {
+            // a repeat from our bootstrap node, whose connection we keep
+            return;
+        }
+        if self.bootstrap_node_id.is_some() || !self.bootstrap_candidates.contains(&peer_endpoint) {
+            // a late responder, or one we never asked
+            self.connection_manager.drop_node(peer_endpoint);
+            return;
+        }
+        // the first candidate to answer is our bootstrap node; the others are let go
+        for candidate in self.bootstrap_candidates.iter() {
+            if *candidate != peer_endpoint {
+                self.connection_manager.drop_node(candidate.clone());
+            }
+        }
+        self.bootstrap_candidates.clear();
+        self.cache_bootstrap_endpoint(peer_endpoint.clone());
+        self.bootstrap_endpoint = Some(peer_endpoint.clone());
         self.bootstrap_node_id = Some(bootstrap_id_response_msg.sender_id.clone());
+        self.emit(RoutingEvent::Bootstrapped(bootstrap_id_response_msg.sender_id.clone()));
 
         self.all_connections.0.insert(peer_endpoint.clone(), bootstrap_id_response_msg.sender_id.clone());
         self.all_connections.1.insert(bootstrap_id_response_msg.sender_id.clone(), peer_endpoint.clone());
@@ -269,13 +1113,24 @@ impl<F> RoutingNode<F> where F: Interface {
 
         // connect to close group
         let own_id = Some(self.id());
-        let messsge = self.construct_find_group_msg(own_id);
-        self.send_to_bootstrap_node(&messsge);
+        let sent = self.construct_find_group_msg(own_id)
+                       .and_then(|message| self.send_to_bootstrap_node(&message));
+        if let Err(error) = sent {
+            self.emit(RoutingEvent::MessageDropped(error));
+        }
+    }
+
+    /// Records an endpoint we connected to successfully, most recent first.  Once the cache is
+    /// full the least recently successful endpoint is evicted.
+    fn cache_bootstrap_endpoint(&mut self, endpoint: Endpoint) {
+        self.bootstrap_cache.retain(|cached| *cached != endpoint);
+        self.bootstrap_cache.insert(0, endpoint);
+        self.bootstrap_cache.truncate(BOOTSTRAP_CACHE_SIZE);
     }
 
-    fn put_own_public_pmid(&mut self) {
+    fn put_own_public_pmid(&mut self) -> Result<(), RoutingError> {
         let our_public_pmid: types::PublicPmid = types::PublicPmid::new(&self.pmid);
-        let message_id = self.get_next_message_id();
+        let message_id = try!(self.get_next_message_id());
         let destination = types::DestinationAddress{ dest: our_public_pmid.name.clone(), reply_to: None };
         let source = types::SourceAddress{ from_node: self.id(), from_group: None,
                                             reply_to: self.bootstrap_node_id.clone() };
@@ -284,130 +1139,509 @@ impl<F> RoutingNode<F> where F: Interface {
         let header = MessageHeader::new(message_id, destination, source, authority);
         let message = RoutingMessage::new(MessageTypeTag::PutPublicPmid, header,
             request, &self.pmid.get_crypto_secret_sign_key());
-        self.send_to_bootstrap_node(&message);
+        self.send_to_bootstrap_node(&message)
     }
 
     fn handle_connect(&mut self, peer_endpoint: Endpoint) {
-        if self.routing_table.mark_as_connected(&peer_endpoint) {
+        if self.bootstrap_node_id.is_none() && self.bootstrap_candidates.contains(&peer_endpoint) {
+            if let Err(error) = self.send_bootstrap_id_request(&peer_endpoint) {
+                self.emit(RoutingEvent::MessageDropped(error));
+            }
+            return;
+        }
+        let peer_id = match self.routing_table.lookup_endpoint(&peer_endpoint) {
+            Some(peer_id) => peer_id,
+            None => match self.reconnected_node(&peer_endpoint) {
+                Some(peer_id) => peer_id,
+                None => return,  // FIXME non routing connection
+            },
+        };
+        if !self.routing_table.mark_as_connected(&peer_endpoint) {
             return;
         }
-        // FIXME
-   }
+        self.reconnecting.remove(&peer_id);
+        self.all_connections.0.insert(peer_endpoint.clone(), peer_id.clone());
+        self.all_connections.1.insert(peer_id.clone(), peer_endpoint.clone());
+        self.unconnected_nodes.remove(&peer_id);
+        self.cache_bootstrap_endpoint(peer_endpoint.clone());
+        self.emit(RoutingEvent::Connected(peer_id.clone()));
+        // retry, once only, anything which failed to send before we reconnected to this peer
+        let (_, messages) = self.pending_retries.remove(&peer_id)
+                                .unwrap_or((SteadyTime::now(), vec![]));
+        for message in messages {
+            if let Err(error) = self.send_to(&peer_endpoint, message) {
+                self.failed_sends += 1;
+                self.emit(RoutingEvent::MessageDropped(From::from(error)));
+            }
+        }
+    }
 
     fn handle_lost_connection(&mut self, peer_endpoint: Endpoint) {
+        if let Some(client_id) = self.non_routing_peer(&peer_endpoint) {
+            self.non_routing_connections.remove(&client_id);
+            return;
+        }
         let removed_entry = self.all_connections.0.remove(&peer_endpoint);
         if removed_entry.is_some() {
             let peer_id = removed_entry.unwrap();
-            self.routing_table.drop_node(&peer_id);
+            let lost_node = self.routing_table.all_nodes().into_iter()
+                                .find(|node| node.id() == peer_id);
+            self.drop_node(&peer_id);
+            // a peer still in range may only have had a transient disconnect
+            if let Some(lost_node) = lost_node {
+                if self.reconnect_attempts > 0 && self.routing_table.check_node(&peer_id) {
+                    self.reconnecting.insert(peer_id.clone(), (lost_node, 0, SteadyTime::now()));
+                    self.reconnect(&peer_id);
+                }
+            }
             self.all_connections.1.remove(&peer_id);
-          // TODO : remove from the non routing list
-          // handle_churn
+            self.inbound_bandwidth.remove(&peer_id);
+            self.message_rates.remove(&peer_id);
+            self.decode_failures.remove(&peer_id);
+            self.last_seen.remove(&peer_id);
+            self.pings_sent.remove(&peer_id);
+            self.emit(RoutingEvent::Disconnected(peer_id));
         }
     }
 
-    //TODO(team) This method needs to be triggered when routing table close group changes
-    fn on_churn(&mut self, close_group: Vec<NameType>) {
-        let actions = self.interface.handle_churn(close_group);
-        self.invoke_routing_actions(actions);
+    /// Adds a node to the routing table, calling `on_churn` if our close group changed.
+    fn add_node(&mut self, node_info: NodeInfo) -> (bool, Option<NodeInfo>) {
+        let close_group = self.routing_table.close_group_snapshot();
+        let peer_id = node_info.id();
+        let connected = node_info.connected_endpoint.is_some();
+        let result = self.routing_table.add_node(node_info);
+        self.check_for_churn(close_group);
+        if result.0 {
+            if !connected {
+                self.unconnected_nodes.insert(peer_id.clone(), SteadyTime::now());
+            }
+            self.replay_unverified_messages(&peer_id);
+        }
+        result
     }
 
-    fn invoke_routing_actions(&mut self, routing_actions: Vec<node_interface::RoutingNodeAction>) {
-        for routing_action in routing_actions {
-            match routing_action {
-                node_interface::RoutingNodeAction::Put { destination: x, content: y, is_client: z, } => self.put(x, y, z),
-                node_interface::RoutingNodeAction::Get { type_id: x, name: y, } => self.get(x, y),
-                node_interface::RoutingNodeAction::Refresh { content: x, } => self.refresh(x),
-                node_interface::RoutingNodeAction::Post => unimplemented!(),
-                node_interface::RoutingNodeAction::None => (),
+    /// Makes another attempt to reconnect to the lost peer `peer_id`: a connect request is routed
+    /// to it, and its last known endpoints are dialled directly.
+    fn reconnect(&mut self, peer_id: &NameType) {
+        let endpoints = match self.reconnecting.get_mut(peer_id) {
+            Some(&mut (ref node, ref mut attempts, ref mut last_attempt)) => {
+                *attempts += 1;
+                *last_attempt = SteadyTime::now();
+                node.endpoints.clone()
+            },
+            None => return,
+        };
+        self.pending_connects.remove(peer_id);
+        self.check_and_send_connect_request_msg(peer_id);
+        self.connection_manager.connect(endpoints);
+    }
+
+    /// Retries lost peers whose last reconnect attempt is a reconnect interval old, giving up on
+    /// those which have had all their attempts.
+    fn retry_reconnects(&mut self) {
+        let now = SteadyTime::now();
+        let due = self.reconnecting.iter()
+                      .filter(|&(_, &(_, _, last_attempt))| now - last_attempt >= self.reconnect_interval)
+                      .map(|(peer_id, &(_, attempts, _))| (peer_id.clone(), attempts))
+                      .collect::<Vec<_>>();
+        for (peer_id, attempts) in due {
+            if attempts >= self.reconnect_attempts {
+                self.reconnecting.remove(&peer_id);
+                self.drop_pending_retries(&peer_id);
+                self.emit(RoutingEvent::ReconnectFailed(peer_id));
+            } else {
+                self.reconnect(&peer_id);
             }
         }
     }
 
-    fn message_received(&mut self, peer_id: &NameType, serialised_message: Bytes) -> RecvResult {
-        // Parse
-        let message = try!(decode::<RoutingMessage>(&serialised_message));
+    /// Drops the messages held for retry to peers which haven't been redialled in time.
+    fn expire_pending_retries(&mut self) {
+        let now = SteadyTime::now();
+        let expired = self.pending_retries.iter()
+                          .filter(|&(_, &(deadline, _))| deadline <= now)
+                          .map(|(peer_id, _)| peer_id.clone())
+                          .collect::<Vec<_>>();
+        for peer_id in expired {
+            self.drop_pending_retries(&peer_id);
+        }
+    }
 
-        let header = message.message_header;
-        let body = message.serialised_body;
-        // filter check
-        if self.filter.check(&header.get_filter()) {
-            // should just return quietly
-            return Err(RoutingError::FilterCheckFailed);
+    /// Gives up on the messages held for retry to `peer_id`, reporting each with
+    /// `MessageDropped`.
+    fn drop_pending_retries(&mut self, peer_id: &NameType) {
+        if let Some((_, messages)) = self.pending_retries.remove(peer_id) {
+            for _ in messages {
+                self.emit(RoutingEvent::MessageDropped(RoutingError::NotConnected(peer_id.clone())));
+            }
         }
-        // add to filter
-        self.filter.add(header.get_filter());
+    }
 
-        // add to cache
-        if message.message_type == MessageTypeTag::GetDataResponse {
-            let get_data_response = try!(decode::<GetDataResponse>(&body));
-            let _ = get_data_response.data.map(|data| {
-                if data.len() != 0 {
-                    let _ = self.mut_interface().handle_cache_put(
-                        header.from_authority(), header.from(), data);
-                }
-            });
+    /// Answers the gets whose deadline has passed with `ResponseError::Timeout` and forgets them.
+    fn expire_pending_gets(&mut self) {
+        let now = SteadyTime::now();
+        let expired = self.pending_gets.iter()
+                          .filter(|&(_, &(_, deadline))| deadline <= now)
+                          .map(|(message_id, _)| *message_id)
+                          .collect::<Vec<_>>();
+        for message_id in expired {
+            if let Some((result_sender, _)) = self.pending_gets.remove(&message_id) {
+                // the caller may have dropped the receiver, which is fine
+                let _ = result_sender.send(Err(ResponseError::Timeout));
+            }
         }
+    }
 
-        // cache check / response
-        if message.message_type == MessageTypeTag::GetData {
-            let get_data = try!(decode::<GetData>(&body));
+    /// Puts a lost peer reached again at `peer_endpoint` back in the routing table, returning its
+    /// id.
+    fn reconnected_node(&mut self, peer_endpoint: &Endpoint) -> Option<NameType> {
+        let peer_id = match self.reconnecting.iter()
+                                .find(|&(_, &(ref node, _, _))| node.endpoints.contains(peer_endpoint)) {
+            Some((peer_id, _)) => peer_id.clone(),
+            None => return None,
+        };
+        let (node, _, _) = self.reconnecting.remove(&peer_id).unwrap();
+        if !self.add_node(NodeInfo::new(node.fob, node.endpoints, None)).0 {
+            return None;
+        }
+        Some(peer_id)
+    }
 
-            let retrieved_data = self.mut_interface().handle_cache_get(
-                get_data.name_and_type_id.type_id.clone() as u64,
-                get_data.name_and_type_id.name.clone(),
-                header.from_authority(),
-                header.from());
-
-            match retrieved_data {
-                Ok(action) => match action {
-                    Action::Reply(data) => {
-                        let reply = self.construct_get_data_response_msg(&header, &get_data, data);
-                        return encode(&reply).map(|reply| {
-                            self.send_swarm_or_parallel(&header.send_to().dest, &reply);
-                        }).map_err(From::from);
-                    },
-                    _ => (),
-                },
-                Err(_) => (),
-            };
+    /// Drops routing table entries whose connection hasn't come up within the connect timeout,
+    /// so that they aren't picked as targets.
+    fn drop_unconnected_nodes(&mut self) {
+        let now = SteadyTime::now();
+        let timed_out = self.unconnected_nodes.iter()
+                            .filter(|&(_, added)| now - *added >= self.connect_timeout)
+                            .map(|(peer_id, _)| peer_id.clone())
+                            .collect::<Vec<_>>();
+        for peer_id in timed_out {
+            self.drop_node(&peer_id);
         }
+    }
 
-        self.send_swarm_or_parallel(&header.destination.dest, &serialised_message);
+    /// Returns true if adding `node_info` would put more than `max_nodes_per_ip` routing table
+    /// entries on one of its IP addresses.
+    fn ip_limit_reached(&self, node_info: &NodeInfo) -> bool {
+        let max_nodes = match self.max_nodes_per_ip {
+            Some(max_nodes) => max_nodes,
+            None => return false,
+        };
+        let ips_of = |node: &NodeInfo| -> Vec<String> {
+            node.endpoints.iter().chain(node.connected_endpoint.iter())
+                .map(|endpoint| endpoint_ip(endpoint)).collect()
+        };
+        let table = self.routing_table.all_nodes();
+        ips_of(node_info).iter().any(|ip| {
+            table.iter().filter(|node| node.id() != node_info.id() && ips_of(node).contains(ip))
+                 .count() >= max_nodes
+        })
+    }
 
-        // handle relay request/response
-        let relay_response = header.destination.reply_to.is_some() &&
-                             header.destination.dest == self.own_id;
-        if relay_response {
-            if self.all_connections.1.contains_key(&header.destination.reply_to.clone().unwrap()) {
-                // TODO : or shall have a separate nrt table recording all clients connecting to this node?
-                let relay_to = self.all_connections.1.get(&header.destination.reply_to.clone().unwrap()).unwrap().clone();
-                // println!("{:?} relay response sent to nrt {:?} {}", self.own_id, header.destination.reply_to,
-                //          match relay_to.clone() { Tcp(socket_addr) => socket_addr } );
-                let _ = self.send_to(&relay_to, serialised_message);
-            } else {
-                // TODO : what shall happen to relaying message ? routing_node choosing a closest node ?
-                for endpoint in self.all_connections.0.keys() {
-                    println!("relaying response to {}", match endpoint.clone() { Tcp(socket_addr) => socket_addr });
-                    let _ = self.send_to(&endpoint, serialised_message);
-                    return Ok(());
+    /// Drops a node from the routing table, calling `on_churn` if our close group changed.
+    fn drop_node(&mut self, peer_id: &NameType) {
+        let close_group = self.routing_table.close_group_snapshot();
+        self.routing_table.drop_node(peer_id);
+        self.unconnected_nodes.remove(peer_id);
+        self.check_for_churn(close_group);
+    }
+
+    /// Checks the signature of a message against its sender's public sign key.  Returns
+    /// `Ok(false)` if the key isn't known yet and the message has been held back instead.  Only
+    /// so many messages are held, and only for so long; one discarded unchecked is reported with
+    /// `MessageDropped`.
+    /// Messages relayed for clients and bootstrapping nodes are not checked.  From senders still
+    /// unknown to us, only the bootstrap, connect and public pmid messages a joining node needs
+    /// to make itself known are let through unchecked; anything else, including messages we only
+    /// pass on, is held until the key is known.
+    fn check_signature(&mut self, message: &RoutingMessage, serialised_message: &Bytes)
+            -> Result<bool, RoutingError> {
+        if message.message_header.is_relayed() {
+            return Ok(true);
+        }
+        let sender = message.message_header.from_node();
+        match self.public_sign_key(&sender) {
+            Some(public_sign_key) => {
+                if message.verify(&public_sign_key) {
+                    Ok(true)
+                } else {
+                    Err(RoutingError::SignatureCheckFailed)
+                }
+            },
+            None => {
+                match message.message_type {
+                    MessageTypeTag::BootstrapIdRequest | MessageTypeTag::BootstrapIdResponse |
+                    MessageTypeTag::ConnectRequest | MessageTypeTag::ConnectResponse |
+                    MessageTypeTag::PutPublicPmid => return Ok(true),
+                    _ => (),
+                }
+                if !self.unverified_messages.contains_key(&sender) &&
+                   self.unverified_messages.len() >= MAX_UNVERIFIED_SENDERS {
+                    return Err(RoutingError::UnknownSender(sender));
                 }
+                let deadline = SteadyTime::now() + Duration::seconds(UNVERIFIED_MESSAGE_TIMEOUT_SECS);
+                let full = {
+                    let held = self.unverified_messages.entry(sender.clone()).or_insert(Vec::new());
+                    let full = held.len() >= MAX_UNVERIFIED_MESSAGES;
+                    if full {
+                        let _ = held.remove(0);
+                    }
+                    held.push((serialised_message.clone(), deadline));
+                    full
+                };
+                if full {
+                    self.emit(RoutingEvent::MessageDropped(RoutingError::UnknownSender(sender)));
+                }
+                Ok(false)
             }
         }
+    }
 
-        // TODO(prakash)
+    /// The public sign key of a node, if it is us, in our routing table or in the public pmid cache.
+    fn public_sign_key(&mut self, name: &NameType) -> Option<types::PublicSignKey> {
+        if *name == self.own_id {
+            return Some(types::PublicSignKey::new(self.pmid.get_crypto_public_sign_key()));
+        }
+        match self.routing_table.get_public_sign_key(name) {
+            Some(public_sign_key) => Some(public_sign_key),
+            None => self.public_pmid_cache.get(name)
+                        .map(|public_pmid| public_pmid.public_sign_key.clone()),
+        }
+    }
 
-        if !self.address_in_close_group_range(&header.destination.dest) {
-            println!("{:?} not for us ", self.own_id);
+    /// Handles messages which were held back because the sender's public sign key wasn't known.
+    /// They were rate limited and counted when they arrived, so only their signature is checked
+    /// again now.
+    fn replay_unverified_messages(&mut self, sender: &NameType) {
+        let held = match self.unverified_messages.remove(sender) {
+            Some(held) => held,
+            None => return,
+        };
+        for (serialised_message, _) in held {
+            if let Err(error) = self.replay_unverified_message(sender, serialised_message) {
+                self.emit(RoutingEvent::MessageDropped(error));
+            }
+        }
+    }
+
+    fn replay_unverified_message(&mut self, sender: &NameType, serialised_message: Bytes)
+            -> RecvResult {
+        let message = try!(self.serialisation.deserialise::<RoutingMessage>(&serialised_message));
+        if !try!(self.check_signature(&message, &serialised_message)) {
             return Ok(());
         }
+        self.handle_verified_message(sender, message, serialised_message)
+    }
 
-        // Drop message before Sentinel check if it is a direct message type (Connect, ConnectResponse)
-        // and this node is in the group but the message destination is another group member node.
+    /// Discards held messages whose sender's public sign key hasn't been learnt in time.
+    fn expire_unverified_messages(&mut self) {
+        let now = SteadyTime::now();
+        let mut dropped = Vec::new();
+        let mut emptied = Vec::new();
+        for (sender, held) in self.unverified_messages.iter_mut() {
+            let before = held.len();
+            held.retain(|&(_, deadline)| deadline > now);
+            for _ in held.len()..before {
+                dropped.push(sender.clone());
+            }
+            if held.is_empty() {
+                emptied.push(sender.clone());
+            }
+        }
+        for sender in emptied {
+            let _ = self.unverified_messages.remove(&sender);
+        }
+        for sender in dropped {
+            self.emit(RoutingEvent::MessageDropped(RoutingError::UnknownSender(sender)));
+        }
+    }
+
+    fn check_for_churn(&mut self, old_close_group: Vec<NameType>) {
+        let close_group = self.routing_table.close_group_snapshot();
+        if close_group != old_close_group {
+            self.on_churn(old_close_group, close_group);
+        }
+    }
+
+    fn on_churn(&mut self, old_close_group: Vec<NameType>, close_group: Vec<NameType>) {
+        self.previous_close_group = old_close_group;
+        self.emit(RoutingEvent::ChurnStarted);
+        let actions = self.interface.handle_churn(close_group);
+        self.invoke_routing_actions(actions);
+    }
+
+    fn invoke_routing_actions(&mut self, routing_actions: Vec<node_interface::RoutingNodeAction>) {
+        for routing_action in routing_actions {
+            let result = match routing_action {
+                node_interface::RoutingNodeAction::Put { destination: x, content: y, is_client: z, } => self.put(x, y, z).map(|_| ()),
+                node_interface::RoutingNodeAction::Get { type_id: x, name: y, } => self.get(x, y).map(|_| ()),
+                node_interface::RoutingNodeAction::Refresh { content: x, } => self.refresh(x).map(|_| ()),
+                node_interface::RoutingNodeAction::Post { destination: x, content: y, } => self.post(x, y).map(|_| ()),
+                node_interface::RoutingNodeAction::None => Ok(()),
+            };
+            if let Err(error) = result {
+                self.emit(RoutingEvent::MessageDropped(error));
+            }
+        }
+    }
+
+    fn message_received(&mut self, peer_id: &NameType, serialised_message: Bytes) -> RecvResult {
+        if !self.check_message_rate(peer_id) {
+            return Err(RoutingError::RateLimitExceeded(peer_id.clone()));
+        }
+        // Parse
+        let message = try!(self.serialisation.deserialise::<RoutingMessage>(&serialised_message));
+        self.message_stats.count_received(&message.message_type);
+        // only a reflected copy of one of our own messages, or a forgery, claims to come from us
+        if message.message_header.source.from_node == self.own_id &&
+           !message.message_header.is_relayed() {
+            return Err(RoutingError::MessageFromSelf);
+        }
+        if !try!(self.check_signature(&message, &serialised_message)) {
+            // held until the sender's public sign key is known
+            return Ok(());
+        }
+        let result = self.handle_verified_message(peer_id, message, serialised_message);
+        if result.is_ok() {
+            let _ = self.decode_failures.remove(peer_id);
+        }
+        result
+    }
+
+    fn handle_verified_message(&mut self, peer_id: &NameType, message: RoutingMessage,
+                               serialised_message: Bytes) -> RecvResult {
+        // a body which fails to decode is reported along with the type it claimed to be
+        let type_tag = message.message_type.clone();
+        let result = self.handle_routing_message(peer_id, message, serialised_message);
+        result.map_err(|error| match error {
+            RoutingError::CborError(source) =>
+                RoutingError::MalformedMessage { type_tag: type_tag, source: source },
+            error => error,
+        })
+    }
+
+    fn handle_routing_message(&mut self, peer_id: &NameType, message: RoutingMessage,
+                              serialised_message: Bytes) -> RecvResult {
+        let header = message.message_header;
+        let body = message.serialised_body;
+        if header.ttl == 0 {
+            return Err(RoutingError::TtlExpired);
+        }
+        // the parts of a split GetDataResponse share one header, so each is filtered separately
+        let part = if message.message_type == MessageTypeTag::GetDataResponse {
+            try!(decode::<GetDataResponse>(&body)).part
+        } else {
+            0
+        };
+        // filter check
+        if self.filter.check(&(header.get_filter(), part)) {
+            // should just return quietly
+            return Err(RoutingError::FilterCheckFailed);
+        }
+        // add to filter
+        self.filter.add((header.get_filter(), part));
+
+        // pings are between directly connected peers and are never passed on
+        match message.message_type {
+            MessageTypeTag::Ping => return self.handle_ping(peer_id, body),
+            // the sender was marked as seen when its message arrived
+            MessageTypeTag::PingResponse => return Ok(()),
+            _ => (),
+        }
+
+        // add to cache, unless it's only a part of the data
+        if message.message_type == MessageTypeTag::GetDataResponse {
+            let get_data_response = try!(decode::<GetDataResponse>(&body));
+            let cacheable = self.is_cacheable(get_data_response.name_and_type_id.type_id);
+            let _ = get_data_response.data.map(|data| {
+                if cacheable && data.len() != 0 && get_data_response.total <= 1 {
+                    let _ = self.mut_interface().handle_cache_put(
+                        header.from_authority(), header.from(), data);
+                }
+            });
+        }
+
+        // cache check / response
+        if message.message_type == MessageTypeTag::GetData {
+            let get_data = try!(decode::<GetData>(&body));
+            if self.is_cacheable(get_data.name_and_type_id.type_id) {
+                let retrieved_data = self.mut_interface().handle_cache_get(
+                    get_data.name_and_type_id.type_id.clone() as u64,
+                    get_data.name_and_type_id.name.clone(),
+                    header.from_authority(),
+                    header.from());
+
+                match retrieved_data {
+                    Ok(Action::Reply(data)) => {
+                        // an oversized cached copy is treated as a cache miss
+                        if data.len() <= self.max_reply_size {
+                            return self.send_cached_get_data_response(&header, &get_data, Ok(data));
+                        }
+                    },
+                    // the cache knows better where the data is held
+                    Ok(Action::SendOn(destinations)) => {
+                        let our_authority = self.our_authority(&get_data.name_and_type_id.name, &header);
+                        return self.send_on(&header, &our_authority, MessageTypeTag::GetData,
+                                            get_data, destinations);
+                    },
+                    // a cache miss, the request is routed on as usual
+                    Err(InterfaceError::Abort) => (),
+                    Err(InterfaceError::Response(error)) =>
+                        return self.send_cached_get_data_response(&header, &get_data, Err(error)),
+                };
+            }
+        }
+
+        // a message for one of our clients goes straight to it, it has no other route
+        let client_endpoint = self.non_routing_connections.get(&header.destination.dest).cloned();
+        if let Some(client_endpoint) = client_endpoint {
+            if !self.send_allowed(&header.destination.dest, &message.message_type) {
+                return Ok(());
+            }
+            try!(self.send_to(&client_endpoint, serialised_message));
+            self.message_stats.count_sent(&message.message_type);
+            return Ok(());
+        }
+
+        // as a client, a response relayed to us by our bootstrap node is ours to handle
+        let relayed_to_us = header.destination.reply_to.as_ref() == Some(&self.own_id);
+        if !relayed_to_us {
+            self.send_swarm_or_parallel(&header.destination.dest, &message.message_type,
+                                        &serialised_message);
+        }
+
+        // handle relay request/response
+        let relay_response = header.destination.reply_to.is_some() && !relayed_to_us &&
+                             header.destination.dest == self.own_id;
+        if relay_response {
+            let reply_to = header.destination.reply_to.clone().unwrap();
+            let relay_to = match self.non_routing_connections.get(&reply_to) {
+                Some(client_endpoint) => client_endpoint.clone(),
+                None => match self.all_connections.1.get(&reply_to) {
+                    Some(endpoint) => endpoint.clone(),
+                    // the node we relay for has gone, nobody else can take its response
+                    None => return Err(RoutingError::NoReplyEndpoint),
+                },
+            };
+            if !self.send_allowed(&reply_to, &message.message_type) {
+                return Ok(());
+            }
+            let _ = self.send_to(&relay_to, serialised_message);
+        }
+
+        // TODO(prakash)
+
+        if !relayed_to_us && !self.address_in_close_group_range(&header.destination.dest) {
+            return Ok(());
+        }
+
+        // Drop message before Sentinel check if it is a direct message type (Connect, ConnectResponse)
+        // and this node is in the group but the message destination is another group member node.
         // "not for me"
 
         // pre-sentinel message handling
         match message.message_type {
-            MessageTypeTag::UnauthorisedPut => self.handle_put_data(header, body),
+            MessageTypeTag::UnauthorisedPut => self.handle_unauthorised_put(header, body),
             MessageTypeTag::GetKey => self.handle_get_key(header, body),
             MessageTypeTag::GetGroupKey => self.handle_get_group_key(header, body),
             _ => {
@@ -417,6 +1651,8 @@ impl<F> RoutingNode<F> where F: Interface {
                 match message.message_type {
                     MessageTypeTag::ConnectRequest => self.handle_connect_request(header, body),
                     MessageTypeTag::ConnectResponse => self.handle_connect_response(body),
+                    MessageTypeTag::DeleteData => self.handle_delete_data(header, body),
+                    MessageTypeTag::DeleteDataResponse => self.handle_delete_data_response(header, body),
                     MessageTypeTag::FindGroup => self.handle_find_group(header, body),
                     MessageTypeTag::FindGroupResponse => self.handle_find_group_response(header, body),
                     MessageTypeTag::GetData => self.handle_get_data(header, body),
@@ -426,11 +1662,9 @@ impl<F> RoutingNode<F> where F: Interface {
                     MessageTypeTag::PutData => self.handle_put_data(header, body),
                     MessageTypeTag::PutDataResponse => self.handle_put_data_response(header, body),
                     MessageTypeTag::PutPublicPmid => self.handle_put_public_pmid(header, body),
-                    //PutKey,
-                    _ => {
-                        println!("unhandled message from {:?}", peer_id);
-                        Err(RoutingError::UnknownMessageType)
-                    }
+                    MessageTypeTag::PutKey => self.handle_put_key(header, body),
+                    MessageTypeTag::Refresh => self.handle_refresh(header, body),
+                    _ => Err(RoutingError::UnknownMessageType),
                 }
             }
         }
@@ -438,22 +1672,27 @@ impl<F> RoutingNode<F> where F: Interface {
     }
 
     fn bootstrap_message_received(&mut self, peer_endpoint: Endpoint, serialised_message: Bytes) -> RecvResult {
-        let message = match decode::<RoutingMessage>(&serialised_message) {
-            Err(err) => {
-                println!("Problem parsing bootstrap message: {} ", err);
-                return Err(RoutingError::UnknownMessageType);
-            },
-            Ok(msg) => msg,
-        };
+        let message = try!(self.serialisation.deserialise::<RoutingMessage>(&serialised_message));
 
         if message.message_type == MessageTypeTag::BootstrapIdRequest {
             let request = try!(decode::<BootstrapIdRequest>(&message.serialised_body));
-            if self.bootstrap_node_id.is_none() {
-                self.bootstrap_node_id = Some(request.sender_id.clone());
-                self.bootstrap_endpoint = Some(peer_endpoint.clone());
+            // an incompatible peer is refused before it is recorded anywhere
+            if request.protocol_version != types::PROTOCOL_VERSION {
+                self.connection_manager.drop_node(peer_endpoint);
+                return Err(RoutingError::IncompatibleProtocolVersion(request.protocol_version));
+            }
+            if message.message_header.authority == Authority::Client {
+                // clients are relayed for rather than routed through
+                self.non_routing_connections.insert(request.sender_id.clone(),
+                                                    peer_endpoint.clone());
+            } else {
+                if self.bootstrap_node_id.is_none() {
+                    self.bootstrap_node_id = Some(request.sender_id.clone());
+                    self.bootstrap_endpoint = Some(peer_endpoint.clone());
+                }
+                self.all_connections.0.insert(peer_endpoint.clone(), request.sender_id.clone());
+                self.all_connections.1.insert(request.sender_id.clone(), peer_endpoint.clone());
             }
-            self.all_connections.0.insert(peer_endpoint.clone(), request.sender_id.clone());
-            self.all_connections.1.insert(request.sender_id.clone(), peer_endpoint.clone());
             self.send_bootstrap_id_response(peer_endpoint);
         } else if message.message_type == MessageTypeTag::BootstrapIdResponse {
             self.handle_bootstrap_id_response(peer_endpoint, message.serialised_body,
@@ -506,33 +1745,40 @@ impl<F> RoutingNode<F> where F: Interface {
     fn handle_get_group_key(&mut self, original_header : MessageHeader, body : Bytes) -> RecvResult {
         let get_group_key = try!(decode::<GetGroupKey>(&body));
 
-        let group_keys = self.routing_table.our_close_group()
-                         .into_iter()
-                         .map(|node| (node.fob.name, node.fob.public_sign_key))
-                         // add our own signature key
-                         .chain(Some((self.pmid.get_name(),self.pmid.get_public_sign_key())).into_iter())
-                         .collect::<Vec<_>>();
-
+        let group_keys = self.group_keys();
         let routing_msg = self.construct_get_group_key_response_msg(&original_header,
                                                                     &get_group_key,
                                                                     group_keys);
-        let encoded_msg = try!(encode(&routing_msg));
+        let encoded_msg = try!(self.serialise(&routing_msg));
         let original_group = original_header.from_group();
-        original_group.map(|group| self.send_swarm_or_parallel(&group, &encoded_msg));
+        original_group.map(|group| self.send_swarm_or_parallel(&group, &MessageTypeTag::GetGroupKeyResponse,
+                                                                &encoded_msg));
         Ok(())
     }
 
+    /// The public sign keys of our close group and ourselves, sorted by name with each name once.
+    fn group_keys(&self) -> Vec<(NameType, types::PublicSignKey)> {
+        let mut group_keys = self.routing_table.our_close_group().into_iter()
+                                 .map(|node| (node.fob.name, node.fob.public_sign_key))
+                                 .collect::<BTreeMap<_, _>>();
+        // our own key is added last, so it is the one kept should we also appear in the group
+        group_keys.insert(self.pmid.get_name(), self.pmid.get_public_sign_key());
+        group_keys.into_iter().collect()
+    }
+
     fn handle_connect_request(&mut self, original_header: MessageHeader, body: Bytes) -> RecvResult {
-        println!("{:?} received ConnectRequest ", self.own_id);
         let connect_request = try!(decode::<ConnectRequest>(&body));
         // Collect the local and external endpoints into a single vector to construct a NodeInfo
         let mut peer_endpoints = connect_request.local_endpoints.clone();
         peer_endpoints.extend(connect_request.external_endpoints.clone().into_iter());
         let peer_node_info =
             NodeInfo::new(connect_request.requester_fob.clone(), peer_endpoints, None);
+        if self.ip_limit_reached(&peer_node_info) {
+            return Err(RoutingError::TooManyNodesOnIp);
+        }
 
         // Try to add to the routing table.  If unsuccessful, no need to continue.
-        let (added, _) = self.routing_table.add_node(peer_node_info);
+        let (added, _) = self.add_node(peer_node_info);
         if !added {
            return Err(RoutingError::AlreadyConnected);
         }
@@ -542,118 +1788,188 @@ impl<F> RoutingNode<F> where F: Interface {
         self.connection_manager.connect(connect_request.external_endpoints.clone());
 
         // Send the response containing out details.
-        let routing_msg = self.construct_connect_response_msg(&original_header, &connect_request);
-        let serialised_message = try!(encode(&routing_msg));
+        let routing_msg = try!(self.construct_connect_response_msg(&original_header, &connect_request));
+        let serialised_message = try!(self.serialise(&routing_msg));
 
-        self.send_swarm_or_parallel(&connect_request.requester_id, &serialised_message);
+        self.send_swarm_or_parallel(&connect_request.requester_id, &MessageTypeTag::ConnectResponse,
+                                    &serialised_message);
 
-        if self.bootstrap_endpoint.is_some() {
-            self.send_to_bootstrap_node(&routing_msg);
+        // we may not be a bootstrapping node ourselves
+        match self.send_to_bootstrap_node(&routing_msg) {
+            Ok(()) | Err(RoutingError::NotBootstrapped) => (),
+            Err(error) => self.emit(RoutingEvent::MessageDropped(error)),
         }
 
         if original_header.source.reply_to.is_some() {
             let reply_to_address = original_header.source.reply_to.unwrap();
             // FIXME: Discuss: Might be the case that we want to ignore these errors?
-            return match self.all_connections.1.get(&reply_to_address) {
-                Some(reply_to) => {
-                    let msg = try!(encode(&routing_msg));
-                    self.send_to(&reply_to, msg).map_err(From::from)
-                },
-                None => Err(RoutingError::Other)
+            let reply_to = match self.all_connections.1.get(&reply_to_address) {
+                Some(reply_to) => reply_to.clone(),
+                None => return Err(RoutingError::RelayTargetNotConnected(reply_to_address)),
+            };
+            if !self.send_allowed(&reply_to_address, &routing_msg.message_type) {
+                return Ok(());
             }
+            let msg = try!(self.serialise(&routing_msg));
+            return self.send_to(&reply_to, msg).map_err(From::from);
         }
 
         Ok(())
     }
 
     fn handle_connect_response(&mut self, body: Bytes) -> RecvResult {
-        println!("{:?} received ConnectResponse", self.own_id);
         let connect_response = try!(decode::<ConnectResponse>(&body));
+        let _ = self.pending_connects.remove(&connect_response.receiver_id);
         // Collect the local and external endpoints into a single vector to construct a NodeInfo
         let mut peer_endpoints = connect_response.receiver_local_endpoints.clone();
         peer_endpoints.extend(connect_response.receiver_external_endpoints.clone().into_iter());
-        let peer_node_info =
-            NodeInfo::new(connect_response.receiver_fob.clone(), peer_endpoints, None);
+        // In the zero state the responder is the node we bootstrapped off.  We already hold a
+        // connection to it, so crust won't report a new one and we mark it connected here.
+        let existing_endpoint = self.all_connections.1.get(&connect_response.receiver_id).cloned();
+        let peer_node_info = NodeInfo::new(connect_response.receiver_fob.clone(), peer_endpoints,
+                                           existing_endpoint.clone());
+        if self.ip_limit_reached(&peer_node_info) {
+            return Err(RoutingError::TooManyNodesOnIp);
+        }
 
         // Try to add to the routing table.  If unsuccessful, no need to continue.
-        let (added, _) = self.routing_table.add_node(peer_node_info.clone());
+        let (added, _) = self.add_node(peer_node_info);
         if !added {
            return Ok(());
         }
 
+        if existing_endpoint.is_some() {
+            self.emit(RoutingEvent::Connected(connect_response.receiver_id.clone()));
+            return Ok(());
+        }
+
         // Try to connect to the peer.
         self.connection_manager.connect(connect_response.receiver_local_endpoints.clone());
         self.connection_manager.connect(connect_response.receiver_external_endpoints.clone());
-
-// FIXME(Prakash) this can be deleted
-        // workaround for zero state
-        // if self.all_connections.0.len() == 1 &&
-        //         self.all_connections.1.contains_key(&connect_response.receiver_id) {
-        //     let result = self.routing_table.add_node(peer_node_info);
-        //     if result.0 {
-        //         println!("{:?} added {:?} <RT size:{}>", self.own_id, connect_response.receiver_id, self.routing_table.size());
-        //     } else {
-        //         println!("{:?} failed to add {:?}", self.own_id, connect_response.receiver_id);
-        //     }
-        // }
         Ok(())
     }
 
     fn handle_find_group(&mut self, original_header: MessageHeader, body: Bytes) -> RecvResult {
-        println!("{:?} received FindGroup {:?}", self.own_id, original_header.message_id);
         let find_group = try!(decode::<FindGroup>(&body));
 
-        let group = self.routing_table.our_close_group().into_iter()
-                    .map(|x|x.fob)
-                    // add ourselves
-                    .chain(Some(types::PublicPmid::new(&self.pmid)).into_iter())
-                    .collect::<Vec<_>>();
+        let group = self.find_group_members();
 
         let routing_msg = self.construct_find_group_response_msg(&original_header, &find_group, group);
 
         // FIXME(Peter) below method is needed
-        self.send_swarm_or_parallel(&original_header.send_to().dest, &try!(encode(&routing_msg)));
+        let serialised_message = try!(self.serialise(&routing_msg));
+        self.send_swarm_or_parallel(&original_header.send_to().dest, &MessageTypeTag::FindGroupResponse,
+                                    &serialised_message);
 
 
         // if node in my group && in non routing list send it to non_routnig list as well
         if original_header.source.reply_to.is_some() {
             let reply_to_address = original_header.source.reply_to.unwrap();
             // FIXME: Discuss: Might be the case that we want to ignore these errors?
-            return match self.all_connections.1.get(&reply_to_address) {
-                Some(reply_to) => {
-                    let msg = try!(encode(&routing_msg));
-                    self.send_to(&reply_to, msg).map_err(From::from)
-                },
-                None => Err(RoutingError::Other)
+            let reply_to = match self.all_connections.1.get(&reply_to_address) {
+                Some(reply_to) => reply_to.clone(),
+                None => return Err(RoutingError::RelayTargetNotConnected(reply_to_address)),
+            };
+            if !self.send_allowed(&reply_to_address, &routing_msg.message_type) {
+                return Ok(());
             }
+            let msg = try!(self.serialise(&routing_msg));
+            return self.send_to(&reply_to, msg).map_err(From::from);
         }
         Ok(())
     }
 
+    /// The members of our close group, and ourselves, as we answer a FindGroup with.
+    fn find_group_members(&self) -> Vec<types::PublicPmid> {
+        self.routing_table.our_close_group().into_iter()
+            .map(|x|x.fob)
+            // add ourselves
+            .chain(Some(types::PublicPmid::new(&self.pmid)).into_iter())
+            .collect::<Vec<_>>()
+    }
+
     fn handle_find_group_response(&mut self, original_header: MessageHeader, body: Bytes) -> RecvResult {
-        println!("{:?} received FindGroupResponse", self.own_id);
         let find_group_response = try!(decode::<FindGroupResponse>(&body));
+        if let Some(target) = original_header.from_group() {
+            // the answer to the search for our own group made while bootstrapping: every member
+            // answers with itself, so if it names nobody but the node we bootstrapped off, and
+            // maybe us, nobody else is close to us and we are starting the network
+            if target == self.own_id && self.routing_table.size() == 0 &&
+               find_group_response.group.iter().all(|peer| {
+                   peer.name == self.own_id || Some(&peer.name) == self.bootstrap_node_id.as_ref()
+               }) && !self.first_node {
+                self.first_node = true;
+                self.emit(RoutingEvent::FirstNode);
+            }
+            self.deliver_find_group_response(original_header.message_id, &find_group_response);
+        }
         for peer in find_group_response.group {
-            self.check_and_send_connect_request_msg(&peer.name);
+            if peer.name != self.own_id {
+                self.check_and_send_connect_request_msg(&peer.name);
+            }
         }
         Ok(())
     }
 
+    /// Passes the names in a response to the `find_group` sent as `message_id` which haven't been
+    /// delivered yet to its caller.
+    fn deliver_find_group_response(&mut self, message_id: MessageId,
+                                   response: &FindGroupResponse) {
+        let receiver_gone = match self.pending_find_groups.get_mut(&message_id) {
+            Some(&mut (ref group_sender, ref mut seen, _)) => {
+                let mut names = Vec::new();
+                for peer in response.group.iter() {
+                    if !seen.contains(&peer.name) {
+                        seen.push(peer.name.clone());
+                        names.push(peer.name.clone());
+                    }
+                }
+                !names.is_empty() && group_sender.send(names).is_err()
+            },
+            None => return,
+        };
+        if receiver_gone {
+            self.pending_find_groups.remove(&message_id);
+        }
+    }
+
+    /// Forgets the `find_group`s no longer awaiting answers, closing their receivers.
+    fn expire_pending_find_groups(&mut self) {
+        let now = SteadyTime::now();
+        let expired = self.pending_find_groups.iter()
+                          .filter(|&(_, &(_, _, deadline))| deadline <= now)
+                          .map(|(message_id, _)| *message_id)
+                          .collect::<Vec<_>>();
+        for message_id in expired {
+            let _ = self.pending_find_groups.remove(&message_id);
+        }
+    }
+
     //FIXME  not sure if we need to return a RecvResult or a generic error
     fn check_and_send_connect_request_msg(&mut self, peer_id: &NameType) {
         if !self.routing_table.check_node(&peer_id) {
             return;
         }
-        let routing_msg = self.construct_connect_request_msg(&peer_id);
-        let serialised_message = match encode(&routing_msg) {
+        // a request to this peer is already under way
+        if self.pending_connects.check(peer_id) {
+            return;
+        }
+        self.pending_connects.add(peer_id.clone(), ());
+        let routing_msg = match self.construct_connect_request_msg(&peer_id) {
+            Ok(message) => message,
+            Err(error) => return self.emit(RoutingEvent::MessageDropped(error)),
+        };
+        let serialised_message = match self.serialise(&routing_msg) {
             Ok(message) => message,
             Err(_) => return,
         };
 
-        self.send_swarm_or_parallel(peer_id, &serialised_message);
+        self.send_swarm_or_parallel(peer_id, &MessageTypeTag::ConnectRequest, &serialised_message);
 
-        if self.bootstrap_endpoint.is_some() {
-            self.send_to_bootstrap_node(&routing_msg);
+        // we may not be a bootstrapping node ourselves
+        match self.send_to_bootstrap_node(&routing_msg) {
+            Ok(()) | Err(RoutingError::NotBootstrapped) => (),
+            Err(error) => self.emit(RoutingEvent::MessageDropped(error)),
         }
         // Ok(())
     }
@@ -666,37 +1982,103 @@ impl<F> RoutingNode<F> where F: Interface {
         let from = header.from();
         let name = get_data.name_and_type_id.name.clone();
 
+        let cached_data = self.data_cache.get(&(name.clone(), type_id)).map(|data| data.clone());
+        if let Some(data) = cached_data {
+            return self.send_get_data_response(header.create_reply(&self.own_id, &our_authority),
+                                               &header.send_to().dest, get_data.name_and_type_id,
+                                               Ok(data));
+        }
+
         match self.mut_interface().handle_get(type_id, name, our_authority.clone(), from_authority, from) {
             Ok(action) => match action {
                 Action::Reply(data) => {
-                    let routing_msg = RoutingMessage::new(MessageTypeTag::GetDataResponse, header.create_reply(&self.own_id, &our_authority),
-                        GetDataResponse{ name_and_type_id :get_data.name_and_type_id, data: Ok(data) },
-                        &self.pmid.get_crypto_secret_sign_key());
-                    let encoded_msg = try!(encode(&routing_msg));
-                    self.send_swarm_or_parallel(&header.send_to().dest, &encoded_msg);
+                    let data = self.check_reply_size(data);
+                    try!(self.send_get_data_response(header.create_reply(&self.own_id, &our_authority),
+                                                     &header.send_to().dest,
+                                                     get_data.name_and_type_id, data));
                 },
                 Action::SendOn(dest_nodes) => {
-                    for dest_node in dest_nodes {
-                        let send_on_header = header.create_send_on(&self.own_id, &our_authority, &dest_node);
-                        let routing_msg = RoutingMessage::new(MessageTypeTag::GetData, send_on_header,
-                            get_data.clone(), &self.pmid.get_crypto_secret_sign_key());
-                        let encoded_msg = try!(encode(&routing_msg));
-                        self.send_swarm_or_parallel(&dest_node, &encoded_msg);
-                    }
+                    try!(self.send_on(&header, &our_authority, MessageTypeTag::GetData,
+                                      get_data.clone(), dest_nodes));
                 }
             },
             Err(InterfaceError::Abort) => {;},
             Err(InterfaceError::Response(error)) => {
-                let routing_msg = RoutingMessage::new(MessageTypeTag::GetDataResponse, header.create_reply(&self.own_id, &our_authority),
-                    GetDataResponse{ name_and_type_id :get_data.name_and_type_id, data: Err(error) },
-                    &self.pmid.get_crypto_secret_sign_key());
-                let encoded_msg = try!(encode(&routing_msg));
-                self.send_swarm_or_parallel(&header.send_to().dest, &encoded_msg);
+                try!(self.send_get_data_response(header.create_reply(&self.own_id, &our_authority),
+                                                 &header.send_to().dest,
+                                                 get_data.name_and_type_id, Err(error)));
             }
         }
         Ok(())
     }
 
+    /// Answers a GetData from the cache, with the data or with the error the cache gave.
+    fn send_cached_get_data_response(&mut self, header: &MessageHeader, get_data: &GetData,
+                                     data: Result<Vec<u8>, ResponseError>) -> RecvResult {
+        let reply = try!(self.construct_get_data_response_msg(header, get_data, data));
+        let serialised_reply = try!(self.serialise(&reply));
+        self.send_swarm_or_parallel(&header.send_to().dest, &MessageTypeTag::GetDataResponse,
+                                    &serialised_reply);
+        Ok(())
+    }
+
+    /// Sends a GetDataResponse towards `target`.  Data larger than the part size is split over
+    /// several responses, each carrying its part number, for the requester to reassemble.  Replies
+    /// relayed to a client are always sent whole.
+    fn send_get_data_response(&mut self, reply_header: MessageHeader, target: &NameType,
+                              name_and_type_id: NameAndTypeId,
+                              data: Result<Vec<u8>, ResponseError>) -> RecvResult {
+        let part_size = self.get_response_part_size;
+        let parts: Vec<Result<Vec<u8>, ResponseError>> = match data {
+            Ok(ref data) if data.len() > part_size && reply_header.destination.reply_to.is_none() =>
+                data.chunks(part_size).map(|part| Ok(part.to_vec())).collect(),
+            data => vec![data],
+        };
+        let total = parts.len() as u32;
+        for (part, data) in parts.into_iter().enumerate() {
+            let get_data_response = GetDataResponse { name_and_type_id: name_and_type_id.clone(),
+                                                      data: data, part: part as u32, total: total };
+            let routing_msg = RoutingMessage::new(MessageTypeTag::GetDataResponse,
+                reply_header.clone(), get_data_response, &self.pmid.get_crypto_secret_sign_key());
+            let encoded_msg = try!(self.serialise(&routing_msg));
+            self.send_swarm_or_parallel(target, &MessageTypeTag::GetDataResponse, &encoded_msg);
+        }
+        Ok(())
+    }
+
+    /// Collects the parts of a split GetDataResponse, returning the whole response once every
+    /// part has arrived.  An assembly which waits too long for its next part is discarded, as is
+    /// the oldest one when too many are under way.  A response claiming more parts than a message
+    /// of the largest size we accept could be split into is dropped unseen.
+    fn assemble_get_data_response(&mut self, message_id: MessageId,
+                                  get_data_response: GetDataResponse) -> Option<GetDataResponse> {
+        if get_data_response.total <= 1 {
+            return Some(get_data_response);
+        }
+        let (part, total) = (get_data_response.part as usize, get_data_response.total as usize);
+        let data = match get_data_response.data {
+            Ok(data) => data,
+            // only data is ever split
+            Err(_) => return None,
+        };
+        let max_parts = self.max_message_size / self.get_response_part_size + 1;
+        if part >= total || total > max_parts {
+            return None;
+        }
+        let key = (message_id, get_data_response.name_and_type_id.name.clone());
+        let mut parts = self.partial_get_responses.remove(&key)
+                            .and_then(|parts| if parts.len() == total { Some(parts) } else { None })
+                            .unwrap_or_else(|| vec![None; total]);
+        parts[part] = Some(data);
+        if parts.iter().any(|part| part.is_none()) {
+            self.partial_get_responses.add(key, parts);
+            return None;
+        }
+        let data = parts.into_iter().flat_map(|part| part.unwrap().into_iter()).collect();
+        Some(GetDataResponse { name_and_type_id: get_data_response.name_and_type_id,
+                               data: Ok(data), part: 0, total: 1 })
+    }
+
     fn handle_get_key(&mut self, header: MessageHeader, body: Bytes) -> RecvResult {
         let get_key = try!(decode::<GetKey>(&body));
         let type_id = 106u64;
@@ -705,65 +2087,122 @@ impl<F> RoutingNode<F> where F: Interface {
         let from = header.from();
         let name = get_key.target_id.clone();
 
-        let mut action: Action;
+        // a key stored by PutKey is served without consulting the interface
+        let cached_key = self.public_key_cache.get(&name).map(|public_key| public_key.clone());
+        let action = match cached_key {
+            Some(public_key) => Ok(Action::Reply(try!(encode(&public_key)))),
+            None => self.mut_interface().handle_get_key(type_id, name, our_authority.clone(),
+                                                        from_authority, from),
+        };
 
-        action = try!(self.mut_interface().handle_get_key(type_id, name, our_authority.clone(), from_authority, from));
+        // a key which isn't held, or is too large to send, is answered with the error
+        let public_key = match action {
+            Ok(Action::Reply(data)) => match self.check_reply_size(data) {
+                Ok(data) => Ok(try!(decode::<types::PublicSignKey>(&data))),
+                Err(error) => Err(error),
+            },
+            Ok(Action::SendOn(dest_nodes)) => {
+                return self.send_on(&header, &our_authority, MessageTypeTag::GetKey, get_key.clone(),
+                                    dest_nodes);
+            },
+            Err(InterfaceError::Abort) => return Ok(()),
+            Err(InterfaceError::Response(error)) => Err(error),
+        };
+        let routing_msg = RoutingMessage::new(MessageTypeTag::GetKeyResponse, header.create_reply(&self.own_id, &our_authority),
+            GetKeyResponse{ address : get_key.target_id.clone(), public_sign_key : public_key },
+            &self.pmid.get_crypto_secret_sign_key());
+        let encoded_msg = try!(self.serialise(&routing_msg));
+        self.send_swarm_or_parallel(&header.send_to().dest, &MessageTypeTag::GetKeyResponse, &encoded_msg);
+        Ok(())
+    }
 
-        match action {
-            Action::Reply(data) => {
-                let public_key = try!(decode::<types::PublicSignKey>(&data));
-                let routing_msg = RoutingMessage::new(MessageTypeTag::GetKeyResponse, header.create_reply(&self.own_id, &our_authority),
-                    GetKeyResponse{ address : get_key.target_id.clone(), public_sign_key : public_key },
-                    &self.pmid.get_crypto_secret_sign_key());
-                let encoded_msg = try!(encode(&routing_msg));
-                self.send_swarm_or_parallel(&header.send_to().dest, &encoded_msg);
-                },
-            Action::SendOn(dest_nodes) => {
-                for dest_node in dest_nodes {
-                    let send_on_header = header.create_send_on(&self.own_id, &our_authority, &dest_node);
-                    let routing_msg = RoutingMessage::new(MessageTypeTag::GetKey, send_on_header,
-                        get_key.clone(), &self.pmid.get_crypto_secret_sign_key());
-                    let encoded_msg = try!(encode(&routing_msg));
-                    self.send_swarm_or_parallel(&dest_node, &encoded_msg);
-                }
+    /// Passes `content` on to each of `destinations` once, however often the interface named it.
+    /// The body is signed once; only the header changes between destinations.
+    fn send_on<T>(&mut self, header: &MessageHeader, our_authority: &Authority,
+                  message_type: MessageTypeTag, content: T, destinations: Vec<NameType>)
+                  -> RecvResult where T: Encodable + Decodable {
+        let mut routing_msg = RoutingMessage::new(message_type.clone(), header.clone(), content,
+                                                  &self.pmid.get_crypto_secret_sign_key());
+        let mut sent_to: Vec<NameType> = Vec::with_capacity(destinations.len());
+        for destination in destinations {
+            if sent_to.contains(&destination) {
+                continue;
+            }
+            routing_msg.message_header = header.create_send_on(&self.own_id, our_authority,
+                                                               &destination);
+            // it would only be dropped by the receiver
+            if routing_msg.message_header.ttl == 0 {
+                return Err(RoutingError::TtlExpired);
             }
+            let encoded_msg = try!(self.serialise(&routing_msg));
+            self.send_swarm_or_parallel(&destination, &message_type, &encoded_msg);
+            sent_to.push(destination);
         }
         Ok(())
     }
 
     fn handle_get_data_response(&mut self, header: MessageHeader, body: Bytes) -> RecvResult {
         let get_data_response = try!(decode::<GetDataResponse>(&body));
-        let from = header.from();
-        self.mut_interface().handle_get_response(from, get_data_response.data);
+        let get_data_response = match self.assemble_get_data_response(header.message_id(),
+                                                                      get_data_response) {
+            Some(get_data_response) => get_data_response,
+            None => return Ok(()),
+        };
+        if let Ok(ref data) = get_data_response.data {
+            self.cache_data(get_data_response.name_and_type_id.name.clone(),
+                            get_data_response.name_and_type_id.type_id, data.clone());
+        }
+        if let Some((result_sender, _)) = self.pending_gets.remove(&header.message_id()) {
+            // the caller may have dropped the receiver, which is fine
+            let _ = result_sender.send(get_data_response.data.clone());
+        }
+        self.mut_interface().handle_get_response(header.from_authority(), header.from(),
+                                                 get_data_response.data);
         Ok(())
     }
 
     fn handle_post(&mut self, header : MessageHeader, body : Bytes) -> RecvResult {
         let post = try!(decode::<Post>(&body));
         let our_authority = self.our_authority(&post.name, &header);
-        match try!(self.mut_interface().handle_post(our_authority.clone(),
-                                                    header.authority.clone(),
-                                                    header.from(),
-                                                    post.name.clone(),
-                                                    post.data.clone())) {
-            Action::Reply(data) => {
-                Ok(()) // TODO: implement post_response
+        let reply_data = match self.mut_interface().handle_post(our_authority.clone(),
+                                                                header.authority.clone(),
+                                                                header.from(),
+                                                                post.name.clone(),
+                                                                post.data.clone()) {
+            Ok(Action::Reply(reply_data)) => Ok(reply_data),
+            Ok(Action::SendOn(destinations)) => {
+                return self.send_on(&header, &our_authority, MessageTypeTag::Post, post.clone(),
+                                    destinations);
             },
-            Action::SendOn(destinations) => {
-                for destination in destinations {
-                    let send_on_header = header.create_send_on(&self.own_id,
-                        &our_authority, &destination);
-                    let routing_msg = RoutingMessage::new(MessageTypeTag::Post,
-                        send_on_header, post.clone(), &self.pmid.get_crypto_secret_sign_key());
-                    self.send_swarm_or_parallel(&destination, &try!(encode(&routing_msg)));
-                }
-                Ok(())
+            Err(InterfaceError::Abort) => return Ok(()),
+            // a failed post is reported back the same way as a successful one
+            Err(InterfaceError::Response(error)) => Err(error),
+        };
+
+        let reply_header = header.create_reply(&self.own_id, &our_authority);
+        let reply_to = match our_authority {
+            Authority::ClientManager => match header.reply_to() {
+                Some(client) => client,
+                None => header.from()
             },
-        }
+            _ => header.from()
+        };
+        let post_response = PostResponse {
+            name : post.name.clone(),
+            data : reply_data,
+        };
+        let routing_msg = RoutingMessage::new(MessageTypeTag::PostResponse,
+            reply_header, post_response, &self.pmid.get_crypto_secret_sign_key());
+        let serialised_message = try!(self.serialise(&routing_msg));
+        self.send_swarm_or_parallel(&reply_to, &MessageTypeTag::PostResponse, &serialised_message);
+        Ok(())
     }
 
-    fn handle_post_response(&self, header : MessageHeader, body : Bytes) -> RecvResult {
-        // currently no post_response object; out of sprint (2015-04-30)
+    fn handle_post_response(&mut self, header : MessageHeader, body : Bytes) -> RecvResult {
+        let post_response = try!(decode::<PostResponse>(&body));
+        let from_authority = header.from_authority();
+        let from = header.from();
+        self.mut_interface().handle_post_response(from_authority, from, post_response.data);
         Ok(())
     }
 
@@ -776,10 +2215,12 @@ impl<F> RoutingNode<F> where F: Interface {
         match self.our_authority(&put_public_pmid.public_pmid.name, &header) {
             Authority::NaeManager => {
                 // FIXME (prakash) signature check ?
-                // TODO (Ben): check whether to accept pmid into group;
-                //             restrict on minimal similar number of leading bits.
-                self.public_pmid_cache.add(put_public_pmid.public_pmid.name.clone(),
-                                           put_public_pmid.public_pmid);
+                let name = put_public_pmid.public_pmid.name.clone();
+                if common_leading_bits(&name, &self.own_id) < self.min_pmid_leading_bits {
+                    return Err(RoutingError::BadAuthority);
+                }
+                self.public_pmid_cache.add(name.clone(), put_public_pmid.public_pmid);
+                self.replay_unverified_messages(&name);
                 Ok(())
             },
             _ => {
@@ -788,49 +2229,235 @@ impl<F> RoutingNode<F> where F: Interface {
         }
     }
 
-    // // for clients, below methods are required
-    fn handle_put_data(&mut self, header: MessageHeader, body: Bytes) -> RecvResult {
+    /// Handles data a member of the group responsible for it has refreshed.  Only refreshes from
+    /// our close group are accepted.  Copies the interface can parse are merged once a quorum of
+    /// members has sent one; the merged, or unparseable, data is then passed to `handle_put` as
+    /// NodeManager.  Refreshes aren't answered.
+    fn handle_refresh(&mut self, header: MessageHeader, body: Bytes) -> RecvResult {
         let put_data = try!(decode::<PutData>(&body));
-        let our_authority = self.our_authority(&put_data.name, &header);
+        if !self.address_in_close_group_range(&put_data.name) {
+            return Err(RoutingError::BadAuthority);
+        }
+        let sender = header.from_node();
+        if !self.routing_table.close_group_snapshot().contains(&sender) {
+            return Err(RoutingError::BadAuthority);
+        }
+        let data = match self.mut_interface().parse_refresh(&put_data.name, &put_data.data) {
+            Some(content) => match self.accumulate_refresh(put_data.name.clone(), sender, content) {
+                Some(merged) => merged.serialised_contents(),
+                None => return Ok(()),
+            },
+            None => put_data.data.clone(),
+        };
+        let our_authority = Authority::NodeManager;
         let from_authority = header.from_authority();
         let from = header.from();
         let to = header.send_to();
-
-        match try!(self.mut_interface().handle_put(our_authority.clone(), from_authority, from,
-                                                   to, put_data.data.clone())) {
-            Action::Reply(reply_data) => {
-                let reply_header = header.create_reply(&self.own_id, &our_authority);
-                let reply_to = match our_authority {
-                    Authority::ClientManager => match header.reply_to() {
-                        Some(client) => client,
-                        None => header.from()
-                    },
-                    _ => header.from()
-                };
-                let put_data_response = PutDataResponse {
-                    name : put_data.name.clone(),
-                    data : Ok(reply_data),
-                };
-                let routing_msg = RoutingMessage::new(MessageTypeTag::PutDataResponse,
-                    reply_header, put_data_response, &self.pmid.get_crypto_secret_sign_key());
-                self.send_swarm_or_parallel(&reply_to, &try!(encode(&routing_msg)));
+        match self.mut_interface().handle_put(our_authority.clone(), from_authority, from, to, data) {
+            Ok(Action::Reply(_)) => {
+                self.invalidate_cached_data(&put_data.name);
                 Ok(())
             },
-            Action::SendOn(destinations) => {
-                for destination in destinations {
-                    let send_on_header = header.create_send_on(&self.own_id,
-                        &our_authority, &destination);
-                    let routing_msg = RoutingMessage::new(MessageTypeTag::PutData,
-                        send_on_header, put_data.clone(), &self.pmid.get_crypto_secret_sign_key());
-                    self.send_swarm_or_parallel(&destination, &try!(encode(&routing_msg)));
+            Ok(Action::SendOn(destinations)) => {
+                self.invalidate_cached_data(&put_data.name);
+                self.send_on(&header, &our_authority, MessageTypeTag::PutData, put_data,
+                             destinations)
+            },
+            Err(InterfaceError::Abort) => Ok(()),
+            Err(InterfaceError::Response(error)) => Err(From::from(error)),
+        }
+    }
+
+    /// Holds a refreshed copy of `name` until a quorum of group members have sent one, then
+    /// merges the copies into the authoritative value.  Only the first copy from each `sender`
+    /// counts, so no member can make up a quorum on its own.  The first copy received merges the
+    /// rest.  If `merge` returns None the copies were insufficient or conflicting; the entry is
+    /// dropped and nothing reaches the interface until the data is refreshed again.  Copies which
+    /// wait too long for the rest of the quorum are dropped the same way.
+    fn accumulate_refresh(&mut self, name: NameType, sender: NameType, content: Box<Sendable>)
+            -> Option<Box<Sendable>> {
+        let quorum = self.routing_table.quorum_size();
+        let quorum_reached = {
+            let entry = self.accumulating_refresh.entry(name.clone())
+                            .or_insert((SteadyTime::now(), vec![]));
+            if entry.1.iter().any(|&(ref copy_sender, _)| *copy_sender == sender) {
+                return None;
+            }
+            entry.1.push((sender, content));
+            entry.1.len() >= quorum
+        };
+        if !quorum_reached {
+            return None;
+        }
+        let (_, copies) = self.accumulating_refresh.remove(&name).unwrap_or((SteadyTime::now(), vec![]));
+        let mut copies = copies.into_iter().map(|(_, copy)| copy).collect::<Vec<_>>();
+        let first = copies.remove(0);
+        first.merge(copies)
+    }
+
+    /// Drops refreshed copies which have waited too long for the rest of a quorum.
+    fn expire_accumulating_refreshes(&mut self) {
+        let expiry = SteadyTime::now() - Duration::seconds(REFRESH_ACCUMULATION_SECS);
+        let expired = self.accumulating_refresh.iter()
+                          .filter(|&(_, &(first_received, _))| first_received <= expiry)
+                          .map(|(name, _)| name.clone())
+                          .collect::<Vec<_>>();
+        for name in expired {
+            let _ = self.accumulating_refresh.remove(&name);
+        }
+    }
+
+    /// Caches data returned for a get, so that repeat reads can be answered locally.
+    fn cache_data(&mut self, name: NameType, type_id: u64, data: Vec<u8>) {
+        if data.is_empty() {
+            return;
+        }
+        self.data_cache.add((name.clone(), type_id), data);
+        {
+            let type_ids = self.data_cache_types.entry(name).or_insert(vec![]);
+            if !type_ids.contains(&type_id) {
+                type_ids.push(type_id);
+            }
+        }
+        // forget names whose copies have all been evicted or expired
+        if self.data_cache_types.len() > self.data_cache.len() {
+            let names: Vec<NameType> = self.data_cache_types.keys().cloned().collect();
+            for name in names {
+                let type_ids = self.data_cache_types.get(&name).cloned().unwrap_or(vec![]);
+                if !type_ids.iter().any(|type_id| self.data_cache.check(&(name.clone(), *type_id))) {
+                    self.data_cache_types.remove(&name);
                 }
+            }
+        }
+    }
+
+    /// Drops every cached copy of `name`, whatever its type.
+    fn invalidate_cached_data(&mut self, name: &NameType) {
+        for type_id in self.data_cache_types.remove(name).unwrap_or(vec![]) {
+            let _ = self.data_cache.remove(&(name.clone(), type_id));
+        }
+    }
+
+    /// Stores a public key for its NaeManager group, so that `handle_get_key` can serve it
+    /// without calling the upper layer.
+    fn handle_put_key(&mut self, header: MessageHeader, body: Bytes) -> RecvResult {
+        let put_key = try!(decode::<PutKey>(&body));
+        match self.our_authority(&put_key.target_id, &header) {
+            Authority::NaeManager => {
+                // FIXME signature check ?
+                self.public_key_cache.add(put_key.target_id, put_key.public_sign_key);
                 Ok(())
             },
+            _ => {
+                Err(RoutingError::BadAuthority)
+            }
+        }
+    }
+
+    // an unauthorised put has no authority to check, so the interface decides whether to accept it
+    fn handle_unauthorised_put(&mut self, header: MessageHeader, body: Bytes) -> RecvResult {
+        let put_data = try!(decode::<PutData>(&body));
+        if !self.mut_interface().authorise_unauthorised_put(&put_data.name, &put_data.data) {
+            return Err(RoutingError::BadAuthority);
         }
+        self.handle_put_data(header, body)
+    }
+
+    // // for clients, below methods are required
+    fn handle_put_data(&mut self, header: MessageHeader, body: Bytes) -> RecvResult {
+        let put_data = try!(decode::<PutData>(&body));
+        let our_authority = self.our_authority(&put_data.name, &header);
+        let from_authority = header.from_authority();
+        let from = header.from();
+        let to = header.send_to();
+        let data = put_data.data.clone();
+
+        let reply_data = match self.mut_interface().handle_put(our_authority.clone(), from_authority,
+                                                               from, to, data) {
+            Ok(Action::Reply(reply_data)) => {
+                self.invalidate_cached_data(&put_data.name);
+                Ok(reply_data)
+            },
+            Ok(Action::SendOn(destinations)) => {
+                self.invalidate_cached_data(&put_data.name);
+                return self.send_on(&header, &our_authority, MessageTypeTag::PutData,
+                                    put_data.clone(), destinations);
+            },
+            Err(InterfaceError::Abort) => return Ok(()),
+            // a failed put is reported back the same way as a successful one
+            Err(InterfaceError::Response(error)) => Err(error),
+        };
+
+        let reply_header = header.create_reply(&self.own_id, &our_authority);
+        let reply_to = match our_authority {
+            Authority::ClientManager => match header.reply_to() {
+                Some(client) => client,
+                None => header.from()
+            },
+            _ => header.from()
+        };
+        let put_data_response = PutDataResponse {
+            name : put_data.name.clone(),
+            data : reply_data,
+        };
+        let routing_msg = RoutingMessage::new(MessageTypeTag::PutDataResponse,
+            reply_header, put_data_response, &self.pmid.get_crypto_secret_sign_key());
+        let serialised_message = try!(self.serialise(&routing_msg));
+        self.send_swarm_or_parallel(&reply_to, &MessageTypeTag::PutDataResponse, &serialised_message);
+        Ok(())
+    }
+
+    fn handle_delete_data(&mut self, header: MessageHeader, body: Bytes) -> RecvResult {
+        let delete_data = try!(decode::<DeleteData>(&body));
+        let our_authority = self.our_authority(&delete_data.name, &header);
+        let from_authority = header.from_authority();
+        let from = header.from();
+        let to = header.send_to();
+
+        let result = match self.mut_interface().handle_delete(our_authority.clone(), from_authority,
+                                                              from, to, delete_data.name.clone()) {
+            Ok(Action::Reply(_)) => Ok(()),
+            Ok(Action::SendOn(destinations)) => {
+                return self.send_on(&header, &our_authority, MessageTypeTag::DeleteData,
+                                    delete_data.clone(), destinations);
+            },
+            Err(InterfaceError::Abort) => return Ok(()),
+            // a failed delete is reported back the same way as a successful one
+            Err(InterfaceError::Response(error)) => Err(error),
+        };
+
+        let reply_header = header.create_reply(&self.own_id, &our_authority);
+        let reply_to = match our_authority {
+            Authority::ClientManager => match header.reply_to() {
+                Some(client) => client,
+                None => header.from()
+            },
+            _ => header.from()
+        };
+        let delete_data_response = DeleteDataResponse {
+            name : delete_data.name.clone(),
+            result : result,
+        };
+        let routing_msg = RoutingMessage::new(MessageTypeTag::DeleteDataResponse,
+            reply_header, delete_data_response, &self.pmid.get_crypto_secret_sign_key());
+        let serialised_message = try!(self.serialise(&routing_msg));
+        self.send_swarm_or_parallel(&reply_to, &MessageTypeTag::DeleteDataResponse, &serialised_message);
+        Ok(())
+    }
+
+    fn handle_delete_data_response(&mut self, header: MessageHeader, body: Bytes) -> RecvResult {
+        let delete_data_response = try!(decode::<DeleteDataResponse>(&body));
+        let from_authority = header.from_authority();
+        let from = header.from();
+        self.mut_interface().handle_delete_response(from_authority, from, delete_data_response.result);
+        Ok(())
     }
 
     fn handle_put_data_response(&mut self, header: MessageHeader, body: Bytes) -> RecvResult {
         let put_data_response = try!(decode::<PutDataResponse>(&body));
+        if put_data_response.data.is_ok() {
+            self.invalidate_cached_data(&put_data_response.name);
+        }
         let from_authority = header.from_authority();
         let from = header.from();
         self.mut_interface().handle_put_response(from_authority, from, put_data_response.data);
@@ -881,9 +2508,10 @@ impl<F> RoutingNode<F> where F: Interface {
         )
     }
 
-    fn construct_find_group_msg(&mut self, reply_to: Option<NameType>) -> RoutingMessage {
+    fn construct_find_group_msg(&mut self, reply_to: Option<NameType>)
+            -> Result<RoutingMessage, RoutingError> {
         let header = MessageHeader::new(
-            self.get_next_message_id(),
+            try!(self.get_next_message_id()),
             types::DestinationAddress {
                  dest:     self.own_id.clone(),
                  reply_to: reply_to
@@ -891,16 +2519,17 @@ impl<F> RoutingNode<F> where F: Interface {
             self.our_source_address(),
             types::Authority::ManagedNode);
 
-        RoutingMessage::new(MessageTypeTag::FindGroup, header,
+        Ok(RoutingMessage::new(MessageTypeTag::FindGroup, header,
             FindGroup{ requester_id: self.own_id.clone(),
                        target_id:    self.own_id.clone()},
-            &self.pmid.get_crypto_secret_sign_key())
+            &self.pmid.get_crypto_secret_sign_key()))
     }
 
     fn construct_find_group_response_msg(&mut self, original_header : &MessageHeader,
                                          find_group: &FindGroup,
                                          group: Vec<types::PublicPmid>) -> RoutingMessage {
-        let header = MessageHeader::new(self.get_next_message_id(),
+        // answered under the id it was asked with, so the asker can tell its searches apart
+        let header = MessageHeader::new(original_header.message_id,
             original_header.send_to(),
             self.our_group_address(find_group.target_id.clone()),
             types::Authority::NaeManager);
@@ -918,128 +2547,653 @@ impl<F> RoutingNode<F> where F: Interface {
         return connect_success
     }
 
-    fn construct_connect_request_msg(&mut self, peer_id: &NameType) -> RoutingMessage {
-        let header = MessageHeader::new(self.get_next_message_id(),
+    fn construct_connect_request_msg(&mut self, peer_id: &NameType)
+            -> Result<RoutingMessage, RoutingError> {
+        let header = MessageHeader::new(try!(self.get_next_message_id()),
             types::DestinationAddress {dest: peer_id.clone(), reply_to: None },
             self.our_source_address(), types::Authority::ManagedNode);
 
-        // // FIXME: Discuss how to use other eps from the list. // FIXME prakash
-        // let first_or_invalid = |eps: Vec<Endpoint>| -> SocketAddr {
-        //     if eps.is_empty() {
-        //         SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0,0,0,0), 0))
-        //     }
-        //     else { match eps[0] { Tcp(ep) => ep.clone() } }
-        // };
-
-        // FIXME: We're sending all accepting connections as local since we don't differentiate
-        // between local and external yet.
+        // every endpoint we accept on is advertised; the peer tries them all
         let connect_request = ConnectRequest {
-            local_endpoints: self.accepting_on.clone(),
-            external_endpoints: vec![],
+            local_endpoints: self.accepting_endpoints().to_vec(),
+            external_endpoints: self.external_endpoints.clone(),
             requester_id: self.own_id.clone(),
             receiver_id: peer_id.clone(),
             requester_fob: types::PublicPmid::new(&self.pmid),
         };
 
-        RoutingMessage::new(MessageTypeTag::ConnectRequest, header, connect_request,
-            &self.pmid.get_crypto_secret_sign_key())
+        Ok(RoutingMessage::new(MessageTypeTag::ConnectRequest, header, connect_request,
+            &self.pmid.get_crypto_secret_sign_key()))
     }
 
     fn construct_connect_response_msg(&mut self, original_header : &MessageHeader,
-                                      connect_request: &ConnectRequest) -> RoutingMessage {
-        println!("{:?} construct_connect_response_msg ", self.own_id);
+                                      connect_request: &ConnectRequest)
+            -> Result<RoutingMessage, RoutingError> {
         debug_assert!(connect_request.receiver_id == self.own_id, format!("{:?} == {:?} failed", self.own_id, connect_request.receiver_id));
 
-        let header = MessageHeader::new(self.get_next_message_id(),
+        let header = MessageHeader::new(try!(self.get_next_message_id()),
             original_header.send_to(), self.our_source_address(),
             types::Authority::ManagedNode);
 
-        // // FIXME: Discuss how to use other eps from the list.
-        // let first_or_invalid = |eps: Vec<Endpoint>| -> SocketAddr {
-        //     if eps.is_empty() {
-        //         SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0,0,0,0), 0))
-        //     }
-        //     else { match eps[0] { Tcp(ep) => ep.clone() } }
-        // };
-
-        // FIXME: We're sending all accepting connections as local since we don't differentiate
-        // between local and external yet.
         let connect_response = ConnectResponse {
             requester_local_endpoints: connect_request.local_endpoints.clone(),
             requester_external_endpoints: connect_request.external_endpoints.clone(),
-            receiver_local_endpoints: self.accepting_on.clone(),
-            receiver_external_endpoints: vec![],
+            receiver_local_endpoints: self.accepting_endpoints().to_vec(),
+            receiver_external_endpoints: self.external_endpoints.clone(),
             requester_id: connect_request.requester_id.clone(),
             receiver_id: self.own_id.clone(),
             receiver_fob: types::PublicPmid::new(&self.pmid) };
 
-        RoutingMessage::new(MessageTypeTag::ConnectResponse, header,
-            connect_response, &self.pmid.get_crypto_secret_sign_key())
+        Ok(RoutingMessage::new(MessageTypeTag::ConnectResponse, header,
+            connect_response, &self.pmid.get_crypto_secret_sign_key()))
     }
 
-    fn construct_get_data_response_msg(&mut self, original_header: &MessageHeader,
-                                       get_data: &GetData, data: Vec<u8>) -> RoutingMessage {
-        let header = MessageHeader::new( self.get_next_message_id(),
+    fn construct_get_data_response_msg(&mut self, original_header: &MessageHeader, get_data: &GetData,
+                                       data: Result<Vec<u8>, ResponseError>)
+            -> Result<RoutingMessage, RoutingError> {
+        let header = MessageHeader::new(try!(self.get_next_message_id()),
             original_header.send_to(), self.our_source_address(),
             types::Authority::ManagedNode);
         let get_data_response = GetDataResponse {
-            name_and_type_id: get_data.name_and_type_id.clone(), data: Ok(data)
+            name_and_type_id: get_data.name_and_type_id.clone(), data: data, part: 0, total: 1
         };
-        RoutingMessage::new(MessageTypeTag::GetDataResponse, header,
-            get_data_response, &self.pmid.get_crypto_secret_sign_key())
+        Ok(RoutingMessage::new(MessageTypeTag::GetDataResponse, header,
+            get_data_response, &self.pmid.get_crypto_secret_sign_key()))
     }
 
-    fn get_next_message_id(&mut self) -> MessageId {
-        let temp = self.next_message_id;
-        self.next_message_id += 1;
-        return temp;
+    /// Whether the interface's cache hooks are called for data of type `type_id`.
+    fn is_cacheable(&self, type_id: u64) -> bool {
+        self.cacheable_type_ids.as_ref().map(|type_ids| type_ids.contains(&type_id)).unwrap_or(true)
     }
 
-    fn send_to(&self, endpoint: &Endpoint, serialised_message: Bytes) -> Result<(), io::Error> {
-        // FIXME: The send function of FM should take endpoint reference.
-        self.connection_manager.send(endpoint.clone(), serialised_message)
-    }
-
-    fn send_to_bootstrap_node(&mut self, routing_message: &RoutingMessage) {
-        // FIXME - remove unwrap
-        let _ = encode(&routing_message).map(
-            |msg| self.send_to(&self.bootstrap_endpoint.clone().unwrap(), msg));
+    /// Refuses data returned by the interface which exceeds the configured reply size, so a
+    /// misbehaving interface can't make us broadcast giant payloads.
+    fn check_reply_size(&self, data: Vec<u8>) -> Result<Vec<u8>, ResponseError> {
+        if data.len() > self.max_reply_size {
+            return Err(ResponseError::TooLarge);
+        }
+        Ok(data)
     }
 
-    fn send_swarm_or_parallel(&self, target: &NameType, serialised_message: &Bytes) {
-        for peer in self.get_connected_target(target) {
-            match self.all_connections.1.get(&peer.id()) {
-                Some(peer_ep) => {
-                    if self.send_to(&peer_ep, serialised_message.clone()).is_err() {
-                        println!("{:?} failed to send to {:?}", self.own_id, peer.id());
-                    }
-                }
-                None => {;}
+    /// Takes a token from `peer_id`'s bucket, returning false if it is empty and the message
+    /// should be dropped.  A peer which stays over rate is reported with `MessageDropped`.
+    fn check_message_rate(&mut self, peer_id: &NameType) -> bool {
+        let now = SteadyTime::now();
+        let (rate, burst) = (self.message_rate as f64, self.message_burst);
+        let report = {
+            let bucket = self.message_rates.entry(peer_id.clone())
+                                           .or_insert_with(|| MessageRate::new(burst));
+            let elapsed_ms = (now - bucket.last_refill).num_milliseconds() as f64;
+            bucket.tokens = (bucket.tokens + elapsed_ms * rate / 1000.0).min(burst as f64);
+            bucket.last_refill = now;
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                bucket.consecutive_drops = 0;
+                return true;
             }
+            bucket.consecutive_drops += 1;
+            bucket.consecutive_drops == MAX_RATE_LIMITED_MESSAGES
+        };
+        self.rate_limited_messages += 1;
+        if report {
+            self.emit(RoutingEvent::MessageDropped(RoutingError::RateLimitExceeded(peer_id.clone())));
         }
+        false
     }
 
+    /// Accounts for `size` bytes received from `peer_id`.  Returns false if the peer has used up
+    /// its byte budget for the current window, in which case the message should be dropped.  A
+    /// peer which stays over budget for `MAX_WINDOWS_EXCEEDED` consecutive windows is dropped.
+    fn check_inbound_bandwidth(&mut self, peer_endpoint: &Endpoint, peer_id: &NameType,
+                               size: usize) -> bool {
+        let now = SteadyTime::now();
+        let max_inbound_bytes = self.max_inbound_bytes;
+        let disconnect = {
+            let usage = self.inbound_bandwidth.entry(peer_id.clone())
+                                              .or_insert_with(PeerBandwidth::new);
+            if now - usage.window_start >= self.inbound_window {
+                if usage.window_bytes <= max_inbound_bytes {
+                    usage.windows_exceeded = 0;
+                }
+                usage.window_start = now;
+                usage.window_bytes = 0;
+            }
+            let was_within_budget = usage.window_bytes <= max_inbound_bytes;
+            usage.window_bytes += size;
+            usage.total_bytes += size as u64;
+            if usage.window_bytes <= max_inbound_bytes {
+                return true;
+            }
+            usage.dropped_messages += 1;
+            if was_within_budget {
+                usage.windows_exceeded += 1;
+            }
+            usage.windows_exceeded >= MAX_WINDOWS_EXCEEDED
+        };
+        if disconnect {
+            self.drop_peer(peer_endpoint.clone());
+        }
+        false
+    }
+
+    /// Pings connected peers we haven't heard from within the ping interval, and drops those
+    /// which were pinged and stayed silent past the deadline.
+    fn ping_silent_peers(&mut self) {
+        let now = SteadyTime::now();
+        let mut silent = Vec::new();
+        let mut due = Vec::new();
+        for (peer_id, peer_endpoint) in self.all_connections.1.iter() {
+            match self.pings_sent.get(peer_id) {
+                Some(sent) => if now - *sent >= self.ping_deadline {
+                    silent.push(peer_endpoint.clone());
+                },
+                None => {
+                    let last_seen = *self.last_seen.entry(peer_id.clone()).or_insert(now);
+                    if now - last_seen >= self.ping_interval {
+                        due.push(peer_id.clone());
+                    }
+                },
+            }
+        }
+        for peer_endpoint in silent {
+            self.drop_peer(peer_endpoint);
+        }
+        for peer_id in due {
+            self.pings_sent.insert(peer_id.clone(), now);
+            if let Err(error) = self.send_ping(MessageTypeTag::Ping, &peer_id) {
+                self.emit(RoutingEvent::MessageDropped(error));
+            }
+        }
+    }
+
+    fn send_ping(&mut self, message_type: MessageTypeTag, peer_id: &NameType)
+            -> Result<(), RoutingError> {
+        let message = RoutingMessage::new(message_type,
+            MessageHeader::new(try!(self.get_next_message_id()),
+                types::DestinationAddress{ dest: peer_id.clone(), reply_to: None },
+                types::SourceAddress{ from_node: self.id(), from_group: None, reply_to: None },
+                types::Authority::ManagedNode),
+            Ping { sender_id: self.id() }, &self.pmid.get_crypto_secret_sign_key());
+        self.send_direct(peer_id, &message)
+    }
+
+    /// Answers a ping from `peer_id`, the peer it came in from, whoever the body names.
+    fn handle_ping(&mut self, peer_id: &NameType, body: Bytes) -> RecvResult {
+        try!(decode::<Ping>(&body));
+        self.send_ping(MessageTypeTag::PingResponse, peer_id)
+    }
+
+    /// Sends the latest content of every coalesced refresh whose window has closed.
+    fn send_due_refreshes(&mut self) {
+        let now = SteadyTime::now();
+        let window = self.refresh_window;
+        let due: Vec<NameType> = self.pending_refreshes.iter()
+            .filter(|&(_, entry)| now - entry.0 >= window)
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in due {
+            if let Some((_, message_id, content)) = self.pending_refreshes.remove(&name) {
+                if let Err(error) = self.send_refresh(message_id, content) {
+                    self.emit(RoutingEvent::MessageDropped(error));
+                }
+            }
+        }
+    }
+
+    /// Message ids wrap around, so ids still awaiting a reply or held in the message filter are
+    /// skipped rather than reused.  Fails with `NoFreeMessageId` if none of the next
+    /// `MAX_MESSAGE_ID_ATTEMPTS` ids is free.
+    fn get_next_message_id(&mut self) -> Result<MessageId, RoutingError> {
+        // as a client our messages are sent under our bootstrap node's name
+        let source = self.our_source_address().from_node;
+        for _ in 0..MAX_MESSAGE_ID_ATTEMPTS {
+            let candidate = self.next_message_id;
+            self.next_message_id = self.next_message_id.wrapping_add(1);
+            if !self.pending_gets.contains_key(&candidate) &&
+               !self.pending_find_groups.contains_key(&candidate) &&
+               !self.filter.check(&((self.own_id.clone(), candidate), 0)) &&
+               !self.filter.check(&((source.clone(), candidate), 0)) {
+                return Ok(candidate);
+            }
+        }
+        Err(RoutingError::NoFreeMessageId)
+    }
+
+    fn send_to(&self, endpoint: &Endpoint, serialised_message: Bytes) -> Result<(), io::Error> {
+        // FIXME: The send function of FM should take endpoint reference.
+        self.connection_manager.send(endpoint.clone(),
+                                     compression::pack_as(&serialised_message, self.serialisation, None))
+    }
+
+    /// Asks the interface whether a message of `message_type` for `target` may leave the node,
+    /// counting a refusal.
+    fn send_allowed(&mut self, target: &NameType, message_type: &MessageTypeTag) -> bool {
+        if self.mut_interface().allow_send(target, message_type.clone()) {
+            return true;
+        }
+        self.vetoed_sends += 1;
+        false
+    }
+
+    /// Sends `message` to the peer `id` over our connection to it, once, rather than routing it
+    /// by proximity.  Fails if we aren't connected to `id`.
+    pub fn send_direct(&mut self, id: &NameType, message: &RoutingMessage) -> Result<(), RoutingError> {
+        let endpoint = match self.all_connections.1.get(id) {
+            Some(endpoint) => endpoint.clone(),
+            None => return Err(RoutingError::NotConnected(id.clone())),
+        };
+        if !self.send_allowed(id, &message.message_type) {
+            return Err(RoutingError::NotSent);
+        }
+        let serialised_message = try!(self.serialise(message));
+        try!(self.check_message_size(&serialised_message));
+        try!(self.send_to(&endpoint, serialised_message));
+        self.message_stats.count_sent(&message.message_type);
+        Ok(())
+    }
+
+    fn send_to_bootstrap_node(&mut self, routing_message: &RoutingMessage)
+            -> Result<(), RoutingError> {
+        let bootstrap_endpoint = match self.bootstrap_endpoint.clone() {
+            Some(endpoint) => endpoint,
+            None => return Err(RoutingError::NotBootstrapped),
+        };
+        if !self.send_allowed(&routing_message.message_header.destination.dest,
+                              &routing_message.message_type) {
+            return Err(RoutingError::NotSent);
+        }
+        let message = try!(self.serialise(&routing_message));
+        try!(self.check_message_size(&message));
+        try!(self.send_to(&bootstrap_endpoint, message));
+        self.message_stats.count_sent(&routing_message.message_type);
+        Ok(())
+    }
+
+    /// Encodes `message` and sends it towards `target`, returning its id.  Fails if it reached no
+    /// peer.
+    fn send_message(&mut self, target: &NameType, message: &RoutingMessage)
+                    -> Result<MessageId, RoutingError> {
+        let serialised_message = try!(self.serialise(message));
+        try!(self.check_message_size(&serialised_message));
+        if self.send_swarm_or_parallel(target, &message.message_type, &serialised_message) == 0 {
+            return Err(RoutingError::NotSent);
+        }
+        Ok(message.message_header.message_id)
+    }
+
+    /// Sends `message` to the close group of `group`, through those of our peers closest to it.
+    fn send_to_group(&mut self, group: &NameType, message: &RoutingMessage)
+                     -> Result<MessageId, RoutingError> {
+        self.send_message(group, message)
+    }
+
+    /// Sends a message we originate on to `destination`.  A node we are connected to is sent to
+    /// directly; any other is routed to like a group.
+    fn send_to_destination(&mut self, destination: &DestinationKind, message: &RoutingMessage)
+                           -> Result<MessageId, RoutingError> {
+        match *destination {
+            DestinationKind::Node(ref id) if self.all_connections.1.contains_key(id) => {
+                try!(self.send_direct(id, message));
+                Ok(message.message_header.message_id)
+            },
+            DestinationKind::Node(ref id) => self.send_message(id, message),
+            DestinationKind::Group(ref group) => self.send_to_group(group, message),
+        }
+    }
+
+    /// The header address of a message we originate for `destination`.
+    fn destination_address(&self, destination: &DestinationKind) -> types::DestinationAddress {
+        types::DestinationAddress { dest: destination.name().clone(), reply_to: None }
+    }
+
+    /// Encodes a message we put on the wire in our serialisation.
+    fn serialise(&self, message: &RoutingMessage) -> Result<Bytes, RoutingError> {
+        self.serialisation.serialise(message)
+    }
+
+    /// Fails with `MessageTooLarge` if `serialised_message` is over the message size limit.
+    fn check_message_size(&self, serialised_message: &Bytes) -> Result<(), RoutingError> {
+        if serialised_message.len() > self.max_message_size {
+            return Err(RoutingError::MessageTooLarge(serialised_message.len()));
+        }
+        Ok(())
+    }
+
+    /// Sends to the peers we hold for `target`, returning how many of them the message was
+    /// handed to.  Zero means it reached no one, e.g. because our routing table is empty.  The
+    /// sends run side by side on the send pool, so a slow peer doesn't hold up the others.
+    fn send_swarm_or_parallel(&mut self, target: &NameType, message_type: &MessageTypeTag,
+                              serialised_message: &Bytes) -> usize {
+        if let Err(error) = self.check_message_size(serialised_message) {
+            self.emit(RoutingEvent::MessageDropped(error));
+            return 0;
+        }
+        if !self.send_allowed(target, message_type) {
+            return 0;
+        }
+        // only data is worth deflating, control messages are small and always sent plain
+        let threshold = match *message_type {
+            MessageTypeTag::PutData | MessageTypeTag::Refresh |
+            MessageTypeTag::GetDataResponse => self.compression_threshold,
+            _ => None,
+        };
+        let mut packed = compression::pack_as(serialised_message, self.serialisation, threshold);
+        let peers: Vec<(NodeInfo, Endpoint)> = self.get_connected_target(target).into_iter()
+            .filter_map(|peer| self.all_connections.1.get(&peer.id()).cloned()
+                                   .map(|peer_ep| (peer, peer_ep)))
+            .collect();
+        let last = peers.len().saturating_sub(1);
+        let sends = peers.iter().enumerate().map(|(index, &(_, ref peer_ep))| {
+            // crust wants a buffer per send; the last peer takes ours rather than a copy
+            let bytes = if index == last { mem::replace(&mut packed, vec![]) } else { packed.clone() };
+            (peer_ep.clone(), bytes)
+        }).collect::<Vec<_>>();
+        let outcomes = if sends.len() == 1 {
+            // not worth handing a single send to another thread
+            sends.into_iter().map(|(peer_ep, bytes)| self.connection_manager.send(peer_ep, bytes))
+                 .collect::<Vec<_>>()
+        } else {
+            self.send_pool.send_all(sends)
+        };
+        let mut sent = 0usize;
+        for ((peer, peer_ep), outcome) in peers.into_iter().zip(outcomes.into_iter()) {
+            match outcome {
+                Ok(()) => sent += 1,
+                Err(error) => {
+                    self.emit(RoutingEvent::MessageDropped(From::from(error)));
+                    self.failed_sends += 1;
+                    self.reconnect_for_retry(&peer, peer_ep, serialised_message.clone());
+                },
+            }
+        }
+        if sent > 0 {
+            self.message_stats.count_sent(message_type);
+        }
+        sent
+    }
+
+    /// Drops a connection which failed to send and redials the peer on its known endpoints.  The
+    /// message is held until the peer reconnects and is then retried once in `handle_connect`.  If
+    /// the peer isn't back within the redial timeout, its held messages are dropped.
+    fn reconnect_for_retry(&mut self, peer: &NodeInfo, failed_endpoint: Endpoint,
+                           serialised_message: Bytes) {
+        let peer_id = peer.id();
+        if let Some(&mut (_, ref mut messages)) = self.pending_retries.get_mut(&peer_id) {
+            // a reconnect is already under way
+            messages.push(serialised_message);
+            return;
+        }
+        self.connection_manager.drop_node(failed_endpoint.clone());
+        self.all_connections.0.remove(&failed_endpoint);
+        self.all_connections.1.remove(&peer_id);
+        // so that nothing more is sent to the dead connection while the peer is redialled
+        let _ = self.routing_table.mark_as_disconnected(&peer_id);
+        let deadline = SteadyTime::now() + Duration::seconds(RETRY_REDIAL_TIMEOUT_SECS);
+        self.pending_retries.insert(peer_id, (deadline, vec![serialised_message]));
+        self.connection_manager.connect(peer.endpoints.clone());
+    }
+
+    /// The connected nodes a message for `target` should go to.  Ideal targets we aren't
+    /// connected to are made up for by the connected members of our close group closest to
+    /// `target`, so messages keep moving during churn.
     fn get_connected_target(&self, target: &NameType) -> Vec<NodeInfo> {
         let mut nodes = self.routing_table.target_nodes(target.clone());
-        //println!("{:?} get_connected_target routing_table.size:{} target:{:?} -> {:?}", self.own_id, self.routing_table.size(), target, nodes);
+        let wanted = nodes.len();
         nodes.retain(|ref candidate| candidate.connected_endpoint.is_some());
+        if nodes.len() < wanted {
+            let missing = wanted - nodes.len();
+            let mut alternates = self.routing_table.our_close_group().into_iter()
+                .filter(|node| node.connected_endpoint.is_some() &&
+                               !nodes.iter().any(|chosen| chosen.id() == node.id()))
+                .collect::<Vec<_>>();
+            alternates.sort_by(|lhs, rhs| if closer_to_target(&lhs.id(), &rhs.id(), target) {
+                ::std::cmp::Ordering::Less
+            } else {
+                ::std::cmp::Ordering::Greater
+            });
+            nodes.extend(alternates.into_iter().take(missing));
+        }
         nodes
     }
 
     fn address_in_close_group_range(&self, address: &NameType) -> bool {
-        if self.routing_table.size() < RoutingTable::get_group_size() {
+        if self.routing_table.size() < self.routing_table.group_size() {
             return true;
         }
 
         let close_group = self.routing_table.our_close_group();
-        closer_to_target(&address, &self.routing_table.our_close_group().pop().unwrap().id(), &self.own_id)
+        closer_to_target(&address, &close_group.last().unwrap().id(), &self.own_id)
     }
 
     pub fn id(&self) -> NameType { self.own_id.clone() }
 
+    /// How full our routing table is, as (current size, close group size, optimal size).  Once the
+    /// current size reaches the group size we hold a full close group.
+    pub fn routing_table_stats(&self) -> (usize, usize, usize) {
+        (self.routing_table.size(), self.routing_table.group_size(), RoutingTable::get_optimal_size())
+    }
+
+    /// Whether we found nobody but our bootstrap node in our close group once bootstrapped, i.e.
+    /// we are among the first nodes of the network.
+    pub fn is_first_node(&self) -> bool { self.first_node }
+
+    /// The XOR distance from us to `target`.
+    pub fn distance_to(&self, target: &NameType) -> NameType { xor_distance(&self.own_id, target) }
+
+    /// Whether `lhs` is closer to us than `rhs`.
+    pub fn is_closer(&self, lhs: &NameType, rhs: &NameType) -> bool {
+        closer_to_target(lhs, rhs, &self.own_id)
+    }
+
+    /// Every endpoint we accept connections on, one per listener.
+    pub fn accepting_endpoints(&self) -> &[Endpoint] { &self.accepting_on }
+
+    /// Number of nodes in our routing table.
+    pub fn routing_table_size(&self) -> usize { self.routing_table.size() }
+
+    /// Names of the nodes in our close group, closest first.
+    pub fn close_group(&self) -> Vec<NameType> {
+        self.routing_table.our_close_group().iter().map(|node| node.id()).collect()
+    }
+
+    /// The nodes which joined and left our close group in the last churn, as (joined, left),
+    /// each sorted.
+    pub fn churn_delta(&self) -> (Vec<NameType>, Vec<NameType>) {
+        let close_group = self.routing_table.close_group_snapshot();
+        let joined = close_group.iter().filter(|id| !self.previous_close_group.contains(id))
+                                .cloned().collect();
+        let left = self.previous_close_group.iter().filter(|id| !close_group.contains(id))
+                                            .cloned().collect();
+        (joined, left)
+    }
+
+    /// The endpoint over which we are connected to `id`, if any.
+    pub fn connection_for(&self, id: &NameType) -> Option<Endpoint> {
+        self.all_connections.1.get(id).cloned()
+    }
+
+    /// Whether we currently hold a connection to `id`.
+    pub fn is_connected(&self, id: &NameType) -> bool {
+        self.all_connections.1.contains_key(id)
+    }
+
+    /// Records `endpoint` as our connection to `id` without the bootstrap id exchange, e.g. to
+    /// set up a known topology.  If we have no bootstrap node yet, this becomes it.  Given the
+    /// peer's `fob`, it is added to the routing table too.  Nothing is checked: the peer must
+    /// really be reachable at `endpoint`, or sends to it will fail.
+    pub fn add_known_node(&mut self, id: NameType, endpoint: Endpoint,
+                          fob: Option<types::PublicPmid>) {
+        if self.bootstrap_node_id.is_none() {
+            self.bootstrap_node_id = Some(id.clone());
+            self.bootstrap_endpoint = Some(endpoint.clone());
+        }
+        self.all_connections.0.insert(endpoint.clone(), id.clone());
+        self.all_connections.1.insert(id, endpoint.clone());
+        if let Some(fob) = fob {
+            let _ = self.add_node(NodeInfo::new(fob, vec![endpoint.clone()], Some(endpoint)));
+        }
+    }
+
+    /// Sets how many consecutive undecodable messages, received within `window`, we accept from
+    /// a peer before dropping it.
+    pub fn set_decode_failure_policy(&mut self, max_failures: u32, window: Duration) {
+        self.max_decode_failures = max_failures;
+        self.decode_failure_window = window;
+    }
+
+    /// Endpoints we recently connected to successfully, serialised so they can be persisted and
+    /// given to `load_bootstrap_cache` after a restart.
+    pub fn bootstrap_cache_bytes(&self) -> Result<Bytes, RoutingError> {
+        encode(&self.bootstrap_cache).map_err(From::from)
+    }
+
+    /// Replaces our bootstrap cache with one from `bootstrap_cache_bytes`.  `bootstrap` tries these
+    /// endpoints when it isn't given a list.
+    pub fn load_bootstrap_cache(&mut self, bytes: &Bytes) -> Result<(), RoutingError> {
+        let mut bootstrap_cache = try!(decode::<Vec<Endpoint>>(bytes));
+        bootstrap_cache.truncate(BOOTSTRAP_CACHE_SIZE);
+        self.bootstrap_cache = bootstrap_cache;
+        Ok(())
+    }
+
+    /// Sets how long a connected peer may stay silent before we ping it, and how long after that
+    /// we wait to hear anything from it before treating the connection as lost.
+    pub fn set_ping_policy(&mut self, interval: Duration, deadline: Duration) {
+        self.ping_interval = interval;
+        self.ping_deadline = deadline;
+    }
+
+    /// Sets how long a node added to the routing table has to connect to us before it is dropped.
+    pub fn set_connect_timeout(&mut self, timeout: Duration) {
+        self.connect_timeout = timeout;
+    }
+
+    /// Sets the endpoints at which peers outside our network can reach us, e.g. addresses mapped
+    /// on a NAT device.  These are advertised separately from the endpoints we accept on.
+    pub fn set_external_endpoints(&mut self, external_endpoints: Vec<Endpoint>) {
+        self.external_endpoints = external_endpoints;
+    }
+
+    /// Sets how many public keys put to our group are held for answering GetKey, and for how
+    /// long.  The least recently used entry is evicted once `capacity` is reached; a get for an
+    /// evicted key goes to the interface.  Anything already held is dropped.
+    pub fn set_public_key_cache(&mut self, capacity: usize, expiry: Duration) {
+        self.public_key_cache = LruCache::with_expiry_duration_and_capacity(expiry, capacity);
+    }
+
+    /// Sets how many times, `interval` apart, we try to reconnect to a routing table peer whose
+    /// connection was lost while it is still in range.  Reconnecting routes a connect request to
+    /// the peer and dials its last known endpoints.  A peer not reached in time is reported with
+    /// `RoutingEvent::ReconnectFailed`.  0, the default, drops lost peers without retrying.
+    pub fn set_reconnect(&mut self, attempts: u32, interval: Duration) {
+        self.reconnect_attempts = attempts;
+        self.reconnect_interval = interval;
+    }
+
+    /// Sets how long a `get` waits for its response before `ResponseError::Timeout` is delivered
+    /// in its place.  Gets already made keep the deadline they were given.
+    pub fn set_get_timeout(&mut self, timeout: Duration) {
+        self.get_timeout = timeout;
+    }
+
+    /// Sets the most routing table entries which may share an IP address.  Connect requests and
+    /// responses from further nodes on that address are refused.  `None`, the default, sets no
+    /// limit.
+    pub fn set_max_nodes_per_ip(&mut self, max_nodes: Option<usize>) {
+        self.max_nodes_per_ip = max_nodes;
+    }
+
+    /// Sets the number of leading bits a public pmid's name must share with our own id for us to
+    /// cache it.  The default of 0 accepts any pmid we are NaeManager for.
+    pub fn set_min_pmid_leading_bits(&mut self, leading_bits: usize) {
+        self.min_pmid_leading_bits = leading_bits;
+    }
+
+    /// Sets how many GetData responses are cached, and for how long they are served.  Anything
+    /// already cached is dropped.
+    pub fn set_data_cache(&mut self, capacity: usize, time_to_live: Duration) {
+        self.data_cache = LruCache::with_expiry_duration_and_capacity(time_to_live, capacity);
+        self.data_cache_types.clear();
+    }
+
+    /// Sets the size, in bytes, above which PutData and GetDataResponse messages are deflated
+    /// before being sent.  `None` sends everything uncompressed.
+    pub fn set_compression_threshold(&mut self, threshold: Option<usize>) {
+        self.compression_threshold = threshold;
+    }
+
+    /// Sets the size, in bytes, of the parts GetData responses larger than that are split into.
+    pub fn set_get_response_part_size(&mut self, part_size: usize) {
+        assert!(part_size > 0);
+        self.get_response_part_size = part_size;
+    }
+
+    /// Sets the data types, by type id, the interface's cache hooks are called for: only responses
+    /// carrying these types are offered to `handle_cache_put`, and only gets for them are looked
+    /// up with `handle_cache_get`.  `None`, the default, caches every type.
+    pub fn set_cacheable_type_ids(&mut self, type_ids: Option<HashSet<u64>>) {
+        self.cacheable_type_ids = type_ids;
+    }
+
+    /// Sets the largest reply, in bytes, we are prepared to send on behalf of `handle_get` and
+    /// `handle_get_key`.  Larger replies are refused with `ResponseError::TooLarge`.
+    pub fn set_max_reply_size(&mut self, max_reply_size: usize) {
+        self.max_reply_size = max_reply_size;
+    }
+
+    /// Sets the largest serialised message, in bytes, we send or accept.  Sending a larger
+    /// message fails with `RoutingError::MessageTooLarge`; a larger incoming message is dropped,
+    /// once inflated if it arrived compressed.
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+
+    /// Sets the number of bytes a single peer may send us within each `window`.  Messages beyond
+    /// that are dropped until the window resets.
+    pub fn set_inbound_byte_limit(&mut self, max_bytes: usize, window: Duration) {
+        self.max_inbound_bytes = max_bytes;
+        self.inbound_window = window;
+    }
+
+    /// Sets the number of messages per second, and the burst above that, we process from a
+    /// single peer.  Messages beyond that are dropped.
+    pub fn set_message_rate_limit(&mut self, rate: u32, burst: u32) {
+        self.message_rate = rate;
+        self.message_burst = burst;
+        self.message_rates.clear();
+    }
+
+    /// Inbound traffic counters for each currently connected peer.
+    pub fn inbound_bandwidth(&self) -> &HashMap<NameType, PeerBandwidth> { &self.inbound_bandwidth }
+
+    /// Sets the window over which repeated refreshes of the same content are coalesced.  A zero
+    /// window sends every refresh immediately.
+    pub fn set_refresh_window(&mut self, window: Duration) {
+        self.refresh_window = window;
+    }
+
+    /// Number of sends to a connected peer which failed at the socket.
+    pub fn failed_sends(&self) -> usize { self.failed_sends }
+
+    /// Number of incoming messages dropped because their sender was over its message rate.
+    pub fn rate_limited_messages(&self) -> usize { self.rate_limited_messages }
+
+    /// Number of outgoing messages dropped because `Interface::allow_send` refused them.
+    pub fn vetoed_sends(&self) -> usize { self.vetoed_sends }
+
+    /// How many messages of each type we have sent and received.
+    pub fn message_stats(&self) -> MessageStats { self.message_stats.clone() }
+
     fn mut_interface(&mut self) -> &mut F { self.interface.deref_mut() }
 }
 
+fn endpoint_ip(endpoint: &Endpoint) -> String {
+    match *endpoint {
+        crust::Endpoint::Tcp(SocketAddr::V4(ref address)) => address.ip().to_string(),
+        crust::Endpoint::Tcp(SocketAddr::V6(ref address)) => address.ip().to_string(),
+    }
+}
+
 fn encode<T>(value: &T) -> Result<Bytes, CborError> where T: Encodable {
     let mut enc = Encoder::from_memory();
     try!(enc.encode(&[value]));
@@ -1056,51 +3210,102 @@ fn decode<T>(bytes: &Bytes) -> Result<T, CborError> where T: Decodable {
 
 #[cfg(test)]
 mod test {
-    use routing_node::{RoutingNode};
+    use routing_node::{RoutingNode, RoutingConfig, NodeSnapshot, Endpoint};
     use node_interface::*;
     use name_type::NameType;
-    use super::encode;
+    use super::{encode, decode, RecvResult, BOOTSTRAP_CACHE_SIZE, MAX_RATE_LIMITED_MESSAGES,
+                MAX_PARTIAL_GET_RESPONSES, MAX_UNVERIFIED_MESSAGES, REFRESH_ACCUMULATION_SECS,
+                MAX_MESSAGE_ID_ATTEMPTS};
     use super::super::Action;
-    use error::{ResponseError, InterfaceError};
+    use error::{ResponseError, InterfaceError, RoutingError};
+    use event::RoutingEvent;
     use sendable::Sendable;
     use messages::put_data::PutData;
+    use messages::delete_data::DeleteData;
+    use messages::delete_data_response::DeleteDataResponse;
     use messages::put_data_response::PutDataResponse;
     use messages::get_data::GetData;
     use messages::get_data_response::GetDataResponse;
     use messages::get_client_key::GetKey;
+    use messages::ping::Ping;
     use messages::post::Post;
+    use messages::post_response::PostResponse;
+    use messages::put_key::PutKey;
     use messages::put_public_pmid::PutPublicPmid;
+    use messages::bootstrap_id_request::BootstrapIdRequest;
+    use messages::bootstrap_id_response::BootstrapIdResponse;
+    use messages::connect_request::ConnectRequest;
+    use messages::find_group::FindGroup;
+    use messages::find_group_response::FindGroupResponse;
+    use messages::connect_response::ConnectResponse;
     use messages::{RoutingMessage, MessageTypeTag};
-    use message_header::MessageHeader;
+    use message_header::{MessageHeader, DEFAULT_TTL};
+    use compression;
     use types::{MessageId};
     use std::sync::{Arc, Mutex};
+    use std::sync::mpsc;
     use routing_table;
     use test_utils::{Random, xor};
     use rand::random;
-    use name_type::{closer_to_target};
+    use name_type::{closer_to_target, NAME_TYPE_LEN};
     use types;
     use types::{Pmid, PublicPmid, Authority};
+    use serialisation::Serialisation;
     use rustc_serialize::{Encodable, Decodable};
     use cbor::{Encoder};
     use std::thread;
     use test_utils::{random_endpoint, random_endpoints};
+    use time::{Duration, SteadyTime};
+    use crust;
+    use crust::Endpoint::Tcp;
+    use std::net::TcpListener;
 
     struct NullInterface;
 
     #[derive(Clone)]
     struct Stats {
         call_count: u32,
-        data: Vec<u8>
+        data: Vec<u8>,
+        sent: Vec<MessageTypeTag>,
+        churn: Vec<Vec<NameType>>
     }
 
     struct TestInterface {
         stats: Arc<Mutex<Stats>>
     }
 
+    // the types of data TestInterface's cache holds, doesn't hold, or refuses to look up
+    const CACHE_HIT_TYPE_ID: u64 = 1001;
+    const CACHE_SEND_ON_TYPE_ID: u64 = 1002;
+    const CACHE_ERROR_TYPE_ID: u64 = 1003;
+
     struct TestData {
         data: Vec<u8>
     }
 
+    struct NamedTestData {
+        name: NameType,
+        data: Vec<u8>
+    }
+
+    impl Sendable for NamedTestData {
+        fn name(&self) -> NameType { self.name.clone() }
+
+        fn type_tag(&self)->u64 { unimplemented!() }
+
+        fn serialised_contents(&self)->Vec<u8> { self.data.clone() }
+
+        fn refresh(&self)->bool { true }
+
+        // agrees only if every copy is the same
+        fn merge(&self, responses: Vec<Box<Sendable>>) -> Option<Box<Sendable>> {
+            if responses.iter().any(|response| response.serialised_contents() != self.data) {
+                return None;
+            }
+            Some(Box::new(NamedTestData { name: self.name.clone(), data: self.data.clone() }))
+        }
+    }
+
     impl TestData {
         fn new(in_data: Vec<u8>) -> TestData {
             TestData { data: in_data }
@@ -1127,6 +3332,9 @@ mod test {
             let stats = self.stats.clone();
             let mut stats_value = stats.lock().unwrap();
             stats_value.call_count += 1;
+            if stats_value.data.is_empty() {
+                return Err(InterfaceError::Response(ResponseError::NoData));
+            }
             let data = stats_value.data.clone();
             Ok(Action::Reply(data))
         }
@@ -1147,22 +3355,43 @@ mod test {
                 types::Authority::Unknown => "UnauthorisedPut".to_string().into_bytes(),
                 _   => "AuthorisedPut".to_string().into_bytes(),
             };
+            if data.is_empty() {
+                return Err(InterfaceError::Response(ResponseError::InvalidRequest));
+            }
             Ok(Action::Reply(data))
         }
+        fn authorise_unauthorised_put(&mut self, name: &NameType, data: &Vec<u8>) -> bool {
+            !data.is_empty()
+        }
+        fn handle_delete(&mut self, our_authority: types::Authority, from_authority: types::Authority,
+                         from_address: NameType, dest_address: types::DestinationAddress,
+                         name: NameType) -> Result<Action, InterfaceError> {
+            let stats = self.stats.clone();
+            let mut stats_value = stats.lock().unwrap();
+            stats_value.call_count += 1;
+            stats_value.data = name.get_id().to_vec();
+            if name == NameType::new([0u8; 64]) {
+                return Err(InterfaceError::Response(ResponseError::NoData));
+            }
+            Ok(Action::Reply(vec![]))
+        }
         fn handle_post(&mut self, our_authority: types::Authority, from_authority: types::Authority,
                        from_address: NameType, name: NameType, data: Vec<u8>) -> Result<Action, InterfaceError> {
             let stats = self.stats.clone();
             let mut stats_value = stats.lock().unwrap();
             stats_value.call_count += 1;
             stats_value.data = data.clone();
+            if data.is_empty() {
+                return Err(InterfaceError::Response(ResponseError::InvalidRequest));
+            }
             Ok(Action::Reply(data))
         }
-        fn handle_get_response(&mut self, from_address: NameType, response: Result<Vec<u8>,
-                               ResponseError>) -> RoutingNodeAction {
+        fn handle_get_response(&mut self, from_authority: types::Authority, from_address: NameType,
+                               response: Result<Vec<u8>, ResponseError>) -> RoutingNodeAction {
             let stats = self.stats.clone();
             let mut stats_value = stats.lock().unwrap();
             stats_value.call_count += 1;
-            stats_value.data = "handle_get_response called".to_string().into_bytes();
+            stats_value.data = encode(&from_authority).unwrap();
             RoutingNodeAction::None
         }
         fn handle_put_response(&mut self, from_authority: types::Authority, from_address: NameType,
@@ -1175,33 +3404,69 @@ mod test {
                 Err(_) => vec![]
             };
         }
+        fn handle_delete_response(&mut self, from_authority: types::Authority, from_address: NameType,
+                                  response: Result<(), ResponseError>) {
+            let stats = self.stats.clone();
+            let mut stats_value = stats.lock().unwrap();
+            stats_value.call_count += 1;
+            stats_value.data = match response {
+                Ok(()) => "DeleteSucceeded".to_string().into_bytes(),
+                Err(_) => vec![]
+            };
+        }
         fn handle_post_response(&mut self, from_authority: types::Authority, from_address: NameType,
                                 response: Result<Vec<u8>, ResponseError>) {
-            unimplemented!();
+            let stats = self.stats.clone();
+            let mut stats_value = stats.lock().unwrap();
+            stats_value.call_count += 1;
+            stats_value.data = match response {
+               Ok(data) => data,
+                Err(_) => vec![]
+            };
         }
         fn handle_churn(&mut self, close_group: Vec<NameType>)
             -> Vec<RoutingNodeAction> {
-            unimplemented!();
+            let stats = self.stats.clone();
+            let mut stats_value = stats.lock().unwrap();
+            stats_value.churn.push(close_group);
+            vec![]
         }
         fn handle_cache_get(&mut self, type_id: u64, name : NameType, from_authority: types::Authority,
                             from_address: NameType) -> Result<Action, InterfaceError> {
-            Err(InterfaceError::Abort)
+            match type_id {
+                CACHE_HIT_TYPE_ID => Ok(Action::Reply("cached".to_string().into_bytes())),
+                CACHE_SEND_ON_TYPE_ID => Ok(Action::SendOn(vec![name])),
+                CACHE_ERROR_TYPE_ID => Err(InterfaceError::Response(ResponseError::InvalidRequest)),
+                _ => Err(InterfaceError::Abort),
+            }
         }
         fn handle_cache_put(&mut self, from_authority: types::Authority, from_address: NameType,
                             data: Vec<u8>) -> Result<Action, InterfaceError> {
+            let stats = self.stats.clone();
+            let mut stats_value = stats.lock().unwrap();
+            stats_value.data = "handle_cache_put called".to_string().into_bytes();
             Err(InterfaceError::Abort)
         }
+        fn parse_refresh(&mut self, name: &NameType, data: &Vec<u8>) -> Option<Box<Sendable>> {
+            Some(Box::new(NamedTestData { name: name.clone(), data: data.clone() }))
+        }
+        fn allow_send(&mut self, target: &NameType, message_type: MessageTypeTag) -> bool {
+            let stats = self.stats.clone();
+            let mut stats_value = stats.lock().unwrap();
+            stats_value.sent.push(message_type.clone());
+            message_type != MessageTypeTag::AccountTransfer
+        }
     }
 
     #[test]
     fn check_next_id() {
-      let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]})) });
-      assert_eq!(routing_node.get_next_message_id() + 1, routing_node.get_next_message_id());
+      let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+      assert_eq!(routing_node.get_next_message_id().unwrap() + 1, routing_node.get_next_message_id().unwrap());
     }
 
     #[test]
     fn our_authority_full_routing_table() {
-        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]})) });
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
 
         let mut count : usize = 0;
         loop {
@@ -1260,7 +3525,8 @@ mod test {
                 from_node : nae_or_client_in_our_close_group.clone(),
                 from_group : None,
                 reply_to : None },
-            authority : types::Authority::Client
+            authority : types::Authority::Client,
+            ttl : DEFAULT_TTL
         };
         assert_eq!(routing_node.our_authority(&name_outside_close_group,
                                               &client_manager_header),
@@ -1276,7 +3542,8 @@ mod test {
                 from_node : Random::generate_random(),
                 from_group : Some(name_outside_close_group.clone()),
                 reply_to : None },
-            authority : types::Authority::ClientManager
+            authority : types::Authority::ClientManager,
+            ttl : DEFAULT_TTL
         };
         assert_eq!(routing_node.our_authority(&nae_or_client_in_our_close_group,
                                               &nae_manager_header),
@@ -1292,7 +3559,8 @@ mod test {
                 from_node : Random::generate_random(),
                 from_group : Some(name_outside_close_group.clone()),
                 reply_to : None },
-            authority : types::Authority::NaeManager
+            authority : types::Authority::NaeManager,
+            ttl : DEFAULT_TTL
         };
         assert_eq!(routing_node.our_authority(&name_outside_close_group,
                                               &node_manager_header),
@@ -1308,7 +3576,8 @@ mod test {
                 from_node : Random::generate_random(),
                 from_group : Some(second_closest_node_in_our_close_group.id.clone()),
                 reply_to : None },
-            authority : types::Authority::NodeManager
+            authority : types::Authority::NodeManager,
+            ttl : DEFAULT_TTL
         };
         assert_eq!(routing_node.our_authority(&name_outside_close_group,
                                               &managed_node_header),
@@ -1318,18 +3587,21 @@ mod test {
     fn call_operation<T>(operation: T, message_type: MessageTypeTag, stats: Arc<Mutex<Stats>>) -> Stats where T: Encodable, T: Decodable {
         let stats_copy = stats.clone();
         let mut n1 = RoutingNode::new(TestInterface { stats: stats_copy });
+        let sender = Pmid::new();
+        n1.public_pmid_cache.add(sender.get_name(), PublicPmid::new(&sender));
         let header = MessageHeader {
-            message_id:  n1.get_next_message_id(),
+            message_id:  n1.get_next_message_id().unwrap(),
             destination: types::DestinationAddress { dest: n1.own_id.clone(), reply_to: None },
-            source:      types::SourceAddress { from_node: Random::generate_random(), from_group: None, reply_to: None },
+            source:      types::SourceAddress { from_node: sender.get_name(), from_group: None, reply_to: None },
             authority:   match message_type {
                 MessageTypeTag::UnauthorisedPut => Authority::Unknown,
                 _ => Authority::NaeManager
-                }
+                },
+            ttl:         DEFAULT_TTL
         };
 
         let message = RoutingMessage::new( message_type, header.clone(),
-            operation, &n1.pmid.get_crypto_secret_sign_key());
+            operation, &sender.get_crypto_secret_sign_key());
 
         let serialised_msssage = encode(&message).unwrap();
 
@@ -1339,71 +3611,202 @@ mod test {
         stats_value.clone()
     }
 
+#[test]
+    fn with_config() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let n1 = RoutingNode::with_config(TestInterface { stats: stats.clone() },
+                                          RoutingConfig { ports_and_protocols: vec![],
+                                                          beacon_port: None,
+                                                          ..RoutingConfig::default() }).unwrap();
+        assert!(n1.listening_for_broadcasts_on_port.is_none());
+        assert!(!n1.accepting_on.is_empty());
+
+        // find a free port to ask for
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let n2 = RoutingNode::with_config(TestInterface { stats: stats.clone() },
+                                          RoutingConfig { ports_and_protocols: vec![crust::Port::Tcp(port)],
+                                                          beacon_port: Some(port),
+                                                          ..RoutingConfig::default() }).unwrap();
+        assert_eq!(n2.listening_for_broadcasts_on_port, Some(port));
+        let accepting_ports: Vec<u16> = n2.accepting_on.iter()
+            .map(|endpoint| match endpoint.clone() { Tcp(socket_addr) => socket_addr.port() })
+            .collect();
+        assert!(accepting_ports.contains(&port));
+    }
+
+#[test]
+    fn two_listeners_advertised() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let ports = (0..2).map(|_| TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port())
+                          .collect::<Vec<_>>();
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: stats.clone() },
+            RoutingConfig { ports_and_protocols: ports.iter().map(|port| crust::Port::Tcp(*port)).collect(),
+                            beacon_port: None, ..RoutingConfig::default() }).unwrap();
+        assert!(routing_node.accepting_endpoints().len() >= 2);
+
+        let peer_id : NameType = Random::generate_random();
+        let connect_request = decode::<ConnectRequest>(
+            &routing_node.construct_connect_request_msg(&peer_id).unwrap().serialised_body).unwrap();
+        let advertised_ports: Vec<u16> = connect_request.local_endpoints.iter()
+            .map(|endpoint| match endpoint.clone() { Tcp(socket_addr) => socket_addr.port() })
+            .collect();
+        for port in ports.iter() {
+            assert!(advertised_ports.contains(port));
+        }
+        assert_eq!(connect_request.local_endpoints, routing_node.accepting_endpoints().to_vec());
+    }
+
 #[test]
     fn call_put() {
         let data = "this is a known string".to_string().into_bytes();
         let chunk = Box::new(TestData::new(data));
-        let mut n1 = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]})) });
+        let mut n1 = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let name: NameType = Random::generate_random();
+        // with no one to send to the put fails
+        match n1.put(name, chunk, true) {
+            Err(RoutingError::NotSent) => (),
+            _ => panic!(),
+        }
+    }
+
+#[test]
+    fn call_post() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut n1 = RoutingNode::new(TestInterface { stats: stats.clone() });
         let name: NameType = Random::generate_random();
-        n1.put(name, chunk, true);
+        // the message is built and offered for sending, but there is no one to send it to
+        match n1.post(name, "this is a known string".to_string().into_bytes()) {
+            Err(RoutingError::NotSent) => (),
+            _ => panic!(),
+        }
+        assert_eq!(stats.lock().unwrap().sent, vec![MessageTypeTag::Post]);
     }
 
 #[test]
     fn call_unauthorised_put() {
         let data = "this is a known string".to_string().into_bytes();
         let chunk = Box::new(TestData::new(data));
-        let mut n1 = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]})) });
+        let mut n1 = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
         let name: NameType = Random::generate_random();
-        n1.unauthorised_put(name, chunk);
+        match n1.unauthorised_put(name, chunk) {
+            Err(RoutingError::NotSent) => (),
+            _ => panic!(),
+        }
     }
 
 #[test]
     fn call_handle_put() {
-        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]}));
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
         let put_data: PutData = Random::generate_random();
         assert_eq!(call_operation(put_data, MessageTypeTag::PutData, stats).call_count, 1u32);
     }
 
 #[test]
     fn call_handle_authorised_put() {
-        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]}));
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
         let unauthorised_put: PutData = Random::generate_random();
         let result_stats = call_operation(unauthorised_put, MessageTypeTag::UnauthorisedPut, stats);
         assert_eq!(result_stats.call_count, 1u32);
         assert_eq!(result_stats.data, "UnauthorisedPut".to_string().into_bytes());
     }
 
+#[test]
+    fn unauthorised_put_refused() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let unauthorised_put = PutData { name: Random::generate_random(), data: vec![] };
+        let result_stats = call_operation(unauthorised_put, MessageTypeTag::UnauthorisedPut, stats);
+        assert_eq!(result_stats.call_count, 0u32);
+        assert!(result_stats.sent.is_empty());
+    }
+
+#[test]
+    fn put_error_replied() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let put_data = PutData { name: Random::generate_random(), data: vec![] };
+        let result_stats = call_operation(put_data, MessageTypeTag::PutData, stats);
+        assert_eq!(result_stats.call_count, 1u32);
+        assert_eq!(result_stats.sent, vec![MessageTypeTag::PutDataResponse]);
+    }
+
 #[test]
     fn call_handle_put_response() {
-        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]}));
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
         let put_data_response: PutDataResponse = Random::generate_random();
         assert_eq!(call_operation(put_data_response, MessageTypeTag::PutDataResponse, stats).call_count, 1u32);
     }
 
 #[test]
     fn call_get() {
-        let mut n1 = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]})) });
+        let mut n1 = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
         let name: NameType = Random::generate_random();
-        n1.get(100u64, name);
+        // with no one to send to the get fails and isn't left pending
+        match n1.get(100u64, name) {
+            Err(RoutingError::NotSent) => (),
+            _ => panic!(),
+        }
+        assert!(n1.pending_gets.is_empty());
+    }
+
+    // registers a get as if it had been sent, since unit tests have no one to send it to
+    fn pending_get(routing_node: &mut RoutingNode<TestInterface>)
+            -> (MessageId, mpsc::Receiver<Result<Vec<u8>, ResponseError>>) {
+        let (result_sender, result_receiver) = mpsc::channel();
+        let message_id = routing_node.get_next_message_id().unwrap();
+        routing_node.pending_gets.insert(message_id, (result_sender, SteadyTime::now() + Duration::minutes(1)));
+        (message_id, result_receiver)
+    }
+
+#[test]
+    fn get_result_delivered() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut n1 = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let (message_id, result) = pending_get(&mut n1);
+        let sender = Pmid::new();
+        n1.public_pmid_cache.add(sender.get_name(), PublicPmid::new(&sender));
+
+        let header = MessageHeader {
+            message_id:  message_id,
+            destination: types::DestinationAddress { dest: n1.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: sender.get_name(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let get_data_response: GetDataResponse = Random::generate_random();
+        let message = RoutingMessage::new(MessageTypeTag::GetDataResponse, header.clone(),
+            get_data_response.clone(), &sender.get_crypto_secret_sign_key());
+        let _ = n1.message_received(&header.source.from_node, encode(&message).unwrap());
+
+        assert_eq!(result.try_recv(), Ok(get_data_response.data));
+        assert!(n1.pending_gets.is_empty());
+        assert_eq!(stats.lock().unwrap().call_count, 1u32);
+        assert_eq!(stats.lock().unwrap().data, encode(&Authority::NaeManager).unwrap());
+    }
+
+#[test]
+    fn get_result_dropped_with_node() {
+        let mut n1 = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let (_, result) = pending_get(&mut n1);
+        drop(n1);
+        assert!(result.recv().is_err());
     }
 
 #[test]
     fn call_handle_get_data() {
-        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]}));
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
         let get_data: GetData = Random::generate_random();
         assert_eq!(call_operation(get_data, MessageTypeTag::GetData, stats).call_count, 1u32);
     }
 
 #[test]
     fn call_handle_get_data_response() {
-        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]}));
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
         let get_data: GetDataResponse = Random::generate_random();
         assert_eq!(call_operation(get_data, MessageTypeTag::GetDataResponse, stats).call_count, 1u32);
     }
 
 #[test]
     fn call_handle_get_key() {
-        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]}));
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
         let get_key: GetKey = Random::generate_random();
         let public_key: types::PublicSignKey = Random::generate_random();
         let mut enc = Encoder::from_memory();
@@ -1414,42 +3817,95 @@ mod test {
 
 #[test]
     fn call_handle_post() {
-        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]}));
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
         let post: Post = Random::generate_random();
-        assert_eq!(call_operation(post, MessageTypeTag::Post, stats).call_count, 1u32);
+        let result_stats = call_operation(post, MessageTypeTag::Post, stats);
+        assert_eq!(result_stats.call_count, 1u32);
+        assert!(result_stats.sent.contains(&MessageTypeTag::PostResponse));
+    }
+
+    #[test]
+    fn failed_post_answered() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        // TestInterface refuses an empty post
+        let post = Post { name: Random::generate_random(), data: vec![] };
+        let result_stats = call_operation(post, MessageTypeTag::Post, stats);
+        assert_eq!(result_stats.call_count, 1u32);
+        assert!(result_stats.sent.contains(&MessageTypeTag::PostResponse));
     }
 
 #[test]
-    fn network() {
-        let network_size = 2usize;
-        let node = Arc::new(Mutex::new(RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]})) })));
-        let use_node = node.clone();
-        let mut runners = Vec::new();
-        runners.push(thread::spawn(move || loop {
-                let mut use_node = use_node.lock().unwrap();
-                use_node.run();
-                if use_node.routing_table.size() == network_size - 1 {
-                    break;
-                }
-            }));
-        let listening_endpoints = node.lock().unwrap().accepting_on.clone();
-        println!("network: {:?},    {:?}", &listening_endpoints, node.lock().unwrap().id());
-        for _ in 0..(network_size - 1) {
-            let mut node = Arc::new(Mutex::new(RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]})) })));
-            let use_node = node.clone();
-            runners.push(thread::spawn(move || loop {
+    fn call_handle_post_response() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let post_response: PostResponse = Random::generate_random();
+        let expected = match post_response.data {
+            Ok(ref data) => data.clone(),
+            Err(_) => vec![]
+        };
+        let result_stats = call_operation(post_response, MessageTypeTag::PostResponse, stats);
+        assert_eq!(result_stats.call_count, 1u32);
+        assert_eq!(result_stats.data, expected);
+    }
+
+#[test]
+    fn call_handle_delete() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let delete_data: DeleteData = Random::generate_random();
+        let result_stats = call_operation(delete_data.clone(), MessageTypeTag::DeleteData, stats);
+        assert_eq!(result_stats.call_count, 1u32);
+        assert_eq!(result_stats.data, delete_data.name.get_id().to_vec());
+        assert!(result_stats.sent.contains(&MessageTypeTag::DeleteDataResponse));
+    }
+
+    #[test]
+    fn failed_delete_answered() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        // TestInterface fails to delete the all-zero name
+        let delete_data = DeleteData { name: NameType::new([0u8; 64]) };
+        let result_stats = call_operation(delete_data, MessageTypeTag::DeleteData, stats);
+        assert_eq!(result_stats.call_count, 1u32);
+        assert!(result_stats.sent.contains(&MessageTypeTag::DeleteDataResponse));
+    }
+
+#[test]
+    fn call_handle_delete_response() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let delete_data_response: DeleteDataResponse = Random::generate_random();
+        let expected = match delete_data_response.result {
+            Ok(()) => "DeleteSucceeded".to_string().into_bytes(),
+            Err(_) => vec![]
+        };
+        let result_stats = call_operation(delete_data_response, MessageTypeTag::DeleteDataResponse, stats);
+        assert_eq!(result_stats.call_count, 1u32);
+        assert_eq!(result_stats.data, expected);
+    }
+
+#[test]
+    fn network() {
+        let network_size = 2usize;
+        let node = Arc::new(Mutex::new(RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) })));
+        let use_node = node.clone();
+        let mut runners = Vec::new();
+        runners.push(thread::spawn(move || loop {
+                let handled = {
                     let mut use_node = use_node.lock().unwrap();
-                    use_node.run();
+                    let handled = use_node.try_run();
                     if use_node.routing_table.size() == network_size - 1 {
                         break;
                     }
-                }));
-            let mut use_node2 = node.lock().unwrap();
-            match use_node2.bootstrap(Some(listening_endpoints.clone()), None) {
-                Ok(_) => { assert!(true) },
-                Err(_)  => { assert!(false); }
-            }
-            thread::sleep_ms(1000);
+                    handled
+                };
+                // don't hold the node while idle
+                if !handled {
+                    thread::sleep_ms(10);
+                }
+            }));
+        let listening_endpoints = node.lock().unwrap().accepting_on.clone();
+        println!("network: {:?},    {:?}", &listening_endpoints, node.lock().unwrap().id());
+        for _ in 0..(network_size - 1) {
+            let mut node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+            assert!(node.bootstrap_and_wait(Some(listening_endpoints.clone()), None,
+                                            Duration::seconds(10)).is_ok());
         }
 
         for runner in runners {
@@ -1460,7 +3916,7 @@ mod test {
     #[test]
     fn cache_public_pmid() {
         // copy from our_authority_full_routing_table test
-        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![]})) });
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
 
         let mut count : usize = 0;
         loop {
@@ -1498,7 +3954,8 @@ mod test {
                     from_node : Random::generate_random(),  // Bootstrap node or ourself
                     from_group : None,
                     reply_to : None },
-                authority : types::Authority::ManagedNode
+                authority : types::Authority::ManagedNode,
+                ttl : DEFAULT_TTL
             };
             let serialised_msg = encode(&put_public_pmid).unwrap();
             let result = routing_node.handle_put_public_pmid(put_public_pmid_header,
@@ -1531,6 +3988,2334 @@ mod test {
         assert_eq!(routing_node.public_pmid_cache.len(), total_inside as usize);
     }
 
+    #[test]
+    fn interface_vetoes_send() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()), random_endpoints(),
+                                                Some(random_endpoint()));
+        routing_node.all_connections.1.insert(peer.id(), peer.connected_endpoint.clone().unwrap());
+        routing_node.routing_table.add_node(peer.clone());
+        let serialised_message = vec![1u8, 2, 3];
+
+        routing_node.send_swarm_or_parallel(&peer.id(), &MessageTypeTag::AccountTransfer,
+                                            &serialised_message);
+        assert_eq!(routing_node.vetoed_sends(), 1);
+        routing_node.send_swarm_or_parallel(&peer.id(), &MessageTypeTag::GetData,
+                                            &serialised_message);
+        assert_eq!(routing_node.vetoed_sends(), 1);
+
+        // sends which bypass the routing table are vetoed too
+        routing_node.add_known_node(peer.id(), peer.connected_endpoint.clone().unwrap(), None);
+        let header = MessageHeader::new(routing_node.get_next_message_id().unwrap(),
+            types::DestinationAddress { dest: peer.id(), reply_to: None },
+            routing_node.our_source_address(), Authority::ManagedNode);
+        let post: Post = Random::generate_random();
+        let message = RoutingMessage::new(MessageTypeTag::AccountTransfer, header, post,
+                                          &routing_node.pmid.get_crypto_secret_sign_key());
+        match routing_node.send_direct(&peer.id(), &message) {
+            Err(RoutingError::NotSent) => (),
+            _ => panic!("expected NotSent"),
+        }
+        assert_eq!(routing_node.vetoed_sends(), 2);
+        match routing_node.send_to_bootstrap_node(&message) {
+            Err(RoutingError::NotSent) => (),
+            _ => panic!("expected NotSent"),
+        }
+        assert_eq!(routing_node.vetoed_sends(), 3);
+    }
+
+    #[test]
+    fn multiple_accepting_endpoints() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let accepting_on = vec![random_endpoint(), random_endpoint()];
+        routing_node.accepting_on = accepting_on.clone();
+        let peer_id : NameType = Random::generate_random();
+        let connect_request = decode::<ConnectRequest>(
+            &routing_node.construct_connect_request_msg(&peer_id).unwrap().serialised_body).unwrap();
+        for endpoint in accepting_on.iter() {
+            assert!(connect_request.local_endpoints.contains(endpoint));
+        }
+
+        // a peer advertising two endpoints is attributed whichever one it connects on
+        for index in 0..2 {
+            let peer_endpoints = vec![random_endpoint(), random_endpoint()];
+            let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                    peer_endpoints.clone(), None);
+            routing_node.routing_table.add_node(peer.clone());
+            routing_node.handle_connect(peer_endpoints[index].clone());
+            assert_eq!(routing_node.all_connections.0.get(&peer_endpoints[index]), Some(&peer.id()));
+            assert_eq!(routing_node.all_connections.1.get(&peer.id()), Some(&peer_endpoints[index]));
+        }
+    }
+
+    #[test]
+    fn oversized_get_reply_refused() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        // TestInterface replies to handle_get with "handle_get called"
+        routing_node.set_max_reply_size(4);
+        assert_eq!(routing_node.check_reply_size(vec![0u8; 5]), Err(ResponseError::TooLarge));
+        assert_eq!(routing_node.check_reply_size(vec![0u8; 4]), Ok(vec![0u8; 4]));
+
+        let get_data: GetData = Random::generate_random();
+        let header = MessageHeader::new(routing_node.get_next_message_id().unwrap(),
+            types::DestinationAddress { dest: routing_node.id(), reply_to: None },
+            types::SourceAddress { from_node: Random::generate_random(), from_group: None, reply_to: None },
+            Authority::Client);
+        assert!(routing_node.handle_get_data(header, encode(&get_data).unwrap()).is_ok());
+        let stats_value = stats.lock().unwrap();
+        assert_eq!(stats_value.call_count, 1u32);
+        // only the error response goes out
+        assert_eq!(stats_value.sent, vec![MessageTypeTag::GetDataResponse]);
+    }
+
+    #[test]
+    fn inbound_bandwidth_throttled() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                vec![random_endpoint()], None);
+        let peer_endpoint = peer.endpoints[0].clone();
+        routing_node.routing_table.add_node(peer.clone());
+        routing_node.handle_connect(peer_endpoint.clone());
+
+        // within a single long window the peer is throttled once over budget
+        routing_node.set_inbound_byte_limit(100, Duration::minutes(1));
+        assert!(routing_node.check_inbound_bandwidth(&peer_endpoint, &peer.id(), 60));
+        assert!(!routing_node.check_inbound_bandwidth(&peer_endpoint, &peer.id(), 60));
+        assert!(!routing_node.check_inbound_bandwidth(&peer_endpoint, &peer.id(), 1));
+        {
+            let usage = routing_node.inbound_bandwidth().get(&peer.id()).unwrap();
+            assert_eq!(usage.total_bytes, 121);
+            assert_eq!(usage.dropped_messages, 2);
+        }
+        assert!(routing_node.all_connections.1.contains_key(&peer.id()));
+
+        // a peer over budget in consecutive windows is disconnected
+        routing_node.set_inbound_byte_limit(100, Duration::zero());
+        for _ in 0..2 {
+            assert!(!routing_node.check_inbound_bandwidth(&peer_endpoint, &peer.id(), 200));
+            assert!(routing_node.all_connections.1.contains_key(&peer.id()));
+        }
+        assert!(!routing_node.check_inbound_bandwidth(&peer_endpoint, &peer.id(), 200));
+        assert!(!routing_node.all_connections.1.contains_key(&peer.id()));
+        assert!(routing_node.inbound_bandwidth().get(&peer.id()).is_none());
+    }
+
+    #[test]
+    fn retry_after_send_failure() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                vec![random_endpoint()], None);
+        let peer_endpoint = peer.endpoints[0].clone();
+        routing_node.routing_table.add_node(peer.clone());
+        routing_node.handle_connect(peer_endpoint.clone());
+
+        // nothing is listening at the peer's endpoint so the send fails and a reconnect starts
+        let message = vec![1u8, 2, 3];
+        routing_node.send_swarm_or_parallel(&peer.id(), &MessageTypeTag::PutData, &message);
+        assert_eq!(routing_node.failed_sends(), 1);
+        assert_eq!(routing_node.pending_retries.get(&peer.id()).unwrap().1, vec![message.clone()]);
+        assert!(!routing_node.all_connections.1.contains_key(&peer.id()));
+
+        // once reconnected the held message is retried exactly once
+        routing_node.handle_connect(peer_endpoint.clone());
+        assert!(routing_node.pending_retries.is_empty());
+        assert_eq!(routing_node.all_connections.1.get(&peer.id()), Some(&peer_endpoint));
+        assert_eq!(routing_node.failed_sends(), 2);
+    }
+
+    #[test]
+    fn flush_pending_retries() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        assert!(routing_node.flush(Duration::milliseconds(100)));
+
+        // a held message for a peer which never reconnects can't be flushed
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                vec![random_endpoint()], None);
+        let peer_endpoint = peer.endpoints[0].clone();
+        routing_node.routing_table.add_node(peer.clone());
+        routing_node.handle_connect(peer_endpoint.clone());
+        routing_node.send_swarm_or_parallel(&peer.id(), &MessageTypeTag::PutData, &vec![1u8, 2, 3]);
+        assert!(!routing_node.flush(Duration::milliseconds(100)));
+
+        // once the peer reconnects the held message is resent and flush succeeds
+        routing_node.handle_connect(peer_endpoint);
+        assert!(routing_node.flush(Duration::milliseconds(100)));
+    }
+
+    #[test]
+    fn unredialled_retries_dropped() {
+        let (event_sender, events) = mpsc::channel();
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { events: Some(event_sender), ..RoutingConfig::default() }).unwrap();
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                vec![random_endpoint()], None);
+        routing_node.routing_table.add_node(peer.clone());
+        routing_node.handle_connect(peer.endpoints[0].clone());
+        routing_node.send_swarm_or_parallel(&peer.id(), &MessageTypeTag::PutData, &vec![1u8, 2, 3]);
+        assert_eq!(routing_node.pending_retries.len(), 1);
+        assert!(routing_node.routing_table.all_nodes()[0].connected_endpoint.is_none());
+        assert!(routing_node.get_connected_target(&peer.id()).is_empty());
+        while let Ok(_) = events.try_recv() {}
+
+        // the peer isn't back by the deadline, so flush gives up at once rather than timing out
+        routing_node.pending_retries.get_mut(&peer.id()).unwrap().0 =
+            SteadyTime::now() - Duration::seconds(1);
+        assert!(routing_node.flush(Duration::seconds(10)));
+        assert!(routing_node.pending_retries.is_empty());
+        let mut dropped = 0;
+        while let Ok(event) = events.try_recv() {
+            if let RoutingEvent::MessageDropped(RoutingError::NotConnected(id)) = event {
+                assert_eq!(id, peer.id());
+                dropped += 1;
+            }
+        }
+        assert_eq!(dropped, 1);
+    }
+
+    #[test]
+    fn refreshes_coalesced() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        routing_node.set_refresh_window(Duration::minutes(1));
+        let name: NameType = Random::generate_random();
+        for index in 0..5u8 {
+            assert!(routing_node.refresh(Box::new(NamedTestData { name: name.clone(), data: vec![index] })).is_ok());
+        }
+        routing_node.run();
+        assert!(stats.lock().unwrap().sent.is_empty());
+        assert_eq!(routing_node.pending_refreshes.len(), 1);
+        assert_eq!(routing_node.pending_refreshes.get(&name).unwrap().2.serialised_contents(),
+                   vec![4u8]);
+
+        // closing the window sends only the latest refresh
+        routing_node.set_refresh_window(Duration::zero());
+        routing_node.run();
+        assert!(routing_node.pending_refreshes.is_empty());
+        assert_eq!(stats.lock().unwrap().sent, vec![MessageTypeTag::PutData]);
+    }
+
+    #[test]
+    fn stop() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                vec![random_endpoint()], None);
+        routing_node.routing_table.add_node(peer.clone());
+        routing_node.handle_connect(peer.endpoints[0].clone());
+        let (_, result) = pending_get(&mut routing_node);
+
+        routing_node.stop();
+        assert!(routing_node.all_connections.0.is_empty());
+        assert!(routing_node.all_connections.1.is_empty());
+        assert!(result.recv().is_err());
+
+        // further events are ignored
+        assert!(routing_node.refresh(Box::new(TestData::new(vec![1u8]))).is_ok());
+        routing_node.set_refresh_window(Duration::zero());
+        routing_node.run();
+        assert_eq!(routing_node.pending_refreshes.len(), 1);
+        routing_node.stop();
+    }
+
+    #[test]
+    fn churn_on_close_group_change() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let mut expected: Vec<NameType> = vec![];
+        for _ in 0..3 {
+            let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                    vec![random_endpoint()], None);
+            assert!(routing_node.add_node(peer.clone()).0);
+            expected.push(peer.id());
+            expected.sort();
+            assert_eq!(stats.lock().unwrap().churn.last(), Some(&expected));
+        }
+        assert_eq!(stats.lock().unwrap().churn.len(), 3);
+
+        let dropped = expected.remove(1);
+        routing_node.drop_node(&dropped);
+        assert_eq!(stats.lock().unwrap().churn.last(), Some(&expected));
+
+        // dropping an unknown node leaves the close group, and so the interface, alone
+        routing_node.drop_node(&Random::generate_random());
+        assert_eq!(stats.lock().unwrap().churn.len(), 4);
+    }
+
+    fn add_group_members(routing_node: &mut RoutingNode<TestInterface>, count: usize)
+            -> Vec<NameType> {
+        (0..count).map(|_| {
+            let member = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                      vec![random_endpoint()], None);
+            assert!(routing_node.routing_table.add_node(member.clone()).0);
+            member.id()
+        }).collect()
+    }
+
+    fn refresh_from_group(routing_node: &mut RoutingNode<TestInterface>, sender: &NameType,
+                          name: &NameType, data: Vec<u8>) -> RecvResult {
+        let header = MessageHeader {
+            message_id:  random::<u32>(),
+            destination: types::DestinationAddress { dest: Random::generate_random(), reply_to: None },
+            source:      types::SourceAddress { from_node: sender.clone(),
+                                                from_group: Some(Random::generate_random()),
+                                                reply_to: None },
+            authority:   Authority::NodeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let put_data = PutData { name: name.clone(), data: data };
+        routing_node.handle_refresh(header, encode(&put_data).unwrap())
+    }
+
+    #[test]
+    fn refresh_merged_at_quorum() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let quorum = routing_table::RoutingTable::get_quorum_size();
+        let members = add_group_members(&mut routing_node, quorum);
+        let name: NameType = Random::generate_random();
+        for member in &members[..quorum - 1] {
+            assert!(refresh_from_group(&mut routing_node, member, &name, vec![1u8, 2, 3]).is_ok());
+        }
+        assert_eq!(stats.lock().unwrap().call_count, 0);
+        assert!(refresh_from_group(&mut routing_node, &members[quorum - 1], &name,
+                                   vec![1u8, 2, 3]).is_ok());
+        assert_eq!(stats.lock().unwrap().call_count, 1);
+        assert!(routing_node.accumulating_refresh.is_empty());
+
+        // conflicting copies can't be merged and are dropped
+        assert!(refresh_from_group(&mut routing_node, &members[0], &name, vec![4u8]).is_ok());
+        for member in &members[1..] {
+            assert!(refresh_from_group(&mut routing_node, member, &name, vec![1u8, 2, 3]).is_ok());
+        }
+        assert_eq!(stats.lock().unwrap().call_count, 1);
+        assert!(routing_node.accumulating_refresh.is_empty());
+    }
+
+    #[test]
+    fn repeated_refresh_from_one_member_not_merged() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let quorum = routing_table::RoutingTable::get_quorum_size();
+        let member = add_group_members(&mut routing_node, 1).remove(0);
+        let name: NameType = Random::generate_random();
+        for _ in 0..quorum {
+            assert!(refresh_from_group(&mut routing_node, &member, &name, vec![1u8, 2, 3]).is_ok());
+        }
+        assert_eq!(stats.lock().unwrap().call_count, 0);
+        assert_eq!(routing_node.accumulating_refresh.get(&name).unwrap().1.len(), 1);
+
+        // and nobody outside our close group may refresh at all
+        match refresh_from_group(&mut routing_node, &Random::generate_random(), &name,
+                                 vec![1u8, 2, 3]) {
+            Err(RoutingError::BadAuthority) => (),
+            _ => panic!("expected BadAuthority")
+        }
+        assert_eq!(routing_node.accumulating_refresh.get(&name).unwrap().1.len(), 1);
+    }
+
+    #[test]
+    fn refresh_from_group_members_merged() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let quorum = routing_table::RoutingTable::get_quorum_size();
+        let name: NameType = Random::generate_random();
+        for count in 0..quorum {
+            let member_stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+            let mut member = RoutingNode::new(TestInterface { stats: member_stats.clone() });
+            member.set_refresh_window(Duration::zero());
+            let _ = member.refresh(Box::new(NamedTestData { name: name.clone(), data: vec![1u8, 2, 3] }));
+            assert_eq!(member_stats.lock().unwrap().sent, vec![MessageTypeTag::Refresh]);
+
+            let message_id = member.get_next_message_id().unwrap();
+            let message = member.construct_refresh_msg(message_id,
+                Box::new(NamedTestData { name: name.clone(), data: vec![1u8, 2, 3] }));
+            routing_node.public_pmid_cache.add(member.own_id.clone(), PublicPmid::new(&member.pmid));
+            assert!(routing_node.routing_table.add_node(routing_table::NodeInfo::new(
+                PublicPmid::new(&member.pmid), vec![random_endpoint()], None)).0);
+            assert!(routing_node.message_received(&member.own_id, encode(&message).unwrap()).is_ok());
+            let expected = if count + 1 < quorum { 0 } else { 1 };
+            assert_eq!(stats.lock().unwrap().call_count, expected);
+        }
+        assert!(routing_node.accumulating_refresh.is_empty());
+
+        // a plain put to the group's managers isn't held for a quorum
+        let header = MessageHeader {
+            message_id:  random::<u32>(),
+            destination: types::DestinationAddress { dest: Random::generate_random(), reply_to: None },
+            source:      types::SourceAddress { from_node: Random::generate_random(),
+                                                from_group: Some(Random::generate_random()),
+                                                reply_to: None },
+            authority:   Authority::NodeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let put_data = PutData { name: name.clone(), data: vec![1u8, 2, 3] };
+        assert_eq!(routing_node.our_authority(&put_data.name, &header), Authority::NodeManager);
+        let _ = routing_node.handle_put_data(header, encode(&put_data).unwrap());
+        assert_eq!(stats.lock().unwrap().call_count, 2);
+        assert!(routing_node.accumulating_refresh.is_empty());
+    }
+
+    #[test]
+    fn stale_refresh_copies_expire() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let member = add_group_members(&mut routing_node, 1).remove(0);
+        let name: NameType = Random::generate_random();
+        assert!(refresh_from_group(&mut routing_node, &member, &name, vec![1u8, 2, 3]).is_ok());
+        assert_eq!(routing_node.accumulating_refresh.len(), 1);
+        routing_node.expire_accumulating_refreshes();
+        assert_eq!(routing_node.accumulating_refresh.len(), 1);
+
+        routing_node.accumulating_refresh.get_mut(&name).unwrap().0 =
+            SteadyTime::now() - Duration::seconds(REFRESH_ACCUMULATION_SECS + 1);
+        routing_node.expire_accumulating_refreshes();
+        assert!(routing_node.accumulating_refresh.is_empty());
+        assert_eq!(stats.lock().unwrap().call_count, 0);
+    }
+
+    #[test]
+    fn config_combines() {
+        let (event_sender, events) = mpsc::channel();
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { group_size: 4, serialisation: Serialisation::Bincode,
+                            events: Some(event_sender), ..RoutingConfig::default() }).unwrap();
+        assert_eq!(routing_node.routing_table_stats().1, 4);
+        assert_eq!(routing_node.serialisation, Serialisation::Bincode);
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                vec![random_endpoint()], None);
+        assert!(routing_node.add_node(peer).0);
+        match events.try_recv() { Ok(RoutingEvent::ChurnStarted) => (), _ => panic!() }
+    }
+
+    #[test]
+    fn with_group_size() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::with_group_size(TestInterface { stats: stats.clone() }, 4).unwrap();
+
+        // the refresh quorum follows the group size
+        let members = add_group_members(&mut routing_node, 3);
+        let name: NameType = Random::generate_random();
+        for member in &members[..2] {
+            assert!(refresh_from_group(&mut routing_node, member, &name, vec![1u8, 2, 3]).is_ok());
+        }
+        assert_eq!(stats.lock().unwrap().call_count, 0);
+        assert!(refresh_from_group(&mut routing_node, &members[2], &name, vec![1u8, 2, 3]).is_ok());
+        assert_eq!(stats.lock().unwrap().call_count, 1);
+
+        for _ in 0..10 {
+            routing_node.routing_table.add_node(routing_table::NodeInfo::new(
+                PublicPmid::new(&Pmid::new()), random_endpoints(), None));
+        }
+        assert_eq!(routing_node.close_group().len(), 4);
+
+        // a group needs at least one member
+        match RoutingNode::with_group_size(TestInterface { stats: stats.clone() }, 0) {
+            Err(RoutingError::InvalidGroupSize) => (),
+            _ => panic!("expected InvalidGroupSize")
+        }
+    }
+
+    #[test]
+    fn put_key_then_get_key() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let put_key: PutKey = Random::generate_random();
+        let header = MessageHeader {
+            message_id:  random::<u32>(),
+            destination: types::DestinationAddress { dest: put_key.target_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: Random::generate_random(), from_group: None, reply_to: None },
+            authority:   Authority::ManagedNode,
+            ttl:         DEFAULT_TTL
+        };
+        assert!(routing_node.handle_put_key(header.clone(), encode(&put_key).unwrap()).is_ok());
+
+        // not our group's key to hold
+        let mut other_header = header.clone();
+        other_header.destination.dest = Random::generate_random();
+        assert!(routing_node.handle_put_key(other_header, encode(&put_key).unwrap()).is_err());
+
+        let get_key = GetKey { requester_id: Random::generate_random(),
+                               target_id: put_key.target_id.clone() };
+        assert!(routing_node.handle_get_key(header, encode(&get_key).unwrap()).is_ok());
+        assert_eq!(stats.lock().unwrap().call_count, 0);
+        assert_eq!(stats.lock().unwrap().sent, vec![MessageTypeTag::GetKeyResponse]);
+    }
+
+    #[test]
+    fn get_key_not_found() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let get_key: GetKey = Random::generate_random();
+        let header = MessageHeader {
+            message_id:  random::<u32>(),
+            destination: types::DestinationAddress { dest: get_key.target_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: Random::generate_random(), from_group: None, reply_to: None },
+            authority:   Authority::ManagedNode,
+            ttl:         DEFAULT_TTL
+        };
+        // the interface holds no key, and the requester is told so
+        assert!(routing_node.handle_get_key(header, encode(&get_key).unwrap()).is_ok());
+        assert_eq!(stats.lock().unwrap().call_count, 1);
+        assert_eq!(stats.lock().unwrap().sent, vec![MessageTypeTag::GetKeyResponse]);
+    }
+
+    #[test]
+    fn bootstrap_retries() {
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { beacon_port: None, ..RoutingConfig::default() }).unwrap();
+        // nothing is listening on a port we've just released
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let bootstrap_list = Some(vec![Tcp(address)]);
+        let started = SteadyTime::now();
+        assert!(routing_node.bootstrap_with_retry(bootstrap_list.clone(), None, 3,
+                                                  Duration::milliseconds(50)).is_err());
+        assert!(SteadyTime::now() - started >= Duration::milliseconds(100));
+        assert!(routing_node.bootstrap(bootstrap_list, None).is_err());
+        assert!(routing_node.bootstrap_endpoint.is_none());
+    }
+
+    #[test]
+    fn bootstrap_and_wait_fails_without_peers() {
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { beacon_port: None, ..RoutingConfig::default() }).unwrap();
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        match routing_node.bootstrap_and_wait(Some(vec![Tcp(address)]), None,
+                                              Duration::milliseconds(100)) {
+            Err(RoutingError::FailedToBootstrap) => (),
+            _ => panic!("expected FailedToBootstrap")
+        }
+        assert_eq!(routing_node.routing_table_size(), 0);
+    }
+
+    #[test]
+    fn external_endpoints_advertised() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let local_endpoints = vec![random_endpoint()];
+        let external_endpoints = vec![random_endpoint(), random_endpoint()];
+        routing_node.accepting_on = local_endpoints.clone();
+        routing_node.set_external_endpoints(external_endpoints.clone());
+
+        let peer_id : NameType = Random::generate_random();
+        let request_message = routing_node.construct_connect_request_msg(&peer_id).unwrap();
+        let connect_request = decode::<ConnectRequest>(&request_message.serialised_body).unwrap();
+        assert_eq!(connect_request.local_endpoints, local_endpoints);
+        assert_eq!(connect_request.external_endpoints, external_endpoints);
+
+        let mut request_to_us = connect_request.clone();
+        request_to_us.receiver_id = routing_node.own_id.clone();
+        let response_message = routing_node.construct_connect_response_msg(
+            &request_message.message_header, &request_to_us).unwrap();
+        let connect_response =
+            decode::<ConnectResponse>(&response_message.serialised_body).unwrap();
+        assert_eq!(connect_response.requester_external_endpoints, external_endpoints);
+        assert_eq!(connect_response.receiver_local_endpoints, local_endpoints);
+        assert_eq!(connect_response.receiver_external_endpoints, external_endpoints);
+    }
+
+    #[test]
+    fn drop_peer_sending_garbage() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let peer_pmid = Pmid::new();
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&peer_pmid),
+                                                vec![random_endpoint()], None);
+        let peer_endpoint = peer.endpoints[0].clone();
+        routing_node.routing_table.add_node(peer.clone());
+        routing_node.handle_connect(peer_endpoint.clone());
+        routing_node.set_decode_failure_policy(3, Duration::minutes(1));
+
+        // foreign traffic counts as much as a body which doesn't decode
+        routing_node.handle_new_message(peer_endpoint.clone(), vec![0xffu8; 10]);
+        routing_node.handle_new_message(peer_endpoint.clone(), compression::pack(&[0xffu8; 10], None));
+        assert_eq!(routing_node.decode_failures.get(&peer.id()).unwrap().0, 2);
+
+        let header = MessageHeader {
+            message_id:  random::<u32>(),
+            destination: types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: peer.id(), from_group: None, reply_to: None },
+            authority:   Authority::ManagedNode,
+            ttl:         DEFAULT_TTL
+        };
+        let post: Post = Random::generate_random();
+
+        // a message which decodes but doesn't verify doesn't reset the count
+        let forged = RoutingMessage::new(MessageTypeTag::Post, header.clone(), post.clone(),
+                                         &routing_node.pmid.get_crypto_secret_sign_key());
+        routing_node.handle_new_message(peer_endpoint.clone(), compression::pack(&encode(&forged).unwrap(), None));
+        assert_eq!(routing_node.decode_failures.get(&peer.id()).unwrap().0, 2);
+
+        // a message which decodes and verifies does
+        let message = RoutingMessage::new(MessageTypeTag::Post, header, post,
+                                          &peer_pmid.get_crypto_secret_sign_key());
+        let packed = compression::pack(&encode(&message).unwrap(), None);
+        routing_node.handle_new_message(peer_endpoint.clone(), packed.clone());
+        assert!(routing_node.decode_failures.get(&peer.id()).is_none());
+
+        // but a repeat of it, caught by the filter, doesn't
+        routing_node.handle_new_message(peer_endpoint.clone(), compression::pack(&[0xffu8; 10], None));
+        routing_node.handle_new_message(peer_endpoint.clone(), packed);
+        assert_eq!(routing_node.decode_failures.get(&peer.id()).unwrap().0, 1);
+        routing_node.decode_failures.clear();
+
+        for _ in 0..3 {
+            assert!(routing_node.all_connections.1.contains_key(&peer.id()));
+            routing_node.handle_new_message(peer_endpoint.clone(), compression::pack(&[0xffu8; 10], None));
+        }
+        assert!(!routing_node.all_connections.1.contains_key(&peer.id()));
+        assert_eq!(routing_node.routing_table.size(), 0);
+        assert!(routing_node.decode_failures.is_empty());
+    }
+
+    #[test]
+    fn message_id_wraps_around() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        routing_node.next_message_id = ::std::u32::MAX - 1;
+        let (sender, _receiver) = ::std::sync::mpsc::channel();
+        routing_node.pending_gets.insert(::std::u32::MAX, (sender, SteadyTime::now()));
+        let own_id = routing_node.own_id.clone();
+        routing_node.filter.add(((own_id, 0), 0));
+
+        assert_eq!(routing_node.get_next_message_id().unwrap(), ::std::u32::MAX - 1);
+        assert_eq!(routing_node.get_next_message_id().unwrap(), 1);
+        assert_eq!(routing_node.get_next_message_id().unwrap(), 2);
+    }
+
+    #[test]
+    fn client_message_id_skips_ids_filtered_under_bootstrap_name() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let bootstrap_endpoint = random_endpoint();
+        let bootstrap_id: NameType = Random::generate_random();
+        routing_node.bootstrap_endpoint = Some(bootstrap_endpoint.clone());
+        routing_node.all_connections.0.insert(bootstrap_endpoint, bootstrap_id.clone());
+        routing_node.next_message_id = 7;
+        routing_node.filter.add(((bootstrap_id, 7), 0));
+
+        assert_eq!(routing_node.get_next_message_id().unwrap(), 8);
+    }
+
+    #[test]
+    fn message_id_search_bounded() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        routing_node.next_message_id = 0;
+        let (sender, _receiver) = ::std::sync::mpsc::channel();
+        for message_id in 0..MAX_MESSAGE_ID_ATTEMPTS {
+            routing_node.pending_gets.insert(message_id, (sender.clone(), SteadyTime::now()));
+        }
+
+        match routing_node.get_next_message_id() {
+            Err(RoutingError::NoFreeMessageId) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert_eq!(routing_node.get_next_message_id().unwrap(), MAX_MESSAGE_ID_ATTEMPTS);
+    }
+
+    #[test]
+    fn routing_table_accessors() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        assert_eq!(routing_node.routing_table_size(), 0);
+        assert!(routing_node.close_group().is_empty());
+        for _ in 0..3 {
+            routing_node.routing_table.add_node(routing_table::NodeInfo::new(
+                PublicPmid::new(&Pmid::new()), random_endpoints(), None));
+        }
+        assert_eq!(routing_node.routing_table_size(), 3);
+        let close_group: Vec<NameType> = routing_node.routing_table.our_close_group().iter()
+                                                     .map(|node| node.id()).collect();
+        assert_eq!(routing_node.close_group(), close_group);
+    }
+
+    #[test]
+    fn events_reported() {
+        let (event_sender, events) = ::std::sync::mpsc::channel();
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { events: Some(event_sender), ..RoutingConfig::default() }).unwrap();
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                vec![random_endpoint()], None);
+        let peer_endpoint = peer.endpoints[0].clone();
+        assert!(routing_node.add_node(peer.clone()).0);
+        match events.try_recv() { Ok(RoutingEvent::ChurnStarted) => (), _ => panic!() }
+
+        routing_node.handle_connect(peer_endpoint.clone());
+        match events.try_recv() {
+            Ok(RoutingEvent::Connected(peer_id)) => assert_eq!(peer_id, peer.id()),
+            _ => panic!()
+        }
+
+        routing_node.handle_new_message(peer_endpoint.clone(), compression::pack(&[0xffu8; 10], None));
+        match events.try_recv() {
+            Ok(RoutingEvent::MessageDropped(RoutingError::CborError(_))) => (),
+            _ => panic!()
+        }
+
+        routing_node.handle_lost_connection(peer_endpoint);
+        match events.try_recv() { Ok(RoutingEvent::ChurnStarted) => (), _ => panic!() }
+        match events.try_recv() {
+            Ok(RoutingEvent::Disconnected(peer_id)) => assert_eq!(peer_id, peer.id()),
+            _ => panic!()
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn not_bootstrapped() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        match routing_node.put_own_public_pmid() {
+            Err(RoutingError::NotBootstrapped) => (),
+            _ => panic!("expected NotBootstrapped")
+        }
+        // an early connect request must not panic
+        let peer_id: NameType = Random::generate_random();
+        routing_node.check_and_send_connect_request_msg(&peer_id);
+    }
+
+    #[test]
+    fn client_connections_kept_apart() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let client_id: NameType = Random::generate_random();
+        let client_endpoint = random_endpoint();
+        let header = MessageHeader::new(random::<u32>(),
+            types::DestinationAddress { dest: NameType::new([0u8; NAME_TYPE_LEN]), reply_to: None },
+            types::SourceAddress { from_node: client_id.clone(), from_group: None, reply_to: None },
+            Authority::Client);
+        let request = RoutingMessage::new(MessageTypeTag::BootstrapIdRequest, header,
+            BootstrapIdRequest { sender_id: client_id.clone(), protocol_version: types::PROTOCOL_VERSION },
+            &routing_node.pmid.get_crypto_secret_sign_key());
+        routing_node.handle_new_message(client_endpoint.clone(), compression::pack(&encode(&request).unwrap(), None));
+        assert_eq!(routing_node.non_routing_connections.get(&client_id), Some(&client_endpoint));
+        assert!(routing_node.all_connections.0.is_empty());
+        assert!(routing_node.bootstrap_node_id.is_none());
+
+        // later messages from the client are handled, not treated as bootstrap messages
+        let header = MessageHeader {
+            message_id:  random::<u32>(),
+            destination: types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: client_id.clone(), from_group: None, reply_to: None },
+            authority:   Authority::Client,
+            ttl:         DEFAULT_TTL
+        };
+        let post: Post = Random::generate_random();
+        let message = RoutingMessage::new(MessageTypeTag::Post, header, post,
+                                          &routing_node.pmid.get_crypto_secret_sign_key());
+        routing_node.handle_new_message(client_endpoint.clone(), compression::pack(&encode(&message).unwrap(), None));
+        assert_eq!(stats.lock().unwrap().call_count, 1);
+
+        routing_node.handle_lost_connection(client_endpoint);
+        assert!(routing_node.non_routing_connections.is_empty());
+    }
+
+#[test]
+    fn signature_check() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut n1 = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let sender = Pmid::new();
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&sender), random_endpoints(), None);
+        assert!(n1.routing_table.add_node(peer).0);
+
+        let header = MessageHeader {
+            message_id:  n1.get_next_message_id().unwrap(),
+            destination: types::DestinationAddress { dest: n1.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: sender.get_name(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let post: Post = Random::generate_random();
+        let mut message = RoutingMessage::new(MessageTypeTag::Post, header.clone(), post,
+                                              &sender.get_crypto_secret_sign_key());
+        let tampered: Post = Random::generate_random();
+        message.set_message_body(tampered);
+        match n1.message_received(&sender.get_name(), encode(&message).unwrap()) {
+            Err(RoutingError::SignatureCheckFailed) => (),
+            _ => panic!("expected SignatureCheckFailed")
+        }
+        assert_eq!(stats.lock().unwrap().call_count, 0u32);
+    }
+
+#[test]
+    fn unverified_messages_held_until_key_known() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut n1 = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let sender = Pmid::new();
+        let header = MessageHeader {
+            message_id:  n1.get_next_message_id().unwrap(),
+            destination: types::DestinationAddress { dest: n1.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: sender.get_name(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let post: Post = Random::generate_random();
+        let message = RoutingMessage::new(MessageTypeTag::Post, header, post,
+                                          &sender.get_crypto_secret_sign_key());
+        assert!(n1.message_received(&sender.get_name(), encode(&message).unwrap()).is_ok());
+        assert_eq!(stats.lock().unwrap().call_count, 0u32);
+        assert_eq!(n1.unverified_messages.get(&sender.get_name()).unwrap().len(), 1);
+
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&sender), random_endpoints(), None);
+        assert!(n1.add_node(peer).0);
+        assert!(n1.unverified_messages.is_empty());
+        assert_eq!(stats.lock().unwrap().call_count, 1u32);
+    }
+
+    #[test]
+    fn replayed_messages_charged_once() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut n1 = RoutingNode::new(TestInterface { stats: stats.clone() });
+        // one message's worth of rate, which the replay mustn't take again
+        n1.set_message_rate_limit(0, 1);
+        let sender = Pmid::new();
+        let header = MessageHeader {
+            message_id:  n1.get_next_message_id().unwrap(),
+            destination: types::DestinationAddress { dest: n1.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: sender.get_name(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let post: Post = Random::generate_random();
+        let message = RoutingMessage::new(MessageTypeTag::Post, header, post,
+                                          &sender.get_crypto_secret_sign_key());
+        assert!(n1.message_received(&sender.get_name(), encode(&message).unwrap()).is_ok());
+
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&sender), random_endpoints(), None);
+        assert!(n1.add_node(peer).0);
+        assert_eq!(stats.lock().unwrap().call_count, 1u32);
+        assert_eq!(n1.message_stats().received(&MessageTypeTag::Post), 1);
+        assert_eq!(n1.rate_limited_messages, 0);
+    }
+
+    #[test]
+    fn unknown_sender_exemptions() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut n1 = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()), vec![random_endpoint()], None);
+        assert!(n1.add_node(peer.clone()).0);
+        n1.handle_connect(peer.endpoints[0].clone());
+        let sender = Pmid::new();
+        let mut far = n1.own_id.get_id();
+        for byte in far.iter_mut() {
+            *byte = !*byte;
+        }
+        let header = |message_id: MessageId, dest: NameType| MessageHeader {
+            message_id:  message_id,
+            destination: types::DestinationAddress { dest: dest, reply_to: None },
+            source:      types::SourceAddress { from_node: sender.get_name(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+
+        // a message only passed on is held rather than sent on unchecked
+        let post: Post = Random::generate_random();
+        let message = RoutingMessage::new(MessageTypeTag::Post, header(1, NameType::new(far)), post,
+                                          &sender.get_crypto_secret_sign_key());
+        assert!(n1.message_received(&peer.id(), encode(&message).unwrap()).is_ok());
+        assert!(stats.lock().unwrap().sent.is_empty());
+
+        // as is a find group, which is no longer exempt
+        let find_group = FindGroup { requester_id: sender.get_name(), target_id: sender.get_name() };
+        let message = RoutingMessage::new(MessageTypeTag::FindGroup, header(2, n1.own_id.clone()),
+                                          find_group, &sender.get_crypto_secret_sign_key());
+        assert!(n1.message_received(&peer.id(), encode(&message).unwrap()).is_ok());
+        assert!(stats.lock().unwrap().sent.is_empty());
+        assert_eq!(n1.unverified_messages.get(&sender.get_name()).unwrap().len(), 2);
+
+        // and both are checked and handled once the sender's key is known
+        n1.public_pmid_cache.add(sender.get_name(), PublicPmid::new(&sender));
+        n1.replay_unverified_messages(&sender.get_name());
+        assert!(n1.unverified_messages.is_empty());
+        assert!(!stats.lock().unwrap().sent.is_empty());
+    }
+
+    #[test]
+    fn unverified_messages_expire() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let (event_output, event_input) = mpsc::channel();
+        let mut n1 = RoutingNode::with_config(TestInterface { stats: stats.clone() },
+            RoutingConfig { events: Some(event_output), ..RoutingConfig::default() }).unwrap();
+        let sender = Pmid::new();
+        for _ in 0..(MAX_UNVERIFIED_MESSAGES + 1) {
+            let header = MessageHeader {
+                message_id:  n1.get_next_message_id().unwrap(),
+                destination: types::DestinationAddress { dest: n1.own_id.clone(), reply_to: None },
+                source:      types::SourceAddress { from_node: sender.get_name(), from_group: None, reply_to: None },
+                authority:   Authority::NaeManager,
+                ttl:         DEFAULT_TTL
+            };
+            let post: Post = Random::generate_random();
+            let message = RoutingMessage::new(MessageTypeTag::Post, header, post,
+                                              &sender.get_crypto_secret_sign_key());
+            assert!(n1.message_received(&sender.get_name(), encode(&message).unwrap()).is_ok());
+        }
+        // the oldest is discarded to make room
+        match event_input.try_recv() {
+            Ok(RoutingEvent::MessageDropped(RoutingError::UnknownSender(name))) =>
+                assert_eq!(name, sender.get_name()),
+            _ => panic!("expected UnknownSender"),
+        }
+        assert_eq!(n1.unverified_messages.get(&sender.get_name()).unwrap().len(),
+                   MAX_UNVERIFIED_MESSAGES);
+
+        for held in n1.unverified_messages.values_mut() {
+            for entry in held.iter_mut() {
+                entry.1 = SteadyTime::now() - Duration::seconds(1);
+            }
+        }
+        n1.expire_unverified_messages();
+        assert!(n1.unverified_messages.is_empty());
+        for _ in 0..MAX_UNVERIFIED_MESSAGES {
+            match event_input.try_recv() {
+                Ok(RoutingEvent::MessageDropped(RoutingError::UnknownSender(name))) =>
+                    assert_eq!(name, sender.get_name()),
+                _ => panic!("expected UnknownSender"),
+            }
+        }
+        assert_eq!(stats.lock().unwrap().call_count, 0u32);
+    }
+
+#[test]
+    fn run_drains_events() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let (event_output, event_input) = mpsc::channel();
+        routing_node.event_input = event_input;
+        let sender = Pmid::new();
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&sender), vec![random_endpoint()], None);
+        routing_node.routing_table.add_node(peer.clone());
+        routing_node.handle_connect(peer.endpoints[0].clone());
+        assert!(routing_node.all_connections.0.contains_key(&peer.endpoints[0]));
+
+        let header = MessageHeader {
+            message_id:  routing_node.get_next_message_id().unwrap(),
+            destination: types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: sender.get_name(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let post: Post = Random::generate_random();
+        let message = RoutingMessage::new(MessageTypeTag::Post, header, post,
+                                          &sender.get_crypto_secret_sign_key());
+        let bytes = compression::pack(&encode(&message).unwrap(), None);
+        event_output.send(crust::Event::NewMessage(peer.endpoints[0].clone(), bytes)).unwrap();
+        event_output.send(crust::Event::LostConnection(peer.endpoints[0].clone())).unwrap();
+
+        routing_node.run();
+        assert!(routing_node.event_input.try_recv().is_err());
+        assert!(!routing_node.all_connections.0.contains_key(&peer.endpoints[0]));
+        assert_eq!(stats.lock().unwrap().call_count, 0u32);
+    }
+
+#[test]
+    fn address_in_close_group_range() {
+        let mut routing_node = RoutingNode::with_group_size(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) }, 4).unwrap();
+        let own_id = routing_node.own_id.clone();
+        let address: NameType = Random::generate_random();
+        for _ in 0..3 {
+            routing_node.routing_table.add_node(routing_table::NodeInfo::new(
+                PublicPmid::new(&Pmid::new()), random_endpoints(), None));
+        }
+        // with fewer than a group's worth of nodes, every address is in range
+        assert!(routing_node.address_in_close_group_range(&address));
+
+        for _ in 0..10 {
+            routing_node.routing_table.add_node(routing_table::NodeInfo::new(
+                PublicPmid::new(&Pmid::new()), random_endpoints(), None));
+        }
+        let close_group = routing_node.routing_table.our_close_group();
+        let furthest = close_group.last().unwrap().id();
+        let beyond = routing_node.routing_table.all_nodes()[4].id();
+
+        // the furthest member of our close group is itself on the boundary, and not in range
+        assert!(routing_node.address_in_close_group_range(&close_group[0].id()));
+        assert!(!routing_node.address_in_close_group_range(&furthest));
+        assert!(!routing_node.address_in_close_group_range(&beyond));
+        assert!(routing_node.address_in_close_group_range(&own_id));
+    }
+
+#[test]
+    fn first_bootstrap_candidate_to_respond_wins() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let slow_endpoint = random_endpoint();
+        let fast_endpoint = random_endpoint();
+        routing_node.bootstrap_candidates = vec![slow_endpoint.clone(), fast_endpoint.clone()];
+        let fast_id: NameType = Random::generate_random();
+        let slow_id: NameType = Random::generate_random();
+
+        routing_node.handle_bootstrap_id_response(fast_endpoint.clone(),
+            encode(&BootstrapIdResponse { sender_id: fast_id.clone(),
+                                          protocol_version: types::PROTOCOL_VERSION }).unwrap(), false);
+        assert_eq!(routing_node.bootstrap_endpoint, Some(fast_endpoint.clone()));
+        assert_eq!(routing_node.bootstrap_node_id, Some(fast_id.clone()));
+        assert!(routing_node.bootstrap_candidates.is_empty());
+
+        // a late responder doesn't displace the bootstrap node
+        routing_node.handle_bootstrap_id_response(slow_endpoint.clone(),
+            encode(&BootstrapIdResponse { sender_id: slow_id.clone(),
+                                          protocol_version: types::PROTOCOL_VERSION }).unwrap(), false);
+        assert_eq!(routing_node.bootstrap_endpoint, Some(fast_endpoint));
+        assert_eq!(routing_node.bootstrap_node_id, Some(fast_id));
+        assert!(!routing_node.all_connections.0.contains_key(&slow_endpoint));
+        assert!(!routing_node.all_connections.1.contains_key(&slow_id));
+    }
+
+#[test]
+    fn duplicate_bootstrap_id_response_ignored() {
+        let (event_sender, events) = mpsc::channel();
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { events: Some(event_sender), ..RoutingConfig::default() }).unwrap();
+        let endpoint = random_endpoint();
+        routing_node.bootstrap_candidates = vec![endpoint.clone()];
+        let id: NameType = Random::generate_random();
+        let response = encode(&BootstrapIdResponse { sender_id: id.clone(),
+                                                     protocol_version: types::PROTOCOL_VERSION }).unwrap();
+
+        routing_node.handle_bootstrap_id_response(endpoint.clone(), response.clone(), false);
+        routing_node.handle_bootstrap_id_response(endpoint.clone(), response, false);
+        assert_eq!(routing_node.bootstrap_endpoint, Some(endpoint.clone()));
+        assert_eq!(routing_node.bootstrap_node_id, Some(id.clone()));
+        assert_eq!(routing_node.connection_for(&id), Some(endpoint));
+        let mut bootstrapped = 0;
+        while let Ok(event) = events.try_recv() {
+            if let RoutingEvent::Bootstrapped(_) = event {
+                bootstrapped += 1;
+            }
+        }
+        assert_eq!(bootstrapped, 1);
+    }
+
+#[test]
+    fn post_action_invoked() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let destination: NameType = Random::generate_random();
+        routing_node.invoke_routing_actions(vec![
+            RoutingNodeAction::Post { destination: destination, content: vec![1u8, 2, 3] }]);
+        assert_eq!(stats.lock().unwrap().sent, vec![MessageTypeTag::Post]);
+    }
+
+#[test]
+    fn find_group_deduplicates_responses() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let target: NameType = Random::generate_random();
+        let result = routing_node.find_group(target.clone());
+        assert!(result.try_recv().is_err());
+        let message_id = *routing_node.pending_find_groups.keys().next().unwrap();
+
+        let first = PublicPmid::new(&Pmid::new());
+        let second = PublicPmid::new(&Pmid::new());
+        let header = MessageHeader {
+            message_id:  message_id,
+            destination: types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: Random::generate_random(),
+                                                from_group: Some(target.clone()),
+                                                reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let response = FindGroupResponse { group: vec![first.clone(), first.clone()] };
+        assert!(routing_node.handle_find_group_response(header.clone(),
+                                                        encode(&response).unwrap()).is_ok());
+        assert_eq!(result.try_recv(), Ok(vec![first.name.clone()]));
+
+        let response = FindGroupResponse { group: vec![second.clone(), first.clone()] };
+        assert!(routing_node.handle_find_group_response(header.clone(),
+                                                        encode(&response).unwrap()).is_ok());
+        assert_eq!(result.try_recv(), Ok(vec![second.name.clone()]));
+
+        // nothing new
+        assert!(routing_node.handle_find_group_response(header, encode(&response).unwrap()).is_ok());
+        assert!(result.try_recv().is_err());
+    }
+
+    #[test]
+    fn find_groups_for_same_target_kept_apart() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let target: NameType = Random::generate_random();
+        let first_result = routing_node.find_group(target.clone());
+        let first_id = *routing_node.pending_find_groups.keys().next().unwrap();
+        let second_result = routing_node.find_group(target.clone());
+        assert_eq!(routing_node.pending_find_groups.len(), 2);
+        let second_id = *routing_node.pending_find_groups.keys().find(|id| **id != first_id).unwrap();
+
+        // a member answers each search under the id it was asked with
+        let member = PublicPmid::new(&Pmid::new());
+        let header = MessageHeader {
+            message_id:  second_id,
+            destination: types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: Random::generate_random(),
+                                                from_group: Some(target.clone()),
+                                                reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let response = FindGroupResponse { group: vec![member.clone()] };
+        assert!(routing_node.handle_find_group_response(header, encode(&response).unwrap()).is_ok());
+        assert_eq!(second_result.try_recv(), Ok(vec![member.name.clone()]));
+        assert!(first_result.try_recv().is_err());
+
+        // unanswered searches are given up on
+        for pending in routing_node.pending_find_groups.values_mut() {
+            pending.2 = SteadyTime::now() - Duration::seconds(1);
+        }
+        routing_node.expire_pending_find_groups();
+        assert!(routing_node.pending_find_groups.is_empty());
+        assert_eq!(first_result.try_recv(), Err(mpsc::TryRecvError::Disconnected));
+    }
+
+#[test]
+    fn get_data_cached_until_put() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let name: NameType = Random::generate_random();
+        let name_and_type_id = types::NameAndTypeId { name: name.clone(), type_id: 100u64 };
+        let header = MessageHeader {
+            message_id:  random::<u32>(),
+            destination: types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: Random::generate_random(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let response = GetDataResponse { name_and_type_id: name_and_type_id.clone(), data: Ok(vec![1u8, 2, 3]),
+                                         part: 0, total: 1 };
+        assert!(routing_node.handle_get_data_response(header.clone(), encode(&response).unwrap()).is_ok());
+        assert_eq!(stats.lock().unwrap().call_count, 1);
+
+        // a repeat read is answered from the cache without asking the interface
+        let get_data = GetData { requester: header.source.clone(), name_and_type_id: name_and_type_id.clone() };
+        assert!(routing_node.handle_get_data(header.clone(), encode(&get_data).unwrap()).is_ok());
+        assert_eq!(stats.lock().unwrap().call_count, 1);
+        assert_eq!(stats.lock().unwrap().sent, vec![MessageTypeTag::GetDataResponse]);
+
+        // a successful put of the same name invalidates the cached copy
+        let put_data = PutData { name: name.clone(), data: vec![4u8] };
+        assert!(routing_node.handle_put_data(header.clone(), encode(&put_data).unwrap()).is_ok());
+        assert_eq!(stats.lock().unwrap().call_count, 2);
+        assert!(routing_node.handle_get_data(header, encode(&get_data).unwrap()).is_ok());
+        assert_eq!(stats.lock().unwrap().call_count, 3);
+    }
+
+    #[test]
+    fn relay_target_not_connected() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let relayed_for: NameType = Random::generate_random();
+        let header = MessageHeader {
+            message_id:  random::<u32>(),
+            destination: types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: Random::generate_random(), from_group: None,
+                                                reply_to: Some(relayed_for.clone()) },
+            authority:   Authority::ManagedNode,
+            ttl:         DEFAULT_TTL
+        };
+        let find_group = FindGroup { requester_id: relayed_for.clone(), target_id: relayed_for.clone() };
+        match routing_node.handle_find_group(header, encode(&find_group).unwrap()) {
+            Err(RoutingError::RelayTargetNotConnected(name)) => assert_eq!(name, relayed_for),
+            _ => panic!("expected RelayTargetNotConnected")
+        }
+    }
+
+#[test]
+    fn send_to_empty_routing_table() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let target: NameType = Random::generate_random();
+        assert_eq!(routing_node.send_swarm_or_parallel(&target, &MessageTypeTag::PutData,
+                                                       &vec![1u8, 2, 3]), 0);
+        assert_eq!(stats.lock().unwrap().sent, vec![MessageTypeTag::PutData]);
+    }
+
+#[test]
+    fn connect_requests_deduplicated() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let first = PublicPmid::new(&Pmid::new());
+        let second = PublicPmid::new(&Pmid::new());
+        let third = PublicPmid::new(&Pmid::new());
+        let header = MessageHeader {
+            message_id:  random::<u32>(),
+            destination: types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: Random::generate_random(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let response = FindGroupResponse { group: vec![first.clone(), second.clone()] };
+        assert!(routing_node.handle_find_group_response(header.clone(), encode(&response).unwrap()).is_ok());
+        let response = FindGroupResponse { group: vec![second.clone(), third.clone()] };
+        assert!(routing_node.handle_find_group_response(header, encode(&response).unwrap()).is_ok());
+
+        let connect_requests = stats.lock().unwrap().sent.iter()
+            .filter(|message_type| **message_type == MessageTypeTag::ConnectRequest)
+            .count();
+        assert_eq!(connect_requests, 3);
+    }
+
+    #[test]
+    fn connection_state() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let peer_id : NameType = Random::generate_random();
+        let peer_endpoint = random_endpoint();
+        assert!(!routing_node.is_connected(&peer_id));
+        assert!(routing_node.connection_for(&peer_id).is_none());
+
+        routing_node.all_connections.0.insert(peer_endpoint.clone(), peer_id.clone());
+        routing_node.all_connections.1.insert(peer_id.clone(), peer_endpoint.clone());
+        assert!(routing_node.is_connected(&peer_id));
+        assert_eq!(routing_node.connection_for(&peer_id), Some(peer_endpoint));
+        assert!(!routing_node.is_connected(&Random::generate_random()));
+    }
+
+    #[test]
+    fn zero_state_connect_response() {
+        let mut n1 = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let mut n2 = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        // n2 bootstrapped off n1, which is the only connection either of them holds
+        let n1_endpoint = random_endpoint();
+        n2.all_connections.0.insert(n1_endpoint.clone(), n1.id());
+        n2.all_connections.1.insert(n1.id(), n1_endpoint.clone());
+
+        let request_msg = n2.construct_connect_request_msg(&n1.id()).unwrap();
+        let request = decode::<ConnectRequest>(&request_msg.serialised_body).unwrap();
+        let response_msg = n1.construct_connect_response_msg(&request_msg.message_header, &request).unwrap();
+        assert!(n2.handle_connect_response(response_msg.serialised_body).is_ok());
+
+        assert_eq!(n2.close_group(), vec![n1.id()]);
+        let targets = n2.get_connected_target(&n1.id());
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].connected_endpoint, Some(n1_endpoint));
+    }
+
+    #[test]
+    fn message_rate_limited() {
+        let (event_sender, events) = ::std::sync::mpsc::channel();
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { events: Some(event_sender), ..RoutingConfig::default() }).unwrap();
+        routing_node.set_message_rate_limit(1, 5);
+        let peer_id : NameType = Random::generate_random();
+        for _ in 0..5 {
+            match routing_node.message_received(&peer_id, vec![0xffu8; 10]) {
+                Err(RoutingError::CborError(_)) => (),
+                _ => panic!(),
+            }
+        }
+        for _ in 0..MAX_RATE_LIMITED_MESSAGES {
+            match routing_node.message_received(&peer_id, vec![0xffu8; 10]) {
+                Err(RoutingError::RateLimitExceeded(id)) => assert_eq!(id, peer_id),
+                _ => panic!(),
+            }
+        }
+        assert_eq!(routing_node.rate_limited_messages(), MAX_RATE_LIMITED_MESSAGES as usize);
+        match events.try_recv() {
+            Ok(RoutingEvent::MessageDropped(RoutingError::RateLimitExceeded(id))) =>
+                assert_eq!(id, peer_id),
+            _ => panic!(),
+        }
+        assert!(events.try_recv().is_err());
+
+        // other peers have their own budget
+        assert!(routing_node.check_message_rate(&Random::generate_random()));
+    }
+
+    #[test]
+    fn silent_peer_dropped() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                vec![random_endpoint()], None);
+        let peer_endpoint = peer.endpoints[0].clone();
+        assert!(routing_node.add_node(peer.clone()).0);
+        routing_node.handle_connect(peer_endpoint.clone());
+        assert!(routing_node.is_connected(&peer.id()));
+
+        // heard from recently, so left alone
+        routing_node.ping_silent_peers();
+        assert!(routing_node.pings_sent.is_empty());
+
+        routing_node.set_ping_policy(Duration::zero(), Duration::milliseconds(50));
+        routing_node.ping_silent_peers();
+        assert!(routing_node.pings_sent.contains_key(&peer.id()));
+        routing_node.ping_silent_peers();
+        assert!(routing_node.is_connected(&peer.id()));
+
+        thread::sleep_ms(100);
+        routing_node.ping_silent_peers();
+        assert!(!routing_node.is_connected(&peer.id()));
+        assert_eq!(routing_node.routing_table_size(), 0);
+        assert!(routing_node.pings_sent.is_empty());
+    }
+
+    #[test]
+    fn ping_from_unconnected_peer() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let peer_id : NameType = Random::generate_random();
+        let ping = Ping { sender_id: peer_id.clone() };
+        match routing_node.handle_ping(&peer_id, encode(&ping).unwrap()) {
+            Err(RoutingError::NotConnected(id)) => assert_eq!(id, peer_id),
+            _ => panic!(),
+        }
+
+        // a ping naming someone else is still answered to the peer it came from
+        let connected_id : NameType = Random::generate_random();
+        let endpoint = random_endpoint();
+        routing_node.all_connections.0.insert(endpoint.clone(), connected_id.clone());
+        routing_node.all_connections.1.insert(connected_id.clone(), endpoint);
+        let ping = Ping { sender_id: connected_id.clone() };
+        match routing_node.handle_ping(&peer_id, encode(&ping).unwrap()) {
+            Err(RoutingError::NotConnected(id)) => assert_eq!(id, peer_id),
+            _ => panic!(),
+        }
+        assert!(stats.lock().unwrap().sent.is_empty());
+        let ping = Ping { sender_id: peer_id.clone() };
+        let _ = routing_node.handle_ping(&connected_id, encode(&ping).unwrap());
+        assert_eq!(stats.lock().unwrap().sent, vec![MessageTypeTag::PingResponse]);
+    }
+
+    #[test]
+    fn bootstrap_cache() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let endpoints: Vec<Endpoint> = (0..BOOTSTRAP_CACHE_SIZE + 1).map(|_| random_endpoint())
+                                                                   .collect();
+        for endpoint in endpoints.iter() {
+            routing_node.cache_bootstrap_endpoint(endpoint.clone());
+        }
+        // the oldest success is evicted, and a repeat success moves an endpoint to the front
+        assert_eq!(routing_node.bootstrap_cache.len(), BOOTSTRAP_CACHE_SIZE);
+        assert!(!routing_node.bootstrap_cache.contains(&endpoints[0]));
+        routing_node.cache_bootstrap_endpoint(endpoints[1].clone());
+        assert_eq!(routing_node.bootstrap_cache.len(), BOOTSTRAP_CACHE_SIZE);
+        assert_eq!(routing_node.bootstrap_cache[0], endpoints[1]);
+
+        let bytes = routing_node.bootstrap_cache_bytes().unwrap();
+        let mut restarted = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        assert!(restarted.load_bootstrap_cache(&bytes).is_ok());
+        assert_eq!(restarted.bootstrap_cache, routing_node.bootstrap_cache);
+        assert!(restarted.load_bootstrap_cache(&vec![0xffu8; 10]).is_err());
+    }
+
+    #[test]
+    fn large_get_response_split() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        routing_node.set_get_response_part_size(5);
+        let header = MessageHeader {
+            message_id:  random::<u32>(),
+            destination: types::DestinationAddress { dest: Random::generate_random(), reply_to: None },
+            source:      types::SourceAddress { from_node: Random::generate_random(), from_group: None, reply_to: None },
+            authority:   Authority::Client,
+            ttl:         DEFAULT_TTL
+        };
+        let get_data: GetData = Random::generate_random();
+        assert!(routing_node.handle_get_data(header, encode(&get_data).unwrap()).is_ok());
+        // TestInterface replies with "handle_get called", 17 bytes
+        assert_eq!(stats.lock().unwrap().sent, vec![MessageTypeTag::GetDataResponse; 4]);
+    }
+
+    #[test]
+    fn split_get_response_reassembled() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let (message_id, result) = pending_get(&mut routing_node);
+        let header = MessageHeader {
+            message_id:  message_id,
+            destination: types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: Random::generate_random(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let name_and_type_id: types::NameAndTypeId = Random::generate_random();
+        let parts = vec![vec![1u8, 2], vec![3u8, 4], vec![5u8]];
+        for &part in [2usize, 0].iter() {
+            let response = GetDataResponse { name_and_type_id: name_and_type_id.clone(),
+                                             data: Ok(parts[part].clone()), part: part as u32, total: 3 };
+            assert!(routing_node.handle_get_data_response(header.clone(), encode(&response).unwrap()).is_ok());
+            assert!(result.try_recv().is_err());
+        }
+        assert_eq!(stats.lock().unwrap().call_count, 0);
+
+        let response = GetDataResponse { name_and_type_id: name_and_type_id.clone(),
+                                         data: Ok(parts[1].clone()), part: 1, total: 3 };
+        assert!(routing_node.handle_get_data_response(header, encode(&response).unwrap()).is_ok());
+        assert_eq!(result.try_recv(), Ok(Ok(vec![1u8, 2, 3, 4, 5])));
+        assert_eq!(stats.lock().unwrap().call_count, 1);
+        assert_eq!(routing_node.partial_get_responses.len(), 0);
+    }
+
+    #[test]
+    fn split_get_response_with_too_many_parts_dropped() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        routing_node.set_max_message_size(1000);
+        routing_node.set_get_response_part_size(100);
+        let name_and_type_id: types::NameAndTypeId = Random::generate_random();
+        for &total in [12u32, ::std::u32::MAX].iter() {
+            let response = GetDataResponse { name_and_type_id: name_and_type_id.clone(),
+                                             data: Ok(vec![1u8]), part: 0, total: total };
+            assert!(routing_node.assemble_get_data_response(1, response).is_none());
+            assert_eq!(routing_node.partial_get_responses.len(), 0);
+        }
+        let response = GetDataResponse { name_and_type_id: name_and_type_id.clone(),
+                                         data: Ok(vec![1u8]), part: 0, total: 11 };
+        assert!(routing_node.assemble_get_data_response(1, response).is_none());
+        assert_eq!(routing_node.partial_get_responses.len(), 1);
+
+        // only so many assemblies are kept at once
+        for message_id in 2..(2 + MAX_PARTIAL_GET_RESPONSES as u32) {
+            let response = GetDataResponse { name_and_type_id: name_and_type_id.clone(),
+                                             data: Ok(vec![1u8]), part: 0, total: 2 };
+            assert!(routing_node.assemble_get_data_response(message_id, response).is_none());
+        }
+        assert_eq!(routing_node.partial_get_responses.len(), MAX_PARTIAL_GET_RESPONSES);
+    }
+
+    #[test]
+    fn malformed_body_reports_type() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let sender = Pmid::new();
+        routing_node.public_pmid_cache.add(sender.get_name(), PublicPmid::new(&sender));
+        let header = MessageHeader {
+            message_id:  routing_node.get_next_message_id().unwrap(),
+            destination: types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: sender.get_name(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let message = RoutingMessage::new(MessageTypeTag::PutData, header,
+            "not a PutData".to_string(), &sender.get_crypto_secret_sign_key());
+        match routing_node.message_received(&sender.get_name(), encode(&message).unwrap()) {
+            Err(RoutingError::MalformedMessage { type_tag, .. }) =>
+                assert_eq!(type_tag, MessageTypeTag::PutData),
+            _ => panic!(),
+        }
+        assert_eq!(stats.lock().unwrap().call_count, 0);
+    }
+
+    #[test]
+    fn send_on_deduplicated() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let header = MessageHeader {
+            message_id:  random::<u32>(),
+            destination: types::DestinationAddress { dest: Random::generate_random(), reply_to: None },
+            source:      types::SourceAddress { from_node: Random::generate_random(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let our_authority = Authority::NaeManager;
+        let first: NameType = Random::generate_random();
+        let second: NameType = Random::generate_random();
+        let put_data: PutData = Random::generate_random();
+        assert!(routing_node.send_on(&header, &our_authority, MessageTypeTag::PutData, put_data,
+                                     vec![first.clone(), second.clone(), first, second.clone(), second])
+                            .is_ok());
+        assert_eq!(stats.lock().unwrap().sent, vec![MessageTypeTag::PutData; 2]);
+    }
+
+    #[test]
+    fn message_filter_capacity() {
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { message_filter_capacity: 10, ..RoutingConfig::default() }).unwrap();
+        let own_id = routing_node.own_id.clone();
+        for message_id in 0..11u32 {
+            routing_node.filter.add(((own_id.clone(), message_id), 0));
+        }
+        // the oldest entry made way for the eleventh
+        assert!(!routing_node.filter.check(&((own_id.clone(), 0), 0)));
+        for message_id in 1..11u32 {
+            assert!(routing_node.filter.check(&((own_id.clone(), message_id), 0)));
+        }
+    }
+
+    #[test]
+    fn nodes_per_ip_limited() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        routing_node.set_max_nodes_per_ip(Some(1));
+        let own_id = routing_node.id();
+        let connect = |routing_node: &mut RoutingNode<TestInterface>, address: &str| {
+            let pmid = Pmid::new();
+            let connect_request = ConnectRequest {
+                local_endpoints: vec![Tcp(address.parse::<::std::net::SocketAddr>().unwrap())],
+                external_endpoints: vec![],
+                requester_id: pmid.get_name(),
+                receiver_id: own_id.clone(),
+                requester_fob: PublicPmid::new(&pmid)
+            };
+            let header = MessageHeader {
+                message_id:  random::<u32>(),
+                destination: types::DestinationAddress { dest: own_id.clone(), reply_to: None },
+                source:      types::SourceAddress { from_node: pmid.get_name(), from_group: None, reply_to: None },
+                authority:   Authority::ManagedNode,
+                ttl:         DEFAULT_TTL
+            };
+            routing_node.handle_connect_request(header, encode(&connect_request).unwrap())
+        };
+        assert!(connect(&mut routing_node, "10.0.0.1:5483").is_ok());
+        match connect(&mut routing_node, "10.0.0.1:5484") {
+            Err(RoutingError::TooManyNodesOnIp) => (),
+            _ => panic!(),
+        }
+        assert!(connect(&mut routing_node, "10.0.0.2:5483").is_ok());
+        assert_eq!(routing_node.routing_table.size(), 2);
+    }
+
+    #[test]
+    fn group_keys_sorted_and_unique() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        for _ in 0..5 {
+            let _ = routing_node.routing_table.add_node(routing_table::NodeInfo::new(
+                PublicPmid::new(&Pmid::new()), vec![random_endpoint()], None));
+        }
+
+        let names = routing_node.group_keys().into_iter().map(|(name, _)| name).collect::<Vec<_>>();
+        let mut expected = names.clone();
+        expected.sort();
+        expected.dedup();
+        assert_eq!(names, expected);
+        assert_eq!(names.iter().filter(|name| **name == routing_node.id()).count(), 1);
+    }
+
+    #[test]
+    fn pmid_leading_bits_policy() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        routing_node.set_min_pmid_leading_bits(8);
+        let own_id = routing_node.own_id.get_id();
+        let put = |routing_node: &mut RoutingNode<TestInterface>, flipped_bit: usize| {
+            let mut name = own_id;
+            name[flipped_bit / 8] ^= 0x80 >> (flipped_bit % 8);
+            let mut public_pmid = PublicPmid::new(&Pmid::new());
+            public_pmid.name = NameType::new(name);
+            let header = MessageHeader {
+                message_id:  random::<u32>(),
+                destination: types::DestinationAddress { dest: public_pmid.name.clone(), reply_to: None },
+                source:      types::SourceAddress { from_node: Random::generate_random(), from_group: None, reply_to: None },
+                authority:   Authority::ManagedNode,
+                ttl:         DEFAULT_TTL
+            };
+            let put_public_pmid = PutPublicPmid { public_pmid: public_pmid.clone() };
+            let result = routing_node.handle_put_public_pmid(header, encode(&put_public_pmid).unwrap());
+            (result, public_pmid.name)
+        };
+        // shares exactly 8 leading bits with us
+        let (result, inside) = put(&mut routing_node, 8);
+        assert!(result.is_ok());
+        assert!(routing_node.public_pmid_cache.check(&inside));
+        // shares only 7
+        let (result, outside) = put(&mut routing_node, 7);
+        match result {
+            Err(RoutingError::BadAuthority) => (),
+            _ => panic!(),
+        }
+        assert!(!routing_node.public_pmid_cache.check(&outside));
+    }
+
+    #[test]
+    fn transport_closed() {
+        let (event_sender, events) = ::std::sync::mpsc::channel();
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { events: Some(event_sender), ..RoutingConfig::default() }).unwrap();
+        // stand in for a connection manager which has dropped its sender
+        let (_, event_input) = mpsc::channel();
+        routing_node.event_input = event_input;
+        assert!(!routing_node.is_stopped());
+        routing_node.run();
+        assert!(routing_node.is_stopped());
+        match events.try_recv() {
+            Ok(RoutingEvent::TransportClosed) => (),
+            _ => panic!(),
+        }
+        // a stopped node does nothing more
+        routing_node.run();
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn disconnected_target_replaced() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        for _ in 0..100 {
+            let _ = routing_node.routing_table.add_node(routing_table::NodeInfo::new(
+                PublicPmid::new(&Pmid::new()), vec![random_endpoint()], None));
+        }
+        assert!(routing_node.routing_table.size() > routing_node.routing_table.group_size());
+        // the node furthest from us is outside our close group, so it is the closest target
+        let target = routing_node.routing_table.all_nodes().last().unwrap().id();
+        let ideal = routing_node.routing_table.target_nodes(target.clone());
+        assert_eq!(ideal[0].id(), target);
+        // connect to everyone but the closest target
+        for node in routing_node.routing_table.all_nodes().iter().filter(|node| node.id() != target) {
+            assert!(routing_node.routing_table.mark_as_connected(&node.endpoints[0]));
+        }
+
+        let targets = routing_node.get_connected_target(&target);
+        assert_eq!(targets.len(), ideal.len());
+        assert!(targets.iter().all(|node| node.id() != target && node.connected_endpoint.is_some()));
+        let close_group = routing_node.close_group();
+        let alternates = targets.iter()
+                                .filter(|node| !ideal.iter().any(|ideal| ideal.id() == node.id()))
+                                .collect::<Vec<_>>();
+        assert_eq!(alternates.len(), 1);
+        assert!(close_group.contains(&alternates[0].id()));
+    }
+
+    #[test]
+    fn incompatible_protocol_version_refused() {
+        let (event_sender, events) = ::std::sync::mpsc::channel();
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { events: Some(event_sender), ..RoutingConfig::default() }).unwrap();
+        let peer = Pmid::new();
+        let peer_endpoint = random_endpoint();
+        let header = MessageHeader::new(random::<u32>(),
+            types::DestinationAddress { dest: NameType::new([0u8; NAME_TYPE_LEN]), reply_to: None },
+            types::SourceAddress { from_node: peer.get_name(), from_group: None, reply_to: None },
+            Authority::ManagedNode);
+        let request = RoutingMessage::new(MessageTypeTag::BootstrapIdRequest, header,
+            BootstrapIdRequest { sender_id: peer.get_name(),
+                                 protocol_version: types::PROTOCOL_VERSION + 1 },
+            &peer.get_crypto_secret_sign_key());
+        routing_node.handle_new_message(peer_endpoint.clone(), compression::pack(&encode(&request).unwrap(), None));
+        match events.try_recv() {
+            Ok(RoutingEvent::MessageDropped(RoutingError::IncompatibleProtocolVersion(version))) =>
+                assert_eq!(version, types::PROTOCOL_VERSION + 1),
+            _ => panic!(),
+        }
+        assert!(routing_node.all_connections.0.is_empty());
+        assert!(routing_node.bootstrap_node_id.is_none());
+
+        // nor is an incompatible bootstrap node accepted
+        routing_node.bootstrap_candidates = vec![peer_endpoint.clone()];
+        routing_node.handle_bootstrap_id_response(peer_endpoint.clone(),
+            encode(&BootstrapIdResponse { sender_id: peer.get_name(),
+                                          protocol_version: types::PROTOCOL_VERSION + 1 }).unwrap(), false);
+        assert!(routing_node.all_connections.0.is_empty());
+        assert!(routing_node.bootstrap_node_id.is_none());
+    }
+
+    #[test]
+    fn send_direct_needs_connection() {
+        let routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let peer_id: NameType = Random::generate_random();
+        let message = RoutingMessage::new(MessageTypeTag::Ping,
+            MessageHeader::new(random::<u32>(),
+                types::DestinationAddress { dest: peer_id.clone(), reply_to: None },
+                types::SourceAddress { from_node: routing_node.id(), from_group: None, reply_to: None },
+                Authority::ManagedNode),
+            Ping { sender_id: routing_node.id() }, &routing_node.pmid.get_crypto_secret_sign_key());
+        match routing_node.send_direct(&peer_id, &message) {
+            Err(RoutingError::NotConnected(id)) => assert_eq!(id, peer_id),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn unconnected_node_dropped() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let never_connects = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                          vec![random_endpoint()], None);
+        let connects = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                    vec![random_endpoint()], None);
+        assert!(routing_node.add_node(never_connects.clone()).0);
+        assert!(routing_node.add_node(connects.clone()).0);
+        routing_node.handle_connect(connects.endpoints[0].clone());
+
+        // within the timeout both are kept
+        routing_node.drop_unconnected_nodes();
+        assert_eq!(routing_node.routing_table.size(), 2);
+
+        routing_node.set_connect_timeout(Duration::milliseconds(0));
+        routing_node.drop_unconnected_nodes();
+        assert_eq!(routing_node.close_group(), vec![connects.id()]);
+        assert!(routing_node.unconnected_nodes.is_empty());
+    }
+
+    #[test]
+    fn io_error_kind_reported() {
+        let (event_sender, events) = ::std::sync::mpsc::channel();
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { events: Some(event_sender), ..RoutingConfig::default() }).unwrap();
+        // no such compression flag
+        let mut packed = compression::pack(&[1u8, 2, 3], None);
+        packed[compression::ENVELOPE_LEN - 1] = 0xff;
+        routing_node.handle_new_message(random_endpoint(), packed);
+        match events.try_recv() {
+            Ok(RoutingEvent::MessageDropped(RoutingError::Io(kind))) =>
+                assert_eq!(kind, ::std::io::ErrorKind::InvalidInput),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn known_node_added() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let first = Pmid::new();
+        let first_endpoint = random_endpoint();
+        routing_node.add_known_node(first.get_name(), first_endpoint.clone(), None);
+        assert_eq!(routing_node.connection_for(&first.get_name()), Some(first_endpoint.clone()));
+        assert_eq!(routing_node.bootstrap_node_id, Some(first.get_name()));
+        assert_eq!(routing_node.bootstrap_endpoint, Some(first_endpoint.clone()));
+        assert_eq!(routing_node.routing_table.size(), 0);
+
+        let second = Pmid::new();
+        let second_endpoint = random_endpoint();
+        routing_node.add_known_node(second.get_name(), second_endpoint.clone(),
+                                    Some(PublicPmid::new(&second)));
+        assert!(routing_node.is_connected(&second.get_name()));
+        assert_eq!(routing_node.bootstrap_node_id, Some(first.get_name()));
+        assert_eq!(routing_node.close_group(), vec![second.get_name()]);
+        let targets = routing_node.get_connected_target(&second.get_name());
+        assert_eq!(targets[0].connected_endpoint, Some(second_endpoint));
+    }
+
+    #[test]
+    fn foreign_traffic_refused() {
+        let (event_sender, events) = ::std::sync::mpsc::channel();
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { events: Some(event_sender), ..RoutingConfig::default() }).unwrap();
+        // raw cbor, as sent by something which isn't a routing node
+        let peer = Pmid::new();
+        let header = MessageHeader::new(random::<u32>(),
+            types::DestinationAddress { dest: NameType::new([0u8; NAME_TYPE_LEN]), reply_to: None },
+            types::SourceAddress { from_node: peer.get_name(), from_group: None, reply_to: None },
+            Authority::ManagedNode);
+        let request = RoutingMessage::new(MessageTypeTag::BootstrapIdRequest, header,
+            BootstrapIdRequest { sender_id: peer.get_name(), protocol_version: types::PROTOCOL_VERSION },
+            &peer.get_crypto_secret_sign_key());
+        routing_node.handle_new_message(random_endpoint(), encode(&request).unwrap());
+        match events.try_recv() {
+            Ok(RoutingEvent::MessageDropped(RoutingError::NotARoutingMessage)) => (),
+            _ => panic!(),
+        }
+        assert!(routing_node.all_connections.0.is_empty());
+    }
+
+    #[test]
+    fn ttl_expired() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let sender = Pmid::new();
+        routing_node.public_pmid_cache.add(sender.get_name(), PublicPmid::new(&sender));
+        let header = MessageHeader {
+            message_id:  random::<u32>(),
+            destination: types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: sender.get_name(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         1
+        };
+        // a message with one send on left isn't sent on again
+        let put_data: PutData = Random::generate_random();
+        match routing_node.send_on(&header, &Authority::NaeManager, MessageTypeTag::PutData,
+                                   put_data.clone(), vec![Random::generate_random()]) {
+            Err(RoutingError::TtlExpired) => (),
+            _ => panic!(),
+        }
+        assert!(stats.lock().unwrap().sent.is_empty());
+
+        // and one with none left is dropped on arrival
+        let mut header = header;
+        header.ttl = 0;
+        let message = RoutingMessage::new(MessageTypeTag::PutData, header, put_data,
+                                          &sender.get_crypto_secret_sign_key());
+        match routing_node.message_received(&sender.get_name(), encode(&message).unwrap()) {
+            Err(RoutingError::TtlExpired) => (),
+            _ => panic!(),
+        }
+        assert_eq!(stats.lock().unwrap().call_count, 0);
+        assert!(stats.lock().unwrap().sent.is_empty());
+    }
+
+    #[test]
+    fn try_run_reports_work() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let (event_output, event_input) = mpsc::channel();
+        routing_node.event_input = event_input;
+        assert!(!routing_node.try_run());
+        event_output.send(crust::Event::NewConnection(random_endpoint())).unwrap();
+        assert!(routing_node.try_run());
+        assert!(!routing_node.try_run());
+    }
+
+    #[test]
+    fn client_receives_relayed_get_response() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut client = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let (message_id, result) = pending_get(&mut client);
+        let bootstrap_node = Pmid::new();
+        client.public_pmid_cache.add(bootstrap_node.get_name(), PublicPmid::new(&bootstrap_node));
+        let response = GetDataResponse { name_and_type_id: Random::generate_random(),
+                                         data: Ok(vec![1u8, 2, 3]), part: 0, total: 1 };
+
+        // the bootstrap node sends on a response addressed to its client straight to it
+        let mut relay = RoutingNode::new(TestInterface { stats: stats.clone() });
+        relay.non_routing_connections.insert(client.own_id.clone(), random_endpoint());
+        let header = MessageHeader {
+            message_id:  message_id,
+            destination: types::DestinationAddress { dest: client.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: bootstrap_node.get_name(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        relay.public_pmid_cache.add(bootstrap_node.get_name(), PublicPmid::new(&bootstrap_node));
+        let message = RoutingMessage::new(MessageTypeTag::GetDataResponse, header, response.clone(),
+                                          &bootstrap_node.get_crypto_secret_sign_key());
+        let _ = relay.message_received(&bootstrap_node.get_name(), encode(&message).unwrap());
+        assert_eq!(stats.lock().unwrap().call_count, 0);
+        assert_eq!(stats.lock().unwrap().sent, vec![MessageTypeTag::GetDataResponse]);
+        stats.lock().unwrap().sent.clear();
+
+        // and the client, with no routing table, handles what its bootstrap node relays
+        let header = MessageHeader {
+            message_id:  message_id,
+            destination: types::DestinationAddress { dest: bootstrap_node.get_name(),
+                                                     reply_to: Some(client.own_id.clone()) },
+            source:      types::SourceAddress { from_node: bootstrap_node.get_name(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let message = RoutingMessage::new(MessageTypeTag::GetDataResponse, header, response,
+                                          &bootstrap_node.get_crypto_secret_sign_key());
+        assert!(client.message_received(&bootstrap_node.get_name(), encode(&message).unwrap()).is_ok());
+        assert_eq!(result.try_recv(), Ok(Ok(vec![1u8, 2, 3])));
+        assert!(stats.lock().unwrap().sent.is_empty());
+    }
+
+    #[test]
+    fn relayed_response_for_gone_client_dropped() {
+        let mut relay = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let bootstrap_node = Pmid::new();
+        relay.public_pmid_cache.add(bootstrap_node.get_name(), PublicPmid::new(&bootstrap_node));
+        // a peer the response used to be sent on to blindly
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                vec![random_endpoint()], None);
+        relay.routing_table.add_node(peer.clone());
+        relay.handle_connect(peer.endpoints[0].clone());
+        let header = MessageHeader {
+            message_id:  random::<u32>(),
+            destination: types::DestinationAddress { dest: relay.own_id.clone(),
+                                                     reply_to: Some(Random::generate_random()) },
+            source:      types::SourceAddress { from_node: bootstrap_node.get_name(), from_group: None, reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let response = GetDataResponse { name_and_type_id: Random::generate_random(),
+                                         data: Ok(vec![1u8, 2, 3]), part: 0, total: 1 };
+        let message = RoutingMessage::new(MessageTypeTag::GetDataResponse, header, response,
+                                          &bootstrap_node.get_crypto_secret_sign_key());
+        match relay.message_received(&bootstrap_node.get_name(), encode(&message).unwrap()) {
+            Err(RoutingError::NoReplyEndpoint) => (),
+            _ => panic!("expected NoReplyEndpoint")
+        }
+    }
+
+    #[test]
+    fn churn_delta_on_join() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        assert_eq!(routing_node.churn_delta(), (vec![], vec![]));
+        let first = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                 vec![random_endpoint()], None);
+        assert!(routing_node.add_node(first.clone()).0);
+        assert_eq!(routing_node.churn_delta(), (vec![first.id()], vec![]));
+
+        let second = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                  vec![random_endpoint()], None);
+        assert!(routing_node.add_node(second.clone()).0);
+        assert_eq!(routing_node.churn_delta(), (vec![second.id()], vec![]));
+        let mut close_group = vec![first.id(), second.id()];
+        close_group.sort();
+        assert_eq!(routing_node.routing_table.close_group_snapshot(), close_group);
+    }
+
+    #[test]
+    fn churn_delta_on_leave() {
+        let mut routing_node = RoutingNode::with_group_size(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) }, 2).unwrap();
+        let mut peers = (0..3).map(|_| routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()),
+                                                                   vec![random_endpoint()], None))
+                              .collect::<Vec<_>>();
+        for peer in peers.iter() {
+            assert!(routing_node.add_node(peer.clone()).0);
+        }
+        let own_id = routing_node.own_id.clone();
+        peers.sort_by(|lhs, rhs| if ::name_type::closer_to_target(&lhs.id(), &rhs.id(), &own_id) {
+            ::std::cmp::Ordering::Less
+        } else {
+            ::std::cmp::Ordering::Greater
+        });
+
+        // the furthest node isn't in the close group, so losing it leaves the group as it is
+        routing_node.drop_node(&peers[2].id());
+        assert_eq!(routing_node.routing_table.close_group_snapshot().len(), 2);
+        routing_node.drop_node(&peers[0].id());
+        assert_eq!(routing_node.churn_delta(), (vec![], vec![peers[0].id()]));
+    }
+
+    #[test]
+    fn message_from_self_dropped() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let peer = Pmid::new();
+        let header = MessageHeader {
+            message_id:  routing_node.get_next_message_id().unwrap(),
+            destination: types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: routing_node.own_id.clone(), from_group: None,
+                                                reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let put_data: PutData = Random::generate_random();
+        let message = RoutingMessage::new(MessageTypeTag::PutData, header.clone(), put_data.clone(),
+                                          &routing_node.pmid.get_crypto_secret_sign_key());
+        match routing_node.message_received(&peer.get_name(), encode(&message).unwrap()) {
+            Err(RoutingError::MessageFromSelf) => (),
+            _ => panic!(),
+        }
+        assert!(!routing_node.filter.check(&(header.get_filter(), 0)));
+        assert_eq!(stats.lock().unwrap().call_count, 0);
+        assert!(stats.lock().unwrap().sent.is_empty());
+    }
+
+    #[test]
+    fn public_pmid_cache_capacity() {
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { public_pmid_cache_capacity: 3, ..RoutingConfig::default() }).unwrap();
+        let pmids = (0..4).map(|_| Pmid::new()).collect::<Vec<_>>();
+        for pmid in pmids[..3].iter() {
+            routing_node.public_pmid_cache.add(pmid.get_name(), PublicPmid::new(pmid));
+        }
+        // using the oldest entry makes the second the least recently used
+        assert!(routing_node.public_pmid_cache.get(&pmids[0].get_name()).is_some());
+        routing_node.public_pmid_cache.add(pmids[3].get_name(), PublicPmid::new(&pmids[3]));
+        assert_eq!(routing_node.public_pmid_cache.len(), 3);
+        assert!(!routing_node.public_pmid_cache.check(&pmids[1].get_name()));
+        for &index in [0usize, 2, 3].iter() {
+            assert!(routing_node.public_pmid_cache.check(&pmids[index].get_name()));
+        }
+    }
+
+    #[test]
+    fn public_key_cache_capacity() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        routing_node.set_public_key_cache(2, Duration::minutes(10));
+        let put_keys = (0..3).map(|_| Random::generate_random()).collect::<Vec<PutKey>>();
+        for put_key in put_keys.iter() {
+            let header = MessageHeader {
+                message_id:  random::<u32>(),
+                destination: types::DestinationAddress { dest: put_key.target_id.clone(), reply_to: None },
+                source:      types::SourceAddress { from_node: Random::generate_random(), from_group: None, reply_to: None },
+                authority:   Authority::ManagedNode,
+                ttl:         DEFAULT_TTL
+            };
+            assert!(routing_node.handle_put_key(header, encode(put_key).unwrap()).is_ok());
+        }
+        assert_eq!(routing_node.public_key_cache.len(), 2);
+        assert!(!routing_node.public_key_cache.check(&put_keys[0].target_id));
+    }
+
+    #[test]
+    fn oversized_message_not_sent() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        routing_node.set_max_message_size(1000);
+        let chunk = Box::new(TestData::new(vec![7u8; 1000]));
+        match routing_node.put(Random::generate_random(), chunk, true) {
+            Err(RoutingError::MessageTooLarge(size)) => assert!(size > 1000),
+            _ => panic!(),
+        }
+        assert!(stats.lock().unwrap().sent.is_empty());
+    }
+
+    #[test]
+    fn oversized_message_not_received() {
+        let (event_sender, events) = ::std::sync::mpsc::channel();
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { events: Some(event_sender), ..RoutingConfig::default() }).unwrap();
+        routing_node.set_max_message_size(1000);
+        let peer = Pmid::new();
+        let header = MessageHeader::new(random::<u32>(),
+            types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            types::SourceAddress { from_node: peer.get_name(), from_group: None, reply_to: None },
+            Authority::ManagedNode);
+        let put_data = PutData { name: Random::generate_random(), data: vec![7u8; 1000] };
+        let message = RoutingMessage::new(MessageTypeTag::PutData, header, put_data,
+                                          &peer.get_crypto_secret_sign_key());
+        // however well it compresses, it is refused once inflated
+        for threshold in vec![None, Some(100)].into_iter() {
+            let packed = compression::pack(&encode(&message).unwrap(), threshold);
+            routing_node.handle_new_message(random_endpoint(), packed);
+            match events.try_recv() {
+                Ok(RoutingEvent::MessageDropped(RoutingError::MessageTooLarge(size))) =>
+                    assert!(size > 1000),
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn lost_peer_reconnected() {
+        let (event_sender, events) = ::std::sync::mpsc::channel();
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { events: Some(event_sender), ..RoutingConfig::default() }).unwrap();
+        routing_node.set_reconnect(2, Duration::zero());
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()), vec![random_endpoint()], None);
+        assert!(routing_node.add_node(peer.clone()).0);
+        routing_node.handle_connect(peer.endpoints[0].clone());
+
+        // a peer lost and reached again is back in the routing table
+        routing_node.handle_lost_connection(peer.endpoints[0].clone());
+        assert!(routing_node.routing_table.all_nodes().is_empty());
+        assert_eq!(routing_node.reconnecting.get(&peer.id()).map(|entry| entry.1), Some(1));
+        routing_node.handle_connect(peer.endpoints[0].clone());
+        assert!(routing_node.is_connected(&peer.id()));
+        assert!(routing_node.reconnecting.is_empty());
+
+        // one which stays away is given up on once it has had its attempts
+        routing_node.handle_lost_connection(peer.endpoints[0].clone());
+        routing_node.retry_reconnects();
+        assert_eq!(routing_node.reconnecting.get(&peer.id()).map(|entry| entry.1), Some(2));
+        routing_node.retry_reconnects();
+        assert!(routing_node.reconnecting.is_empty());
+        let mut given_up = 0;
+        while let Ok(event) = events.try_recv() {
+            if let RoutingEvent::ReconnectFailed(peer_id) = event {
+                assert_eq!(peer_id, peer.id());
+                given_up += 1;
+            }
+        }
+        assert_eq!(given_up, 1);
+    }
+
+    #[test]
+    fn distance_from_us() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let name = |first: u8, last: u8| {
+            let mut id = [0u8; NAME_TYPE_LEN];
+            id[0] = first;
+            id[NAME_TYPE_LEN - 1] = last;
+            NameType::new(id)
+        };
+        routing_node.own_id = name(0x80, 0);
+        assert_eq!(routing_node.distance_to(&name(0x80, 0)), name(0, 0));
+        assert_eq!(routing_node.distance_to(&name(0x81, 3)), name(0x01, 3));
+        assert_eq!(routing_node.distance_to(&name(0, 0)), name(0x80, 0));
+
+        // the first byte outweighs all those after it
+        assert!(routing_node.is_closer(&name(0x81, 0xff), &name(0x82, 0)));
+        assert!(routing_node.is_closer(&name(0xff, 0), &name(0x7f, 0)));
+        assert!(routing_node.is_closer(&name(0x80, 1), &name(0x80, 2)));
+        assert!(!routing_node.is_closer(&name(0x80, 2), &name(0x80, 1)));
+        assert!(!routing_node.is_closer(&name(0x80, 1), &name(0x80, 1)));
+    }
+
+    #[test]
+    fn uncacheable_type_not_cached() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::with_group_size(TestInterface { stats: stats.clone() }, 1).unwrap();
+        routing_node.set_cacheable_type_ids(Some(vec![1u64].into_iter().collect()));
+        let peer = routing_table::NodeInfo::new(PublicPmid::new(&Pmid::new()), vec![random_endpoint()], None);
+        assert!(routing_node.add_node(peer).0);
+        let sender = Pmid::new();
+        routing_node.public_pmid_cache.add(sender.get_name(), PublicPmid::new(&sender));
+        // the furthest name from us, so the response is only passed on
+        let mut far = routing_node.own_id.get_id();
+        for byte in far.iter_mut() {
+            *byte = !*byte;
+        }
+        let respond = |routing_node: &mut RoutingNode<TestInterface>, type_id: u64| {
+            let header = MessageHeader::new(random::<u32>(),
+                types::DestinationAddress { dest: NameType::new(far), reply_to: None },
+                types::SourceAddress { from_node: sender.get_name(), from_group: None, reply_to: None },
+                Authority::NaeManager);
+            let response = GetDataResponse {
+                name_and_type_id: types::NameAndTypeId { name: Random::generate_random(), type_id: type_id },
+                data: Ok(vec![1u8, 2, 3]), part: 0, total: 1 };
+            let message = RoutingMessage::new(MessageTypeTag::GetDataResponse, header, response,
+                                              &sender.get_crypto_secret_sign_key());
+            assert!(routing_node.message_received(&sender.get_name(), encode(&message).unwrap()).is_ok());
+        };
+        respond(&mut routing_node, 2);
+        assert!(stats.lock().unwrap().data.is_empty());
+        respond(&mut routing_node, 1);
+        assert_eq!(stats.lock().unwrap().data, "handle_cache_put called".to_string().into_bytes());
+        assert_eq!(stats.lock().unwrap().call_count, 0);
+    }
+
+    #[test]
+    fn with_pmid_keeps_identity() {
+        let pmid = Pmid::new();
+        let persisted: Pmid = decode(&encode(&pmid).unwrap()).unwrap();
+
+        let node = RoutingNode::with_pmid(TestInterface { stats: Arc::new(Mutex::new(
+            Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) }, persisted);
+        assert_eq!(node.own_id, pmid.get_name());
+        assert!(node.pmid.get_public_sign_key() == pmid.get_public_sign_key());
+    }
+
+    #[test]
+    fn lone_bootstrap_node_makes_first_node() {
+        let (event_sender, events) = mpsc::channel();
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: stats.clone() },
+            RoutingConfig { events: Some(event_sender), ..RoutingConfig::default() }).unwrap();
+        let mut bootstrap_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        routing_node.add_known_node(bootstrap_node.own_id.clone(), random_endpoint(), None);
+
+        // the bootstrap node answers our search for our own group
+        let find_group = FindGroup { requester_id: routing_node.own_id.clone(),
+                                     target_id: routing_node.own_id.clone() };
+        let request_header = MessageHeader::new(random::<u32>(),
+            types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            routing_node.our_source_address(), Authority::ManagedNode);
+        let group = bootstrap_node.find_group_members();
+        assert_eq!(group.len(), 1);
+        let response = bootstrap_node.construct_find_group_response_msg(&request_header, &find_group,
+                                                                         group);
+        assert!(!routing_node.is_first_node());
+        assert!(routing_node.handle_find_group_response(response.message_header.clone(),
+                                                        response.serialised_body.clone()).is_ok());
+        assert!(routing_node.is_first_node());
+
+        // told once only
+        assert!(routing_node.handle_find_group_response(response.message_header.clone(),
+                                                        response.serialised_body.clone()).is_ok());
+        let mut first_node_events = 0;
+        while let Ok(event) = events.try_recv() {
+            if let RoutingEvent::FirstNode = event {
+                first_node_events += 1;
+            }
+        }
+        assert_eq!(first_node_events, 1);
+
+        // a group with anyone else in it means the network is already there
+        let mut joining_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        joining_node.add_known_node(bootstrap_node.own_id.clone(), random_endpoint(), None);
+        let find_group = FindGroup { requester_id: joining_node.own_id.clone(),
+                                     target_id: joining_node.own_id.clone() };
+        let request_header = MessageHeader::new(random::<u32>(),
+            types::DestinationAddress { dest: joining_node.own_id.clone(), reply_to: None },
+            joining_node.our_source_address(), Authority::ManagedNode);
+        let mut group = bootstrap_node.find_group_members();
+        group.push(PublicPmid::new(&Pmid::new()));
+        let response = bootstrap_node.construct_find_group_response_msg(&request_header, &find_group,
+                                                                         group);
+        assert!(joining_node.handle_find_group_response(response.message_header,
+                                                        response.serialised_body).is_ok());
+        assert!(!joining_node.is_first_node());
+    }
+
+    #[test]
+    fn message_stats_count_received() {
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let mut routing_node = RoutingNode::new(TestInterface { stats: stats.clone() });
+        let sender = Pmid::new();
+        routing_node.public_pmid_cache.add(sender.get_name(), PublicPmid::new(&sender));
+        let header = MessageHeader {
+            message_id:  random::<u32>(),
+            destination: types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            source:      types::SourceAddress { from_node: sender.get_name(), from_group: None,
+                                                reply_to: None },
+            authority:   Authority::NaeManager,
+            ttl:         DEFAULT_TTL
+        };
+        let put_data: PutData = Random::generate_random();
+        let message = RoutingMessage::new(MessageTypeTag::PutData, header, put_data,
+                                          &sender.get_crypto_secret_sign_key());
+        // counted whether or not handling it goes on to succeed
+        let _ = routing_node.message_received(&sender.get_name(), encode(&message).unwrap());
+        let message_stats = routing_node.message_stats();
+        assert_eq!(message_stats.received(&MessageTypeTag::PutData), 1);
+        assert_eq!(message_stats.received(&MessageTypeTag::FindGroup), 0);
+        // with an empty routing table nothing could be sent on
+        assert_eq!(message_stats.sent(&MessageTypeTag::PutData), 0);
+    }
+
+    #[test]
+    fn unanswered_get_times_out() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let (message_id, result) = pending_get(&mut routing_node);
+        routing_node.expire_pending_gets();
+        assert!(result.try_recv().is_err());
+        assert!(routing_node.pending_gets.contains_key(&message_id));
+
+        routing_node.pending_gets.get_mut(&message_id).unwrap().1 = SteadyTime::now();
+        routing_node.expire_pending_gets();
+        assert_eq!(result.try_recv(), Ok(Err(ResponseError::Timeout)));
+        assert!(routing_node.pending_gets.is_empty());
+    }
+
+    #[test]
+    fn dropped_node_closes_connections() {
+        // nodes made and dropped in turn, as the multi-node tests do
+        for _ in 0..3 {
+            let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+            routing_node.add_known_node(Pmid::new().get_name(), random_endpoint(), None);
+        }
+
+        // what a drop does to a node which wasn't stopped
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        let peer = Pmid::new();
+        routing_node.add_known_node(peer.get_name(), random_endpoint(), None);
+        routing_node.close();
+        assert!(routing_node.is_stopped());
+        assert!(routing_node.connection_for(&peer.get_name()).is_none());
+
+        // dropping after an explicit stop does no more
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        routing_node.stop();
+        drop(routing_node);
+    }
+
+    #[test]
+    fn put_sent_to_destination_group() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        for _ in 0..100 {
+            let _ = routing_node.routing_table.add_node(routing_table::NodeInfo::new(
+                PublicPmid::new(&Pmid::new()), vec![random_endpoint()], None));
+        }
+        for node in routing_node.routing_table.all_nodes() {
+            assert!(routing_node.routing_table.mark_as_connected(&node.endpoints[0]));
+            routing_node.all_connections.0.insert(node.endpoints[0].clone(), node.id());
+            routing_node.all_connections.1.insert(node.id(), node.endpoints[0].clone());
+        }
+        // the node furthest from us is well outside our close group
+        let destination = routing_node.routing_table.all_nodes().last().unwrap().id();
+        let mut expected = routing_node.routing_table.target_nodes(destination.clone()).iter()
+                                       .map(|node| node.id()).collect::<Vec<_>>();
+        expected.sort();
+
+        // without real connections every send fails, leaving a retry held for each peer tried
+        let chunk = Box::new(TestData::new(vec![7u8; 10]));
+        let _ = routing_node.put(destination, chunk, true);
+        let mut tried = routing_node.pending_retries.keys().cloned().collect::<Vec<_>>();
+        tried.sort();
+        assert_eq!(tried, expected);
+    }
+
+    #[test]
+    fn bincode_node_refuses_cbor() {
+        let (event_sender, events) = mpsc::channel();
+        let mut routing_node = RoutingNode::with_config(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) },
+            RoutingConfig { serialisation: Serialisation::Bincode, events: Some(event_sender),
+                            ..RoutingConfig::default() }).unwrap();
+        let peer = Pmid::new();
+        let peer_endpoint = random_endpoint();
+        routing_node.public_pmid_cache.add(peer.get_name(), PublicPmid::new(&peer));
+        routing_node.all_connections.0.insert(peer_endpoint.clone(), peer.get_name());
+        routing_node.all_connections.1.insert(peer.get_name(), peer_endpoint.clone());
+        let header = MessageHeader::new(random::<u32>(),
+            types::DestinationAddress { dest: routing_node.own_id.clone(), reply_to: None },
+            types::SourceAddress { from_node: peer.get_name(), from_group: None, reply_to: None },
+            Authority::NaeManager);
+        let put_data: PutData = Random::generate_random();
+        let message = RoutingMessage::new(MessageTypeTag::PutData, header, put_data,
+                                          &peer.get_crypto_secret_sign_key());
+
+        let bytes = Serialisation::Bincode.serialise(&message).unwrap();
+        routing_node.handle_new_message(peer_endpoint.clone(),
+                                        compression::pack_as(&bytes, Serialisation::Bincode, None));
+        assert_eq!(routing_node.message_stats().received(&MessageTypeTag::PutData), 1);
+
+        while let Ok(_) = events.try_recv() {}
+        routing_node.handle_new_message(peer_endpoint, compression::pack(&encode(&message).unwrap(), None));
+        assert_eq!(routing_node.message_stats().received(&MessageTypeTag::PutData), 1);
+        match events.try_recv() {
+            Ok(RoutingEvent::MessageDropped(RoutingError::SerialisationMismatch(format))) =>
+                assert_eq!(format, Serialisation::Cbor.format_byte()),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn routing_table_stats() {
+        let mut routing_node = RoutingNode::with_group_size(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) }, 4).unwrap();
+        assert_eq!(routing_node.routing_table_stats(), (0, 4, routing_table::RoutingTable::get_optimal_size()));
+        for _ in 0..3 {
+            let _ = routing_node.routing_table.add_node(routing_table::NodeInfo::new(
+                PublicPmid::new(&Pmid::new()), vec![random_endpoint()], None));
+        }
+        assert_eq!(routing_node.routing_table_stats().0, 3);
+    }
+
+    #[test]
+    fn cache_get_outcomes() {
+        let get_data = |type_id: u64| {
+            let mut get_data: GetData = Random::generate_random();
+            get_data.name_and_type_id.type_id = type_id;
+            get_data
+        };
+
+        // a hit is answered from the cache
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let result = call_operation(get_data(CACHE_HIT_TYPE_ID), MessageTypeTag::GetData, stats);
+        assert_eq!(result.call_count, 0);
+        assert_eq!(result.sent, vec![MessageTypeTag::GetDataResponse]);
+
+        // the request is sent where the cache says, and not handled here
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let result = call_operation(get_data(CACHE_SEND_ON_TYPE_ID), MessageTypeTag::GetData, stats);
+        assert_eq!(result.call_count, 0);
+        assert_eq!(result.sent, vec![MessageTypeTag::GetData]);
+
+        // an error is given back to the requester
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let result = call_operation(get_data(CACHE_ERROR_TYPE_ID), MessageTypeTag::GetData, stats);
+        assert_eq!(result.call_count, 0);
+        assert_eq!(result.sent, vec![MessageTypeTag::GetDataResponse]);
+
+        // a miss goes on to be handled as usual
+        let stats = Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]}));
+        let result = call_operation(get_data(100u64), MessageTypeTag::GetData, stats);
+        assert_eq!(result.call_count, 1);
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let mut routing_node = RoutingNode::new(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        for _ in 0..20 {
+            routing_node.routing_table.add_node(routing_table::NodeInfo::new(
+                                       PublicPmid::new(&Pmid::new()), random_endpoints(),
+                                       Some(random_endpoint())));
+        }
+        let peer_id : NameType = Random::generate_random();
+        let peer_endpoint = random_endpoint();
+        routing_node.all_connections.0.insert(peer_endpoint.clone(), peer_id.clone());
+        routing_node.all_connections.1.insert(peer_id.clone(), peer_endpoint.clone());
+
+        let snapshot = routing_node.snapshot();
+        let decoded_snapshot = decode::<NodeSnapshot>(&encode(&snapshot).unwrap()).unwrap();
+        assert_eq!(snapshot, decoded_snapshot);
+
+        let restored = RoutingNode::restore(decoded_snapshot,
+            TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        assert_eq!(restored.id(), routing_node.id());
+        assert_eq!(restored.routing_table.size(), routing_node.routing_table.size());
+        let original_nodes = routing_node.routing_table.all_nodes();
+        let restored_nodes = restored.routing_table.all_nodes();
+        for (original, restored) in original_nodes.iter().zip(restored_nodes.iter()) {
+            assert_eq!(original.id(), restored.id());
+            assert_eq!(original.endpoints, restored.endpoints);
+            assert_eq!(original.connected_endpoint, restored.connected_endpoint);
+        }
+        assert_eq!(restored.all_connections.1.get(&peer_id), Some(&peer_endpoint));
+        assert_eq!(restored.all_connections.0.get(&peer_endpoint), Some(&peer_id));
+    }
+
+    #[test]
+    fn restored_node_signatures() {
+        let pmid = Pmid::new();
+        let routing_node = RoutingNode::with_pmid(TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) }, pmid.clone());
+        let snapshot = routing_node.snapshot();
+        let public_sign_key = snapshot.public_pmid.public_sign_key.clone();
+        let post: Post = Random::generate_random();
+        let signed_by = |node: &mut RoutingNode<TestInterface>| {
+            let header = MessageHeader::new(node.get_next_message_id().unwrap(),
+                types::DestinationAddress { dest: Random::generate_random(), reply_to: None },
+                node.our_source_address(), Authority::ManagedNode);
+            RoutingMessage::new(MessageTypeTag::Post, header, post.clone(),
+                                &node.pmid.get_crypto_secret_sign_key())
+        };
+
+        // without its secret keys a restored node can't sign as the original
+        let mut restored = RoutingNode::restore(snapshot.clone(),
+            TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        assert_eq!(restored.id(), snapshot.own_id);
+        assert!(!signed_by(&mut restored).verify(&public_sign_key));
+
+        // given them back, it can
+        let mut restored = RoutingNode::restore_with_pmid(snapshot.clone(), pmid,
+            TestInterface { stats: Arc::new(Mutex::new(Stats {call_count: 0, data: vec![], sent: vec![], churn: vec![]})) });
+        assert_eq!(restored.id(), snapshot.own_id);
+        assert!(signed_by(&mut restored).verify(&public_sign_key));
+    }
+
     //#[test]
     //fn test_routing_node() {
     //    let f1 = NullInterface;
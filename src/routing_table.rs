@@ -83,11 +83,18 @@ impl NodeInfo {
 pub struct RoutingTable {
     routing_table: Vec<NodeInfo>,
     our_id: NameType,
+    group_size: usize,
 }
 
 impl RoutingTable {
     pub fn new(our_id: NameType) -> RoutingTable {
-        RoutingTable { routing_table: Vec::<NodeInfo>::new(), our_id: our_id }
+        RoutingTable::with_group_size(our_id, types::GROUP_SIZE)
+    }
+
+    /// Creates a table with a close group of `group_size` contacts instead of `GROUP_SIZE`.
+    pub fn with_group_size(our_id: NameType, group_size: usize) -> RoutingTable {
+        assert!(group_size >= 1, "group size must be at least 1");
+        RoutingTable { routing_table: Vec::<NodeInfo>::new(), our_id: our_id, group_size: group_size }
     }
 
     pub fn get_bucket_size() -> usize { BUCKET_SIZE }
@@ -100,6 +107,15 @@ impl RoutingTable {
 
     pub fn get_quorum_size() -> usize { types::QUORUM_SIZE }
 
+    /// The size of the close group of this table.
+    pub fn group_size(&self) -> usize { self.group_size }
+
+    /// The quorum for this table's close group, scaled from `QUORUM_SIZE` in proportion to the
+    /// group size and rounded up.
+    pub fn quorum_size(&self) -> usize {
+        (self.group_size * types::QUORUM_SIZE + types::GROUP_SIZE - 1) / types::GROUP_SIZE
+    }
+
     /// Adds a contact to the routing table.  If the contact is added, the first return arg is true,
     /// otherwise false.  If adding the contact caused another contact to be dropped, the dropped
     /// one is returned in the second field, otherwise the optional field is empty.  The following
@@ -127,7 +143,7 @@ impl RoutingTable {
         }
 
         if closer_to_target(&their_info.id(),
-                            &self.routing_table[RoutingTable::get_group_size()].id(),
+                            &self.routing_table[self.group_size].id(),
                             &self.our_id) {
             self.push_back_then_sort(their_info);
             let removal_node_index = self.find_candidate_for_removal();
@@ -172,6 +188,26 @@ impl RoutingTable {
         }
     }
 
+    /// This changes the connected status of the peer back to false, keeping it in the table, e.g.
+    /// while it is being redialled.  Returns false if the peer isn't in the table.
+    pub fn mark_as_disconnected(&mut self, peer_id: &NameType) -> bool {
+        match self.routing_table.iter().position(|node_info| node_info.id() == *peer_id) {
+            None => false,
+            Some(index) => {
+                self.routing_table[index].connected_endpoint = None;
+                true
+            },
+        }
+    }
+
+    /// This returns the ID of the contact listing the given endpoint amongst its endpoints.  A peer
+    /// may advertise several endpoints and a connection on any of them identifies it.
+    pub fn lookup_endpoint(&self, endpoint: &Endpoint) -> Option<NameType> {
+        self.routing_table.iter()
+            .find(|node_info| node_info.endpoints.iter().any(|candidate| candidate == endpoint))
+            .map(|node_info| node_info.id())
+    }
+
     /// This is used to check whether it is worth while retrieving a contact's public key from the
     /// PKI with a view to adding the contact to our routing table.  The checking procedure is the
     /// same as for 'AddNode' above, except for the lack of a public key to check in step 1.
@@ -186,7 +222,7 @@ impl RoutingTable {
         if self.routing_table.len() < RoutingTable::get_optimal_size() {
             return true;
         }
-        let group_size = RoutingTable::get_group_size() - 1;
+        let group_size = self.group_size - 1;
         let thier_id_clone = their_id.clone();
         if closer_to_target(&their_id, &self.routing_table[group_size].id(), &self.our_id) {
             return true;
@@ -227,7 +263,7 @@ impl RoutingTable {
         };
 
         for iter in self.routing_table.iter() {
-            if iterations < RoutingTable::get_group_size() {
+            if iterations < self.group_size {
                 our_close_group.push(iter.clone());
             }
             closest_to_target.push(iter.clone());
@@ -260,8 +296,7 @@ impl RoutingTable {
     /// This returns our close group, i.e. the 'GroupSize' contacts closest to our ID (or the entire
     /// table if we hold less than 'GroupSize' contacts in total).
     pub fn our_close_group(&self) -> Vec<NodeInfo> {
-        let group_size = RoutingTable::get_group_size();
-        let size = cmp::min(group_size, self.routing_table.len());
+        let size = cmp::min(self.group_size, self.routing_table.len());
         let mut result = Vec::new();
         for i in 0..size {
             // is cloning advisable?
@@ -270,6 +305,19 @@ impl RoutingTable {
         result
     }
 
+    /// The IDs of our close group, sorted, so that snapshots taken at different times can be
+    /// compared directly.
+    pub fn close_group_snapshot(&self) -> Vec<NameType> {
+        let mut close_group = self.our_close_group().iter().map(|node| node.id()).collect::<Vec<_>>();
+        close_group.sort();
+        close_group
+    }
+
+    /// This returns a copy of every contact in the table, sorted closest to our ID first.
+    pub fn all_nodes(&self) -> Vec<NodeInfo> {
+        self.routing_table.clone()
+    }
+
     /// This returns the public key for the given node if the node is in our table.
     pub fn get_public_key(&self, their_id: NameType)->Option<crypto::asymmetricbox::PublicKey> {
 
@@ -287,6 +335,13 @@ impl RoutingTable {
         }
     }
 
+    /// This returns the public sign key of the given node if the node is in our routing table.
+    pub fn get_public_sign_key(&self, their_id: &NameType) -> Option<types::PublicSignKey> {
+        self.routing_table.iter()
+            .find(|node_info| node_info.id() == *their_id)
+            .map(|node_info| node_info.fob.public_sign_key.clone())
+    }
+
     /// This returns the length of the routing table.
     pub fn size(&self)->usize {
         //std::lock_guard<std::mutex> lock(mutex_);
@@ -298,10 +353,10 @@ impl RoutingTable {
     /// close group. If the routing table contains less than GroupSize nodes, then every address is
     /// considered to be in our close group range.
     pub fn address_in_our_close_group_range(&self, id: &NameType) -> bool {
-        if self.routing_table.len() < types::GROUP_SIZE {
+        if self.routing_table.len() < self.group_size {
             return true;
         }
-        let furthest_close_node = self.routing_table[types::GROUP_SIZE - 1].clone();
+        let furthest_close_node = self.routing_table[self.group_size - 1].clone();
         closer_to_target_or_equal(&id, &furthest_close_node.id(), &self.our_id)
     }
 
@@ -317,7 +372,7 @@ impl RoutingTable {
 
         // Stop iterating at our furthest close group member since we won't remove any peer in our
         // close group
-        let finish = RoutingTable::get_group_size();
+        let finish = self.group_size;
 
         while counter >= finish {
             let bucket_index = self.bucket_index(&self.routing_table[counter].id());
@@ -493,9 +548,7 @@ mod test {
             let node_info = create_random_node_info();
             let table = RoutingTableUnitTest {
                 our_id: node_info.id().clone(),
-                table: RoutingTable {
-                    our_id: node_info.id().clone(), routing_table: Vec::new(),
-                },
+                table: RoutingTable::new(node_info.id().clone()),
                 buckets: initialise_buckets(&node_info.id()),
                 node_info: node_info,
                 initial_count: (rand::random::<usize>() % (RoutingTable::get_group_size() - 1)) + 1,
@@ -591,7 +644,7 @@ mod test {
 
         let mut vector: Vec<RoutingTable> = Vec::with_capacity(num_of_tables);
         for i in 0..num_of_tables {
-            vector.push(RoutingTable { routing_table: Vec::new(), our_id: Random::generate_random() });
+            vector.push(RoutingTable::new(Random::generate_random()));
         }
         vector
     }
@@ -617,10 +670,7 @@ mod test {
     #[test]
     fn routing_table_test() {
 
-        let mut table = RoutingTable {
-            routing_table: Vec::new(),
-            our_id: Random::generate_random()
-        };
+        let mut table = RoutingTable::new(Random::generate_random());
 
         for i in 0..RoutingTable::get_group_size() {
             let id = Random::generate_random();
@@ -1262,6 +1312,39 @@ mod test {
         }
     }
 
+    #[test]
+    fn custom_group_size() {
+        let mut table = RoutingTable::with_group_size(Random::generate_random(), 4);
+        assert_eq!(table.group_size(), 4);
+        assert_eq!(table.quorum_size(), 3);
+        assert_eq!(RoutingTable::new(Random::generate_random()).quorum_size(),
+                   RoutingTable::get_quorum_size());
+
+        for _ in 0..10 {
+            assert!(table.add_node(create_random_node_info()).0);
+        }
+        assert_eq!(table.our_close_group().len(), 4);
+        assert_eq!(table.target_nodes(table.routing_table[0].id()).len(), 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_group_size() {
+        let _ = RoutingTable::with_group_size(Random::generate_random(), 0);
+    }
+
+    #[test]
+    fn mark_as_disconnected() {
+        let mut table = RoutingTable::new(Random::generate_random());
+        let node_info = create_random_node_info();
+        let endpoint = node_info.endpoints[0].clone();
+        assert!(table.add_node(node_info.clone()).0);
+        assert!(table.mark_as_connected(&endpoint));
+        assert!(table.mark_as_disconnected(&node_info.id()));
+        assert!(table.all_nodes()[0].connected_endpoint.is_none());
+        assert!(!table.mark_as_disconnected(&Random::generate_random()));
+    }
+
     #[test]
     fn trivial_functions_test() {
         let mut table_unit_test = RoutingTableUnitTest::new();
@@ -0,0 +1,105 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! A fixed number of threads over which the sends of one message to several peers are spread, so
+//! that a peer slow to take its copy doesn't hold up the rest.
+
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use crust::{ConnectionManager, Endpoint};
+
+type Bytes = Vec<u8>;
+
+/// One send, and where to report how it went
+struct Job {
+    index: usize,
+    endpoint: Endpoint,
+    bytes: Bytes,
+    result: Sender<(usize, io::Result<()>)>,
+}
+
+pub struct SendPool {
+    jobs: Sender<Job>,
+}
+
+impl SendPool {
+    /// Starts `threads` workers sending over `connection_manager`.  They stop once the pool is
+    /// dropped.
+    pub fn new(threads: usize, connection_manager: Arc<ConnectionManager>) -> SendPool {
+        let (jobs, job_input) = mpsc::channel::<Job>();
+        let job_input = Arc::new(Mutex::new(job_input));
+        for _ in 0..threads {
+            let job_input = job_input.clone();
+            let connection_manager = connection_manager.clone();
+            let _ = thread::spawn(move || loop {
+                let job = match job_input.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => break,
+                };
+                let result = connection_manager.send(job.endpoint, job.bytes);
+                let _ = job.result.send((job.index, result));
+            });
+        }
+        SendPool { jobs: jobs }
+    }
+
+    /// Hands each message to the workers and waits for all of them to be sent.  Returns the
+    /// outcome of every send, in the order given.
+    pub fn send_all(&self, sends: Vec<(Endpoint, Bytes)>) -> Vec<io::Result<()>> {
+        let mut outcomes = sends.iter().map(|_| None).collect::<Vec<Option<io::Result<()>>>>();
+        let (result_sender, results) = mpsc::channel();
+        for (index, (endpoint, bytes)) in sends.into_iter().enumerate() {
+            let _ = self.jobs.send(Job { index: index, endpoint: endpoint, bytes: bytes,
+                                         result: result_sender.clone() });
+        }
+        // once every job has reported, or been lost with its worker, the results run out
+        drop(result_sender);
+        for (index, result) in results.iter() {
+            outcomes[index] = Some(result);
+        }
+        outcomes.into_iter().map(|outcome| outcome.unwrap_or_else(|| {
+            Err(io::Error::new(io::ErrorKind::Other, "send abandoned"))
+        })).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crust;
+    use crust::Endpoint;
+    use std::sync::Arc;
+    use std::sync::mpsc;
+
+    #[test]
+    fn outcomes_in_order() {
+        let (event_output, _event_input) = mpsc::channel();
+        let pool = SendPool::new(2, Arc::new(crust::ConnectionManager::new(event_output)));
+        assert!(pool.send_all(vec![]).is_empty());
+
+        // with no connections, every send fails and is reported as such
+        let sends = (0..5u16).map(|port| {
+            (Endpoint::Tcp(format!("127.0.0.1:{}", 5600 + port).parse().unwrap()), vec![port as u8])
+        }).collect::<Vec<_>>();
+        let outcomes = pool.send_all(sends);
+        assert_eq!(outcomes.len(), 5);
+        assert!(outcomes.iter().all(|outcome| outcome.is_err()));
+    }
+}
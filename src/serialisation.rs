@@ -0,0 +1,99 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! The formats a node may encode the messages it puts on the wire in.  Message bodies are signed
+//! as they are and so stay CBOR whichever format the message around them is in.
+
+use bincode;
+use cbor::{CborError, Decoder, Encoder};
+use rustc_serialize::{Decodable, Encodable};
+use error::RoutingError;
+
+type Bytes = Vec<u8>;
+
+/// A message encoding, chosen per node with `RoutingConfig::serialisation`.  Every node of a
+/// network must use the same one: a message in another format is refused on arrival.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Serialisation {
+    /// The default, and the only format clients speak
+    Cbor,
+    /// Smaller and faster to encode than CBOR for our fixed layout messages
+    Bincode,
+}
+
+impl Serialisation {
+    /// The byte identifying this format in the wire envelope
+    pub fn format_byte(&self) -> u8 {
+        match *self {
+            Serialisation::Cbor => 0,
+            Serialisation::Bincode => 1,
+        }
+    }
+
+    pub fn serialise<T: Encodable>(&self, value: &T) -> Result<Bytes, RoutingError> {
+        match *self {
+            Serialisation::Cbor => {
+                let mut encoder = Encoder::from_memory();
+                try!(encoder.encode(&[value]));
+                Ok(encoder.into_bytes())
+            },
+            Serialisation::Bincode => bincode::encode(value, bincode::SizeLimit::Infinite)
+                .map_err(|error| RoutingError::BincodeError(format!("{:?}", error))),
+        }
+    }
+
+    pub fn deserialise<T: Decodable>(&self, bytes: &Bytes) -> Result<T, RoutingError> {
+        match *self {
+            Serialisation::Cbor => {
+                let mut decoder = Decoder::from_bytes(&bytes[..]);
+                match decoder.decode().next() {
+                    Some(result) => result.map_err(From::from),
+                    None => Err(RoutingError::CborError(CborError::UnexpectedEOF)),
+                }
+            },
+            Serialisation::Bincode => bincode::decode(&bytes[..])
+                .map_err(|error| RoutingError::BincodeError(format!("{:?}", error))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sodiumoxide::crypto;
+    use messages::{RoutingMessage, MessageTypeTag};
+    use messages::put_data::PutData;
+    use message_header::MessageHeader;
+    use test_utils::Random;
+    use types;
+
+    #[test]
+    fn routing_message_round_trip() {
+        let (_, secret_key) = crypto::sign::gen_keypair();
+        let header = MessageHeader::new(1u32,
+            types::DestinationAddress { dest: Random::generate_random(), reply_to: None },
+            Random::generate_random(), types::Authority::NaeManager);
+        let put_data: PutData = Random::generate_random();
+        let message = RoutingMessage::new(MessageTypeTag::PutData, header, put_data, &secret_key);
+
+        for serialisation in vec![Serialisation::Cbor, Serialisation::Bincode].into_iter() {
+            let bytes = serialisation.serialise(&message).unwrap();
+            let decoded: RoutingMessage = serialisation.deserialise(&bytes).unwrap();
+            assert_eq!(decoded, message);
+        }
+    }
+}
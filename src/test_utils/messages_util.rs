@@ -90,6 +90,29 @@ impl Random for messages::connect_success::ConnectSuccess {
     }
 }
 
+impl Random for messages::delete_data::DeleteData {
+    fn generate_random() -> messages::delete_data::DeleteData {
+        messages::delete_data::DeleteData {
+            name: Random::generate_random(),
+        }
+    }
+}
+
+impl Random for messages::delete_data_response::DeleteDataResponse {
+     fn generate_random() -> messages::delete_data_response::DeleteDataResponse {
+         let result = if random::<bool>() {
+             Ok(())
+         } else {
+             Err(ResponseError::NoData)
+         };
+
+         messages::delete_data_response::DeleteDataResponse {
+             name: Random::generate_random(),
+             result: result,
+         }
+    }
+}
+
 impl Random for messages::find_group::FindGroup {
     fn generate_random() -> messages::find_group::FindGroup {
         messages::find_group::FindGroup {
@@ -125,7 +148,7 @@ impl Random for messages::get_client_key_response::GetKeyResponse {
     fn generate_random() -> messages::get_client_key_response::GetKeyResponse {
         messages::get_client_key_response::GetKeyResponse {
             address: Random::generate_random(),
-            public_sign_key: Random::generate_random(),
+            public_sign_key: Ok(Random::generate_random()),
         }
     }
 }
@@ -144,6 +167,8 @@ impl Random for messages::get_data_response::GetDataResponse {
         messages::get_data_response::GetDataResponse {
             name_and_type_id: Random::generate_random(),
             data: Ok(generate_random_vec_u8(99)),
+            part: 0,
+            total: 1,
         }
     }
 }
@@ -170,6 +195,14 @@ impl Random for messages::get_group_key_response::GetGroupKeyResponse {
     }
 }
 
+impl Random for messages::ping::Ping {
+    fn generate_random() -> messages::ping::Ping {
+        messages::ping::Ping {
+            sender_id: Random::generate_random(),
+        }
+    }
+}
+
 impl Random for messages::post::Post {
     fn generate_random() -> messages::post::Post {
         messages::post::Post {
@@ -179,6 +212,21 @@ impl Random for messages::post::Post {
     }
 }
 
+impl Random for messages::post_response::PostResponse {
+     fn generate_random() -> messages::post_response::PostResponse {
+         let data = if random::<bool>() {
+             Ok(generate_random_vec_u8(99))
+         } else {
+             Err(ResponseError::NoData)
+         };
+
+         messages::post_response::PostResponse {
+             name: Random::generate_random(),
+             data: data,
+         }
+    }
+}
+
 impl Random for messages::put_data::PutData {
     fn generate_random() -> messages::put_data::PutData {
         messages::put_data::PutData {
@@ -203,6 +251,15 @@ impl Random for messages::put_data_response::PutDataResponse {
     }
 }
 
+impl Random for messages::put_key::PutKey {
+    fn generate_random() -> messages::put_key::PutKey {
+        messages::put_key::PutKey {
+            target_id: Random::generate_random(),
+            public_sign_key: Random::generate_random(),
+        }
+    }
+}
+
 impl Random for messages::put_public_pmid::PutPublicPmid {
     fn generate_random() -> messages::put_public_pmid::PutPublicPmid {
         let public_pmid : PublicPmid = Random::generate_random();
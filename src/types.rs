@@ -61,6 +61,10 @@ pub fn generate_random_vec_u8(size: usize) -> Vec<u8> {
 
 pub static GROUP_SIZE: usize = 32;
 pub static QUORUM_SIZE: usize = 19;
+/// Version of the wire protocol, exchanged when bootstrapping.  Peers must match it exactly.
+pub static PROTOCOL_VERSION: u32 = 1;
+/// Default limit, in bytes, on the size of a serialised message sent or accepted
+pub static MAX_MESSAGE_SIZE: usize = 1 << 21;
 
 pub trait Mergeable {
     fn merge<'a, I>(xs: I) -> Option<Self> where I: Iterator<Item=&'a Self>;
@@ -351,6 +355,47 @@ impl Pmid {
   }
 }
 
+/// Carries the secret keys, so that a node can keep its identity across restarts.  Whoever
+/// reads an encoded `Pmid` can act as that node; it must be stored where only the node can read
+/// it, and never sent over the network.
+impl Encodable for Pmid {
+  fn encode<E: Encoder>(&self, e: &mut E)->Result<(), E::Error> {
+    let (crypto::sign::PublicKey(pub_sign_vec), crypto::asymmetricbox::PublicKey(pub_asym_vec)) = self.public_keys;
+    let (crypto::sign::SecretKey(sec_sign_vec), crypto::asymmetricbox::SecretKey(sec_asym_vec)) = self.secret_keys;
+    CborTagEncode::new(5483_001, &(pub_sign_vec.as_ref(), pub_asym_vec.as_ref(),
+                                   sec_sign_vec.as_ref(), sec_asym_vec.as_ref(),
+                                   &self.validation_token, &self.name)).encode(e)
+  }
+}
+
+impl Decodable for Pmid {
+  fn decode<D: Decoder>(d: &mut D)->Result<Pmid, D::Error> {
+    try!(d.read_u64());
+    let (pub_sign_vec, pub_asym_vec, sec_sign_vec, sec_asym_vec, validation_token, name)
+        : (Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>, Signature, NameType) = try!(Decodable::decode(d));
+
+    let pub_sign_arr = container_of_u8_to_array!(pub_sign_vec, crypto::sign::PUBLICKEYBYTES);
+    let pub_asym_arr =
+        container_of_u8_to_array!(pub_asym_vec, crypto::asymmetricbox::PUBLICKEYBYTES);
+    let sec_sign_arr = container_of_u8_to_array!(sec_sign_vec, crypto::sign::SECRETKEYBYTES);
+    let sec_asym_arr =
+        container_of_u8_to_array!(sec_asym_vec, crypto::asymmetricbox::SECRETKEYBYTES);
+
+    if pub_sign_arr.is_none() || pub_asym_arr.is_none() || sec_sign_arr.is_none() || sec_asym_arr.is_none() {
+        return Err(d.error("Bad Pmid size"));
+    }
+
+    Ok(Pmid {
+      public_keys : (crypto::sign::PublicKey(pub_sign_arr.unwrap()),
+                     crypto::asymmetricbox::PublicKey(pub_asym_arr.unwrap())),
+      secret_keys : (crypto::sign::SecretKey(sec_sign_arr.unwrap()),
+                     crypto::asymmetricbox::SecretKey(sec_asym_arr.unwrap())),
+      validation_token : validation_token,
+      name : name
+    })
+  }
+}
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
 pub struct AccountTransferInfo {
   pub name : NameType
@@ -392,6 +437,24 @@ impl Decodable for SourceAddress {
   }
 }
 
+/// Whom a message we originate is for
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum DestinationKind {
+  /// the node with this id
+  Node(NameType),
+  /// the close group of this name, i.e. the nodes closest to it
+  Group(NameType),
+}
+
+impl DestinationKind {
+  pub fn name(&self) -> &NameType {
+    match *self {
+      DestinationKind::Node(ref name) => name,
+      DestinationKind::Group(ref name) => name,
+    }
+  }
+}
+
 /// Address of the destination of the message
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
 pub struct DestinationAddress {
@@ -419,6 +482,8 @@ impl Encodable for ResponseError {
         match *self {
             ResponseError::NoData => type_tag = "NoData",
             ResponseError::InvalidRequest => type_tag = "InvalidRequest",
+            ResponseError::TooLarge => type_tag = "TooLarge",
+            ResponseError::Timeout => type_tag = "Timeout",
         };
         CborTagEncode::new(5483_100, &(&type_tag)).encode(e)
     }
@@ -432,6 +497,8 @@ impl Decodable for ResponseError {
         match &type_tag[..] {
             "NoData" => Ok(ResponseError::NoData),
             "InvalidRequest" => Ok(ResponseError::InvalidRequest),
+            "TooLarge" => Ok(ResponseError::TooLarge),
+            "Timeout" => Ok(ResponseError::Timeout),
             _ => Err(d.error("Unrecognised ResponseError"))
         }
     }
@@ -485,6 +552,22 @@ mod test {
                                 reply_to: None });
   }
 
+#[test]
+    fn serialisation_pmid() {
+        let obj_before = Pmid::new();
+
+        let mut e = cbor::Encoder::from_memory();
+        e.encode(&[&obj_before]).unwrap();
+
+        let mut d = cbor::Decoder::from_bytes(e.as_bytes());
+        let obj_after: Pmid = d.decode().next().unwrap().unwrap();
+        assert_eq!(PublicPmid::new(&obj_before), PublicPmid::new(&obj_after));
+        assert_eq!(obj_before.get_crypto_secret_sign_key().0.to_vec(),
+                   obj_after.get_crypto_secret_sign_key().0.to_vec());
+        assert_eq!(obj_before.get_crypto_secret_key().0.to_vec(),
+                   obj_after.get_crypto_secret_key().0.to_vec());
+    }
+
 #[test]
     fn serialisation_public_pmid() {
         let obj_before = PublicPmid::generate_random();